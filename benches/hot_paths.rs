@@ -0,0 +1,30 @@
+//! Criterion benchmarks of the paths every cached run goes through
+//!
+//! Run with `cargo bench`. The fixtures are shared with `cacher bench`, which
+//! checks the same paths against the budgets in [`cacher::bench::Benchmark`].
+
+use cacher::bench::{ArtifactRestore, Benchmark, HitServing, KeyGeneration};
+use criterion::{criterion_group, criterion_main, Criterion};
+use std::hint::black_box;
+
+fn key_generation(c: &mut Criterion) {
+    let fixture = KeyGeneration::new();
+    c.bench_function(Benchmark::KeyGeneration.name(), |b| b.iter(|| black_box(fixture.run())));
+}
+
+fn hit_serving(c: &mut Criterion) {
+    let fixture = HitServing::new().expect("Failed to set up the hit serving fixture");
+    c.bench_function(Benchmark::HitServing.name(), |b| {
+        b.iter(|| black_box(fixture.run().expect("The cached output wasn't served")))
+    });
+}
+
+fn artifact_restore(c: &mut Criterion) {
+    let fixture = ArtifactRestore::new().expect("Failed to set up the artifact restore fixture");
+    c.bench_function(Benchmark::ArtifactRestore.name(), |b| {
+        b.iter(|| black_box(fixture.run().expect("Failed to restore the artifact")))
+    });
+}
+
+criterion_group!(benches, key_generation, hit_serving, artifact_restore);
+criterion_main!(benches);
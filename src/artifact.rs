@@ -1,8 +1,12 @@
 use std::path::{Path, PathBuf};
 use std::fs;
-use std::io;
-use std::process::Command;
+use std::io::{self, Read, Write};
+use std::process::{Command, Stdio};
+use std::collections::BTreeMap;
 use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use crate::crypto::{self, Cipher};
+use crate::ignore::IgnoreRules;
 
 /// Types of artifacts that can be cached
 #[derive(Debug, Deserialize, Serialize, Clone)]
@@ -25,23 +29,71 @@ pub enum ArtifactType {
 }
 
 /// Handles caching and restoring of artifacts
+#[derive(Clone)]
 pub struct ArtifactManager {
     base_dir: PathBuf,
+    cipher: Option<Cipher>,
+    force_restore: bool,
+    ignore: IgnoreRules,
+}
+
+/// The files in a cached directory and their content hashes
+///
+/// Stored next to the archive as `manifest.json`, so a restore can be skipped
+/// when the directory on disk already matches what was cached.
+#[derive(Debug, Deserialize, Serialize, Clone, PartialEq, Default)]
+pub struct ArtifactManifest {
+    /// SHA-256 of each file, or `symlink:<target>` for symlinks, keyed by
+    /// path relative to the directory
+    pub files: BTreeMap<String, String>,
 }
 
 impl ArtifactManager {
     /// Create a new ArtifactManager
     pub fn new(base_dir: PathBuf) -> Self {
-        ArtifactManager { base_dir }
+        ArtifactManager { base_dir, cipher: None, force_restore: false, ignore: IgnoreRules::default() }
+    }
+    
+    /// Create a copy of this manager that encrypts the archives it writes
+    ///
+    /// Encrypted archives can only be restored by a manager with the same key.
+    pub fn with_cipher(&self, cipher: Cipher) -> Self {
+        ArtifactManager {
+            cipher: Some(cipher),
+            ..self.clone()
+        }
+    }
+    
+    /// Always re-extract artifacts on restore, even if they are already up to date
+    pub fn set_force_restore(&mut self, force_restore: bool) {
+        self.force_restore = force_restore;
+    }
+    
+    /// Leave the paths these rules ignore out of cached directories
+    ///
+    /// Ignored files are neither archived nor compared on restore, so junk in
+    /// a restored directory doesn't force it to be extracted again.
+    pub fn set_ignore(&mut self, ignore: IgnoreRules) {
+        self.ignore = ignore;
     }
     
     /// Get the path where artifacts for a specific cache ID are stored
     pub fn get_artifacts_path(&self, cache_id: &str) -> PathBuf {
         let artifacts_dir = self.base_dir.join(cache_id).join("artifacts");
-        fs::create_dir_all(&artifacts_dir).unwrap_or_else(|_| {});
+        fs::create_dir_all(&artifacts_dir).unwrap_or(());
         artifacts_dir
     }
     
+    /// Whether the archive stored for a cache ID is encrypted
+    pub fn has_encrypted_archive(&self, cache_id: &str) -> bool {
+        let archive_path = self.base_dir.join(cache_id).join("artifacts").join("directory.tar.gz");
+        let mut header = [0u8; 16];
+        
+        fs::File::open(archive_path)
+            .and_then(|mut file| file.read(&mut header))
+            .is_ok_and(|read| crypto::is_encrypted(&header[..read]))
+    }
+    
     /// Cache a directory artifact
     pub fn cache_directory(&self, dir_path: &Path, cache_id: &str) -> io::Result<()> {
         let artifacts_dir = self.get_artifacts_path(cache_id);
@@ -55,25 +107,62 @@ impl ArtifactManager {
             ));
         }
         
+        // Record what the directory contains so unchanged restores can be skipped
+        let manifest = ArtifactManifest::from_dir(dir_path, &self.ignore)?;
+        self.write_manifest(&manifest, cache_id)?;
+        
         // Create tar.gz of the directory
         let dir_name = dir_path.file_name().unwrap_or_default().to_string_lossy();
         let parent_dir = dir_path.parent().unwrap_or_else(|| Path::new("."));
         
-        let tar_cmd = format!(
-            "tar -czf {} -C {} {}", 
-            archive_path.display(),
-            parent_dir.display(),
-            dir_name
-        );
+        // With ignored paths, tar is given every other path instead of the whole directory
+        let member_list = if self.ignore.is_empty() {
+            None
+        } else {
+            let mut list = tempfile::NamedTempFile::new()?;
+            for member in archive_members(dir_path, &dir_name, &self.ignore)? {
+                list.write_all(member.as_bytes())?;
+                list.write_all(b"\0")?;
+            }
+            Some(list)
+        };
+        let mut members: Vec<std::ffi::OsString> = match &member_list {
+            Some(list) => vec!["--null".into(), "--no-recursion".into(), "-T".into(), list.path().into()],
+            None => vec![dir_name.as_ref().into()],
+        };
         
-        let output = Command::new("sh")
-            .arg("-c")
-            .arg(&tar_cmd)
+        // Encrypted archives are built in memory so the plaintext never touches the disk
+        if let Some(cipher) = &self.cipher {
+            let output = Command::new("tar")
+                .arg("-czf")
+                .arg("-")
+                .arg("-C")
+                .arg(parent_dir)
+                .args(&members)
+                .output()?;
+            
+            if !output.status.success() {
+                return Err(io::Error::other(
+                    format!(
+                        "Failed to create archive: {}", 
+                        String::from_utf8_lossy(&output.stderr)
+                    )
+                ));
+            }
+            
+            let encrypted = cipher.encrypt(&output.stdout, &archive_context(cache_id))?;
+            return fs::write(&archive_path, encrypted);
+        }
+        
+        let mut args: Vec<std::ffi::OsString> = vec!["-czf".into(), archive_path.into(), "-C".into(), parent_dir.into()];
+        args.append(&mut members);
+        
+        let output = Command::new("tar")
+            .args(&args)
             .output()?;
             
         if !output.status.success() {
-            return Err(io::Error::new(
-                io::ErrorKind::Other,
+            return Err(io::Error::other(
                 format!(
                     "Failed to create archive: {}", 
                     String::from_utf8_lossy(&output.stderr)
@@ -94,14 +183,38 @@ impl ArtifactManager {
             return Ok(false);
         }
         
+        // Leave the directory alone if it already matches the cached contents
+        if !self.force_restore && dir_path.is_dir() {
+            if let Some(manifest) = self.read_manifest(cache_id) {
+                if ArtifactManifest::from_dir(dir_path, &self.ignore).is_ok_and(|current| current == manifest) {
+                    return Ok(true);
+                }
+            }
+        }
+        
         // Get the parent directory where we'll extract
         let parent_dir = dir_path.parent().unwrap_or_else(|| Path::new("."));
         
+        let archive = fs::read(&archive_path)?;
+        let decrypted = if crypto::is_encrypted(&archive) {
+            let cipher = self.cipher.as_ref().ok_or_else(|| io::Error::new(
+                io::ErrorKind::PermissionDenied,
+                "Archive is encrypted but no encryption key is configured"
+            ))?;
+            Some(cipher.decrypt(&archive, &archive_context(cache_id))?)
+        } else {
+            None
+        };
+        
         // Remove the directory if it exists to ensure clean extraction
         if dir_path.exists() {
             fs::remove_dir_all(dir_path)?;
         }
         
+        if let Some(decrypted) = decrypted {
+            return extract_from_memory(&decrypted, parent_dir).map(|_| true);
+        }
+        
         // Extract directory from archive
         let extract_cmd = format!(
             "tar -xzf {} -C {}", 
@@ -118,8 +231,7 @@ impl ArtifactManager {
             
         if !output.status.success() {
             println!("Extraction failed: {}", String::from_utf8_lossy(&output.stderr));
-            return Err(io::Error::new(
-                io::ErrorKind::Other,
+            return Err(io::Error::other(
                 format!(
                     "Failed to extract archive: {}", 
                     String::from_utf8_lossy(&output.stderr)
@@ -133,6 +245,33 @@ impl ArtifactManager {
         Ok(true)
     }
     
+    /// Write the manifest for a cache ID's directory artifact, encrypting it along with the archive
+    fn write_manifest(&self, manifest: &ArtifactManifest, cache_id: &str) -> io::Result<()> {
+        let manifest_path = self.get_artifacts_path(cache_id).join("manifest.json");
+        let content = serde_json::to_vec(manifest)
+            .map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))?;
+        
+        match &self.cipher {
+            Some(cipher) => fs::write(manifest_path, cipher.encrypt(&content, &manifest_context(cache_id))?),
+            None => fs::write(manifest_path, content),
+        }
+    }
+    
+    /// Read the manifest for a cache ID's directory artifact
+    ///
+    /// Returns None if there is no manifest, e.g. for entries cached before
+    /// manifests were recorded, or it can't be read.
+    fn read_manifest(&self, cache_id: &str) -> Option<ArtifactManifest> {
+        let content = fs::read(self.base_dir.join(cache_id).join("artifacts").join("manifest.json")).ok()?;
+        let content = if crypto::is_encrypted(&content) {
+            self.cipher.as_ref()?.decrypt(&content, &manifest_context(cache_id)).ok()?
+        } else {
+            content
+        };
+        
+        serde_json::from_slice(&content).ok()
+    }
+    
     /// Cache an artifact based on its type
     pub fn cache_artifact(&self, artifact: &ArtifactType, cache_id: &str, base_dir: &Path) -> io::Result<()> {
         match artifact {
@@ -157,3 +296,110 @@ impl ArtifactManager {
         }
     }
 }
+
+impl ArtifactManifest {
+    /// Hash every file under `dir` that `ignore` doesn't ignore
+    pub fn from_dir(dir: &Path, ignore: &IgnoreRules) -> io::Result<Self> {
+        let mut manifest = ArtifactManifest::default();
+        manifest.add_dir(dir, dir, ignore)?;
+        Ok(manifest)
+    }
+    
+    fn add_dir(&mut self, root: &Path, dir: &Path, ignore: &IgnoreRules) -> io::Result<()> {
+        for entry in fs::read_dir(dir)? {
+            let entry = entry?;
+            let path = entry.path();
+            let file_type = entry.file_type()?;
+            if ignore.is_ignored(&path, file_type.is_dir()) {
+                continue;
+            }
+            
+            let relative_path = path
+                .strip_prefix(root)
+                .unwrap_or(&path)
+                .to_string_lossy()
+                .replace('\\', "/");
+            
+            if file_type.is_symlink() {
+                let target = fs::read_link(&path)?;
+                self.files.insert(relative_path, format!("symlink:{}", target.display()));
+            } else if file_type.is_dir() {
+                self.add_dir(root, &path, ignore)?;
+            } else {
+                let mut hasher = Sha256::new();
+                io::copy(&mut fs::File::open(&path)?, &mut hasher)?;
+                self.files.insert(relative_path, format!("{:x}", hasher.finalize()));
+            }
+        }
+        
+        Ok(())
+    }
+}
+
+/// The paths to archive for a directory, relative to its parent, leaving out ignored ones
+///
+/// Directories are listed along with their contents, since tar is run
+/// without recursing into them.
+fn archive_members(dir: &Path, dir_name: &str, ignore: &IgnoreRules) -> io::Result<Vec<String>> {
+    let mut members = vec![dir_name.to_string()];
+    add_archive_members(dir, dir_name, ignore, &mut members)?;
+    Ok(members)
+}
+
+fn add_archive_members(dir: &Path, prefix: &str, ignore: &IgnoreRules, members: &mut Vec<String>) -> io::Result<()> {
+    for entry in fs::read_dir(dir)? {
+        let entry = entry?;
+        let path = entry.path();
+        let is_dir = entry.file_type()?.is_dir();
+        if ignore.is_ignored(&path, is_dir) {
+            continue;
+        }
+        
+        let member = format!("{}/{}", prefix, entry.file_name().to_string_lossy());
+        members.push(member.clone());
+        if is_dir {
+            add_archive_members(&path, &member, ignore, members)?;
+        }
+    }
+    
+    Ok(())
+}
+
+/// Context an entry's encrypted manifest is bound to
+fn manifest_context(cache_id: &str) -> String {
+    format!("{}/artifacts/manifest.json", cache_id)
+}
+
+/// Context an entry's encrypted archive is bound to
+fn archive_context(cache_id: &str) -> String {
+    format!("{}/artifacts/directory.tar.gz", cache_id)
+}
+
+/// Extract a tar.gz archive held in memory into `parent_dir`
+fn extract_from_memory(archive: &[u8], parent_dir: &Path) -> io::Result<()> {
+    let mut child = Command::new("tar")
+        .arg("-xzf")
+        .arg("-")
+        .arg("-C")
+        .arg(parent_dir)
+        .stdin(Stdio::piped())
+        .stdout(Stdio::null())
+        .stderr(Stdio::piped())
+        .spawn()?;
+    
+    if let Some(mut stdin) = child.stdin.take() {
+        stdin.write_all(archive)?;
+    }
+    
+    let output = child.wait_with_output()?;
+    if !output.status.success() {
+        return Err(io::Error::other(
+            format!(
+                "Failed to extract archive: {}", 
+                String::from_utf8_lossy(&output.stderr)
+            )
+        ));
+    }
+    
+    Ok(())
+}
@@ -0,0 +1,96 @@
+use std::io;
+use std::sync::Mutex;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::thread;
+use std::time::{Duration, Instant};
+use crate::{CommandCache, CommandFailed};
+
+/// The outcome of one command run by [`CommandCache::run_all`]
+#[derive(Debug)]
+pub struct TaskResult {
+    /// The command that was run
+    pub command: String,
+    /// Whether the output was served from the cache
+    pub cached: bool,
+    /// How long serving or executing the command took
+    pub duration: Duration,
+    /// The command's output, or why it failed
+    pub result: io::Result<String>,
+}
+
+impl TaskResult {
+    /// Whether the command produced output without an error
+    pub fn success(&self) -> bool {
+        self.result.is_ok()
+    }
+}
+
+/// Parse a list of commands, one per line
+///
+/// Blank lines and lines starting with `#` are skipped.
+pub fn parse_task_list(content: &str) -> Vec<String> {
+    content
+        .lines()
+        .map(str::trim)
+        .filter(|line| !line.is_empty() && !line.starts_with('#'))
+        .map(str::to_string)
+        .collect()
+}
+
+/// Run commands on up to `jobs` threads, each through its own copy of `cache`
+///
+/// Results are returned in the order of `commands`, regardless of the order
+/// in which they finished.
+pub(crate) fn run_all(cache: &CommandCache, commands: &[String], ttl: Option<Duration>, force: bool, jobs: usize) -> Vec<TaskResult> {
+    let next = AtomicUsize::new(0);
+    let results: Mutex<Vec<Option<TaskResult>>> = Mutex::new(commands.iter().map(|_| None).collect());
+    let workers = jobs.clamp(1, commands.len().max(1));
+
+    thread::scope(|scope| {
+        for _ in 0..workers {
+            scope.spawn(|| {
+                let mut worker_cache = cache.clone();
+                loop {
+                    let index = next.fetch_add(1, Ordering::SeqCst);
+                    let command = match commands.get(index) {
+                        Some(command) => command,
+                        None => break,
+                    };
+
+                    let result = run_task(&mut worker_cache, command, ttl, force);
+                    results.lock().unwrap()[index] = Some(result);
+                }
+            });
+        }
+    });
+
+    results
+        .into_inner()
+        .unwrap()
+        .into_iter()
+        .flatten()
+        .collect()
+}
+
+/// Run a single command through the cache, noting whether it was a hit
+fn run_task(cache: &mut CommandCache, command: &str, ttl: Option<Duration>, force: bool) -> TaskResult {
+    let started = Instant::now();
+
+    let (cached, result) = match cache.execute_with_decision(command, ttl, force) {
+        Ok(decision) => (decision.is_hit(), Ok(decision.output)),
+        Err(e) => {
+            // A failure cached with `cache_failures` was served, not run
+            let cached = e.get_ref()
+                .and_then(|e| e.downcast_ref::<CommandFailed>())
+                .is_some_and(|failed| failed.cached);
+            (cached, Err(e))
+        },
+    };
+
+    TaskResult {
+        command: command.to_string(),
+        cached,
+        duration: started.elapsed(),
+        result,
+    }
+}
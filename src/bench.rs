@@ -0,0 +1,216 @@
+use std::io;
+use std::time::{Duration, Instant};
+use crate::artifact::{ArtifactManager, ArtifactType};
+use crate::testing::TestEnv;
+use crate::CommandCache;
+
+/// Dependency files hashed by the key generation benchmark
+pub const DEPENDENCY_FILES: usize = 1000;
+
+/// Files in the directory artifact checked by the artifact restore benchmark
+pub const ARTIFACT_FILES: usize = 200;
+
+/// Dependency files of the command served by the hit serving benchmark
+pub const HIT_DEPENDENCY_FILES: usize = 100;
+
+/// The hot paths covered by `cacher bench` and the criterion suite
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Benchmark {
+    KeyGeneration,
+    HitServing,
+    ArtifactRestore,
+}
+
+impl Benchmark {
+    pub const ALL: [Benchmark; 3] = [Benchmark::KeyGeneration, Benchmark::HitServing, Benchmark::ArtifactRestore];
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            Benchmark::KeyGeneration => "key generation",
+            Benchmark::HitServing => "hit serving",
+            Benchmark::ArtifactRestore => "artifact restore",
+        }
+    }
+
+    /// How long one run may take in a release build, as enforced by `cacher bench --assert`
+    ///
+    /// Budgets leave ample headroom over what the paths take on a laptop, so
+    /// only real regressions trip them; tighten one after making its path faster.
+    pub fn budget(&self) -> Duration {
+        match self {
+            Benchmark::KeyGeneration => Duration::from_millis(50),
+            Benchmark::HitServing => Duration::from_millis(20),
+            Benchmark::ArtifactRestore => Duration::from_millis(50),
+        }
+    }
+}
+
+/// Computing the key of a command depending on [`DEPENDENCY_FILES`] files through a glob,
+/// plus a few single files and a lockfile
+pub struct KeyGeneration {
+    _env: TestEnv,
+    cache: CommandCache,
+}
+
+impl KeyGeneration {
+    pub const COMMAND: &'static str = "cargo build";
+
+    pub fn new() -> Self {
+        let env = TestEnv::new();
+        env.write_hint_file(r#"
+commands:
+  - pattern: "cargo build"
+    include_env: ["PATH", "HOME"]
+    depends_on:
+      - files: "src/**/*.rs"
+      - file: "Cargo.toml"
+      - file: "build.rs"
+      - lockfile: auto
+"#);
+        for i in 0..DEPENDENCY_FILES {
+            env.write_file(format!("src/module_{}/file_{}.rs", i % 20, i), format!("pub fn f{}() {{}}\n", i));
+        }
+        env.write_file("Cargo.toml", "[package]\nname = \"bench\"\n");
+        env.write_file("build.rs", "fn main() {}\n");
+        env.write_file("Cargo.lock", "version = 3\n".repeat(500));
+
+        let cache = env.cache();
+        KeyGeneration { _env: env, cache }
+    }
+
+    pub fn run(&self) -> String {
+        self.cache.generate_id(Self::COMMAND)
+    }
+}
+
+impl Default for KeyGeneration {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Serving a cached 1 MB output from disk, as a fresh `cacher run` process would,
+/// for a command depending on [`HIT_DEPENDENCY_FILES`] files, so that computing
+/// its key more than once per hit shows up
+pub struct HitServing {
+    _env: TestEnv,
+    cache: CommandCache,
+}
+
+impl HitServing {
+    pub const COMMAND: &'static str = "cat output.txt";
+
+    pub fn new() -> io::Result<Self> {
+        let env = TestEnv::new();
+        env.write_hint_file(r#"
+commands:
+  - pattern: "cat *"
+    ttl: 3600
+    depends_on:
+      - files: "templates/*.txt"
+      - file: "output.txt"
+"#);
+        env.write_file("output.txt", "cached output line\n".repeat(1024 * 1024 / 19));
+        for i in 0..HIT_DEPENDENCY_FILES {
+            env.write_file(format!("templates/part_{}.txt", i), format!("part {}\n", i));
+        }
+
+        let mut cache = env.cache();
+        cache.execute_and_cache(Self::COMMAND, None, false)?;
+
+        // Start from a cache that hasn't seen the command, so each run reads the disk
+        let cache = env.cache();
+        Ok(HitServing { _env: env, cache })
+    }
+
+    pub fn run(&self) -> Option<String> {
+        self.cache.clone().get_cached(Self::COMMAND, None)
+    }
+}
+
+/// Checking a cached directory artifact of [`ARTIFACT_FILES`] files against the
+/// project on a hit, where the directory is unchanged and left alone
+pub struct ArtifactRestore {
+    env: TestEnv,
+    manager: ArtifactManager,
+    artifact: ArtifactType,
+}
+
+impl ArtifactRestore {
+    const ID: &'static str = "artifact-restore-bench";
+
+    pub fn new() -> io::Result<Self> {
+        let env = TestEnv::new();
+        for i in 0..ARTIFACT_FILES {
+            env.write_file(format!("dist/chunk_{}.js", i), format!("console.log({});\n", i).repeat(50));
+        }
+
+        let manager = ArtifactManager::new(env.cache_dir().to_path_buf());
+        let artifact = ArtifactType::Directory { path: "dist".to_string() };
+        manager.cache_artifact(&artifact, Self::ID, env.project_dir())?;
+
+        Ok(ArtifactRestore { env, manager, artifact })
+    }
+
+    pub fn run(&self) -> io::Result<bool> {
+        self.manager.restore_artifact(&self.artifact, Self::ID, self.env.project_dir())
+    }
+}
+
+/// How long a benchmark took, against its budget
+#[derive(Debug, Clone, PartialEq)]
+pub struct BenchResult {
+    pub benchmark: Benchmark,
+
+    /// Median time of one run
+    pub median: Duration,
+}
+
+impl BenchResult {
+    pub fn within_budget(&self) -> bool {
+        self.median <= self.benchmark.budget()
+    }
+}
+
+/// Run every benchmark `iterations` times, after one warm-up run
+///
+/// This is the lightweight harness behind `cacher bench`; the criterion
+/// suite in `benches/` runs the same fixtures with statistical analysis.
+pub fn run(iterations: usize) -> io::Result<Vec<BenchResult>> {
+    let key_generation = KeyGeneration::new();
+    let hit_serving = HitServing::new()?;
+    let artifact_restore = ArtifactRestore::new()?;
+
+    let mut results = Vec::new();
+    for benchmark in Benchmark::ALL {
+        let median = match benchmark {
+            Benchmark::KeyGeneration => median_time(iterations, || {
+                key_generation.run();
+                Ok(())
+            })?,
+            Benchmark::HitServing => median_time(iterations, || {
+                hit_serving.run().map(|_| ()).ok_or_else(|| io::Error::other("The cached output wasn't served"))
+            })?,
+            Benchmark::ArtifactRestore => median_time(iterations, || {
+                artifact_restore.run()?.then_some(()).ok_or_else(|| io::Error::other("The artifact wasn't restored"))
+            })?,
+        };
+        results.push(BenchResult { benchmark, median });
+    }
+
+    Ok(results)
+}
+
+fn median_time(iterations: usize, mut run: impl FnMut() -> io::Result<()>) -> io::Result<Duration> {
+    run()?;
+
+    let mut times = Vec::with_capacity(iterations.max(1));
+    for _ in 0..iterations.max(1) {
+        let started = Instant::now();
+        run()?;
+        times.push(started.elapsed());
+    }
+
+    times.sort();
+    Ok(times[times.len() / 2])
+}
@@ -0,0 +1,83 @@
+use std::env;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::time::SystemTime;
+
+/// The program a command runs: its first word, skipping `NAME=value` assignments
+/// that precede it in shell mode
+pub fn program(command: &str) -> Option<&str> {
+    command.split_whitespace().find(|word| !is_assignment(word))
+}
+
+fn is_assignment(word: &str) -> bool {
+    match word.split_once('=') {
+        Some((name, _)) => {
+            !name.is_empty()
+                && !name.starts_with(|c: char| c.is_ascii_digit())
+                && name.chars().all(|c| c.is_ascii_alphanumeric() || c == '_')
+        },
+        None => false,
+    }
+}
+
+/// Find the binary a program name refers to, the way the shell would
+///
+/// Names containing a path separator are taken relative to `current_dir`;
+/// others are looked up in `PATH`, trying the `PATHEXT` extensions on Windows.
+/// Symlinks are followed, so a tool upgraded by repointing a link resolves to
+/// the new binary.
+pub fn resolve(program: &str, current_dir: &Path) -> Option<PathBuf> {
+    let candidate = if program.contains('/') || program.contains(std::path::MAIN_SEPARATOR) {
+        Some(current_dir.join(program)).filter(|path| is_executable(path))
+    } else {
+        let path = env::var_os("PATH")?;
+        env::split_paths(&path)
+            .flat_map(|dir| executable_names(program).into_iter().map(move |name| dir.join(name)))
+            .find(|path| is_executable(path))
+    };
+
+    candidate.and_then(|path| fs::canonicalize(path).ok())
+}
+
+/// Identify a build of a binary by its size and modification time
+///
+/// Cheaper than hashing the binary on every lookup, and any reinstall or
+/// rebuild changes at least the modification time. Binaries inside
+/// `project_root` are identified by their path relative to it, so checkouts
+/// at different locations agree.
+pub fn fingerprint(path: &Path, project_root: &Path) -> Option<String> {
+    let metadata = fs::metadata(path).ok()?;
+    let modified = metadata.modified().ok()?.duration_since(SystemTime::UNIX_EPOCH).ok()?;
+    Some(format!("{}:{}:{}", display_path(path, project_root).display(), metadata.len(), modified.as_nanos()))
+}
+
+/// A resolved binary's path relative to `project_root` if it lies inside it,
+/// otherwise its absolute path
+pub fn display_path(path: &Path, project_root: &Path) -> PathBuf {
+    let root = fs::canonicalize(project_root).unwrap_or_else(|_| project_root.to_path_buf());
+    path.strip_prefix(&root).map(Path::to_path_buf).unwrap_or_else(|_| path.to_path_buf())
+}
+
+#[cfg(not(windows))]
+fn executable_names(program: &str) -> Vec<String> {
+    vec![program.to_string()]
+}
+
+#[cfg(windows)]
+fn executable_names(program: &str) -> Vec<String> {
+    let extensions = env::var("PATHEXT").unwrap_or_else(|_| ".COM;.EXE;.BAT;.CMD".to_string());
+    std::iter::once(program.to_string())
+        .chain(extensions.split(';').filter(|ext| !ext.is_empty()).map(|ext| format!("{}{}", program, ext)))
+        .collect()
+}
+
+#[cfg(unix)]
+fn is_executable(path: &Path) -> bool {
+    use std::os::unix::fs::PermissionsExt;
+    fs::metadata(path).is_ok_and(|metadata| metadata.is_file() && metadata.permissions().mode() & 0o111 != 0)
+}
+
+#[cfg(not(unix))]
+fn is_executable(path: &Path) -> bool {
+    path.is_file()
+}
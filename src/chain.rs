@@ -0,0 +1,269 @@
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use std::thread::{self, JoinHandle};
+use std::time::{Duration, Instant};
+use serde::{Deserialize, Serialize};
+
+/// Environment variable naming the file the instrumented script appends its marks to
+pub const MARKS_ENV: &str = "CACHER_CHAIN_MARKS";
+
+/// How often the marks file is checked while the chain runs
+const POLL_INTERVAL: Duration = Duration::from_millis(5);
+
+/// Words that start shell constructs whose bodies contain `;`, e.g. `for f in *; do ...; done`
+///
+/// Commands using them aren't split, since telling their separators apart
+/// from the chain's would take a real shell parser.
+const COMPOUND_KEYWORDS: &[&str] = &["if", "then", "else", "elif", "fi", "for", "while", "until", "do", "done", "case", "esac", "function"];
+
+/// How a part of a chain is joined to the part before it
+#[derive(Debug, Deserialize, Serialize, Clone, Copy, PartialEq)]
+pub enum Separator {
+    /// `&&`: the part only runs if the previous one succeeded
+    #[serde(rename = "&&")]
+    And,
+    /// `;`: the part runs regardless
+    #[serde(rename = ";")]
+    Then,
+}
+
+impl Separator {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Separator::And => "&&",
+            Separator::Then => ";",
+        }
+    }
+}
+
+/// One command of a chain like `npm ci && npm run build`
+#[derive(Debug, Deserialize, Serialize, Clone, PartialEq)]
+pub struct ChainPart {
+    pub command: String,
+
+    /// How the part is joined to the previous one; None for the first part
+    #[serde(default)]
+    pub separator: Option<Separator>,
+
+    /// Cache ID the part would have on its own, so a later run can tell which parts changed
+    #[serde(default)]
+    pub id: Option<String>,
+
+    /// How long the part ran, in milliseconds; None if it didn't run
+    #[serde(default)]
+    pub duration_ms: Option<u64>,
+
+    /// Exit code of the part; None if it didn't run
+    #[serde(default)]
+    pub exit_code: Option<i32>,
+}
+
+impl ChainPart {
+    /// How long the part ran, if it ran
+    pub fn duration(&self) -> Option<Duration> {
+        self.duration_ms.map(Duration::from_millis)
+    }
+}
+
+/// Split a shell command at its top-level `&&` and `;` separators
+///
+/// Separators inside quotes, parentheses, braces, backticks and comments are
+/// left alone. Commands using compound constructs, heredocs or `||` come back
+/// as a single part, as does any command without separators. `||` is left
+/// out because `a && b || c` can't be run as parts joined by `&&`.
+pub fn split(command: &str) -> Vec<ChainPart> {
+    let whole = || vec![part(command.trim(), None)];
+
+    if has_compound_keyword(command) || command.contains("<<") || command.contains("||") {
+        return whole();
+    }
+
+    let mut parts = Vec::new();
+    let mut current = String::new();
+    let mut separator = None;
+    let mut chars = command.chars().peekable();
+    let (mut single, mut double, mut backtick) = (false, false, false);
+    let mut depth = 0usize;
+    let mut word_start = true;
+
+    while let Some(c) = chars.next() {
+        let top_level = !single && !double && !backtick && depth == 0;
+        let next_separator = match c {
+            '&' if top_level && chars.peek() == Some(&'&') => {
+                chars.next();
+                Some(Separator::And)
+            },
+            ';' | '\n' if top_level => Some(Separator::Then),
+            _ => None,
+        };
+
+        if let Some(next_separator) = next_separator {
+            if !current.trim().is_empty() {
+                parts.push(part(current.trim(), separator));
+            } else if next_separator == Separator::And {
+                // `&&` with nothing before it isn't a chain we understand
+                return whole();
+            }
+            current.clear();
+            separator = Some(next_separator);
+            word_start = true;
+            continue;
+        }
+
+        current.push(c);
+        match c {
+            '\\' if !single => {
+                if let Some(escaped) = chars.next() {
+                    current.push(escaped);
+                }
+            },
+            '\'' if !double && !backtick => single = !single,
+            '"' if !single && !backtick => double = !double,
+            '`' if !single => backtick = !backtick,
+            '(' if !single && !double => depth += 1,
+            ')' if !single && !double => depth = depth.saturating_sub(1),
+            '{' if !single && !double && word_start => depth += 1,
+            '}' if !single && !double && word_start && depth > 0 => depth -= 1,
+            '#' if top_level && word_start => {
+                // The rest of the line is a comment; the newline ending it still separates
+                while let Some(c) = chars.next_if(|c| *c != '\n') {
+                    current.push(c);
+                }
+            },
+            _ => {},
+        }
+        word_start = c.is_whitespace() || c == '(';
+    }
+
+    if !current.trim().is_empty() {
+        parts.push(part(current.trim(), separator));
+    } else if separator == Some(Separator::And) {
+        return whole();
+    }
+
+    if parts.len() < 2 || single || double || backtick || depth > 0 {
+        return whole();
+    }
+    parts
+}
+
+/// Whether a compound keyword appears where a command would, e.g. `for` but not `echo for`
+fn has_compound_keyword(command: &str) -> bool {
+    let mut command_position = true;
+    for word in command.split_whitespace() {
+        let bare = word.trim_start_matches(['(', '{']).trim_end_matches(';');
+        let keyword = COMPOUND_KEYWORDS.contains(&bare);
+        if command_position && keyword {
+            return true;
+        }
+        command_position = keyword || word.ends_with([';', '&', '|', '(', '{']);
+    }
+    false
+}
+
+fn part(command: &str, separator: Option<Separator>) -> ChainPart {
+    ChainPart { command: command.to_string(), separator, id: None, duration_ms: None, exit_code: None }
+}
+
+/// Build a script that runs the parts like the original command, appending
+/// `<index> <exit code>` to the file named by [`MARKS_ENV`] after each part
+///
+/// Everything runs in one shell, so `cd` and variables carry over between
+/// parts. `&&` binds tighter than `;`, as in the shell, and the script exits
+/// with the status of the last part that ran.
+pub fn instrument(parts: &[ChainPart]) -> String {
+    let mut script = String::from("__cacher_status=0\n");
+    let mut open_ifs = 0;
+
+    for (i, part) in parts.iter().enumerate() {
+        match part.separator {
+            Some(Separator::And) => {
+                script.push_str("if [ \"$__cacher_status\" -eq 0 ]; then\n");
+                open_ifs += 1;
+            },
+            Some(Separator::Then) => {
+                script.push_str(&"fi\n".repeat(open_ifs));
+                open_ifs = 0;
+            },
+            None => {},
+        }
+
+        script.push_str(&part.command);
+        script.push('\n');
+        script.push_str(&format!("__cacher_status=$?; echo \"{} $__cacher_status\" >> \"${}\"\n", i, MARKS_ENV));
+    }
+
+    script.push_str(&"fi\n".repeat(open_ifs));
+    script.push_str("(exit \"$__cacher_status\")\n");
+    script
+}
+
+/// Watches the marks file of a running instrumented script, timing each mark as it appears
+pub struct MarkWatcher {
+    stop: Arc<AtomicBool>,
+    handle: JoinHandle<Vec<(usize, i32, Duration)>>,
+}
+
+impl MarkWatcher {
+    pub fn start(path: &Path) -> Self {
+        let stop = Arc::new(AtomicBool::new(false));
+        let path: PathBuf = path.to_path_buf();
+
+        let handle = {
+            let stop = Arc::clone(&stop);
+            thread::spawn(move || {
+                let started = Instant::now();
+                let mut marks = Vec::new();
+                let mut seen_lines = 0;
+
+                loop {
+                    // Check the flag first, so the file is read once more after the script exits
+                    let stopping = stop.load(Ordering::SeqCst);
+
+                    let content = fs::read_to_string(&path).unwrap_or_default();
+                    let complete = content.rfind('\n').map_or("", |end| &content[..end]);
+                    for line in complete.lines().skip(seen_lines) {
+                        seen_lines += 1;
+                        let mut fields = line.split_whitespace();
+                        if let (Some(Ok(index)), Some(Ok(code))) = (fields.next().map(str::parse), fields.next().map(str::parse)) {
+                            marks.push((index, code, started.elapsed()));
+                        }
+                    }
+
+                    if stopping {
+                        return marks;
+                    }
+                    thread::sleep(POLL_INTERVAL);
+                }
+            })
+        };
+
+        MarkWatcher { stop, handle }
+    }
+
+    /// Stop watching once the script has exited
+    ///
+    /// # Returns
+    ///
+    /// The index and exit code of each part that finished, with the time it finished
+    pub fn finish(self) -> Vec<(usize, i32, Duration)> {
+        self.stop.store(true, Ordering::SeqCst);
+        self.handle.join().unwrap_or_default()
+    }
+}
+
+/// Fill in the duration and exit code of each part from the marks of a run
+///
+/// A part's duration runs from the previous mark, or the start, to its own.
+pub fn attribute(parts: &mut [ChainPart], marks: &[(usize, i32, Duration)]) {
+    let mut previous = Duration::ZERO;
+    for (index, code, at) in marks {
+        if let Some(part) = parts.get_mut(*index) {
+            part.duration_ms = Some(at.saturating_sub(previous).as_millis() as u64);
+            part.exit_code = Some(*code);
+        }
+        previous = *at;
+    }
+}
@@ -0,0 +1,114 @@
+use std::env;
+use std::path::{Path, PathBuf};
+use std::collections::HashSet;
+use crate::hint_file::{CommandHint, Dependency};
+
+/// Compose file names looked up by `compose_file: auto`, in the order
+/// `docker compose` prefers them
+pub const COMPOSE_FILES: &[&str] = &[
+    "compose.yaml",
+    "compose.yml",
+    "docker-compose.yaml",
+    "docker-compose.yml",
+];
+
+/// Override files merged into the compose file found by `compose_file: auto`
+pub const OVERRIDE_FILES: &[&str] = &[
+    "compose.override.yaml",
+    "compose.override.yml",
+    "docker-compose.override.yaml",
+    "docker-compose.override.yml",
+];
+
+/// Environment variables that change which project or daemon compose talks to
+pub const COMPOSE_ENV_VARS: &[&str] = &[
+    "COMPOSE_PROJECT_NAME",
+    "COMPOSE_FILE",
+    "COMPOSE_PROFILES",
+    "DOCKER_HOST",
+    "DOCKER_CONTEXT",
+];
+
+/// Resolve a `compose_file:` dependency value to the files it refers to
+///
+/// `auto` follows `docker compose`: the files listed in `COMPOSE_FILE` if it
+/// is set, otherwise the first known compose file in `base_dir` plus its
+/// override file. Any other value is treated as a path relative to `base_dir`.
+///
+/// # Returns
+///
+/// The existing compose files, paired with the name used for them in cache keys
+pub fn resolve(base_dir: &Path, compose_file: &str) -> Vec<(String, PathBuf)> {
+    let names: Vec<String> = if compose_file != "auto" {
+        vec![compose_file.to_string()]
+    } else if let Some(files) = env::var("COMPOSE_FILE").ok().filter(|f| !f.is_empty()) {
+        let separator = env::var("COMPOSE_PATH_SEPARATOR")
+            .unwrap_or_else(|_| if cfg!(windows) { ";" } else { ":" }.to_string());
+        files.split(separator.as_str()).map(str::to_string).collect()
+    } else {
+        let main = COMPOSE_FILES.iter().find(|name| base_dir.join(name).is_file());
+        let overrides = OVERRIDE_FILES.iter().find(|name| base_dir.join(name).is_file());
+        main.into_iter().chain(overrides).map(|name| name.to_string()).collect()
+    };
+
+    names
+        .into_iter()
+        .map(|name| {
+            let path = base_dir.join(&name);
+            (name, path)
+        })
+        .filter(|(_, path)| path.is_file())
+        .collect()
+}
+
+/// Determine the compose project name for `base_dir`
+///
+/// Uses `COMPOSE_PROJECT_NAME` if set, otherwise the directory name
+/// normalized the way `docker compose` does it. A `name:` key inside the
+/// compose file is covered by hashing the file itself.
+pub fn project_name(base_dir: &Path) -> String {
+    if let Some(name) = env::var("COMPOSE_PROJECT_NAME").ok().filter(|n| !n.is_empty()) {
+        return name;
+    }
+
+    let dir_name = base_dir
+        .canonicalize()
+        .unwrap_or_else(|_| base_dir.to_path_buf())
+        .file_name()
+        .map(|n| n.to_string_lossy().to_lowercase())
+        .unwrap_or_default();
+
+    dir_name
+        .chars()
+        .filter(|c| c.is_ascii_alphanumeric() || *c == '_' || *c == '-')
+        .collect()
+}
+
+/// Command hints added by `presets: [docker_compose]`
+///
+/// Covers the read-only `ps`, `config` and `logs --tail` commands for both the
+/// `docker compose` plugin and the standalone `docker-compose` binary, keyed on
+/// the compose file and project name. TTLs are short because container state
+/// changes without any file changing.
+pub fn preset_hints() -> Vec<CommandHint> {
+    let subcommands = [
+        ("ps*", 5),
+        ("config*", 300),
+        ("logs --tail*", 10),
+    ];
+
+    let mut hints = Vec::new();
+    for program in ["docker compose", "docker-compose"] {
+        for (subcommand, ttl) in subcommands {
+            hints.push(CommandHint {
+                pattern: format!("{} {}", program, subcommand),
+                ttl: Some(ttl),
+                include_env: COMPOSE_ENV_VARS.iter().map(|v| v.to_string()).collect::<HashSet<_>>(),
+                depends_on: vec![Dependency::ComposeFile { compose_file: "auto".to_string() }],
+                ..CommandHint::default()
+            });
+        }
+    }
+
+    hints
+}
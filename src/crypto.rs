@@ -0,0 +1,239 @@
+use std::env;
+use std::fs;
+use std::io::{self, Error, ErrorKind, Write};
+use std::path::Path;
+use std::process::{Command, Stdio};
+use chacha20poly1305::aead::{Aead, AeadCore, KeyInit, OsRng, Payload};
+use chacha20poly1305::{Key, XChaCha20Poly1305, XNonce};
+
+/// Environment variable holding the passphrase used to encrypt cached entries
+pub const PASSPHRASE_ENV_VAR: &str = "CACHER_ENCRYPTION_KEY";
+
+/// Prefix written before every encrypted file
+const MAGIC: &[u8] = b"CACHERENC1";
+
+/// Length of an XChaCha20-Poly1305 nonce
+const NONCE_LEN: usize = 24;
+
+/// Shortest salt accepted for passphrase-derived keys
+const MIN_SALT_LEN: usize = 16;
+
+/// Keychain service and account the generated key is stored under
+const KEYCHAIN_SERVICE: &str = "cacher";
+const KEYCHAIN_ACCOUNT: &str = "encryption-key";
+
+/// Encrypts and decrypts cached files with XChaCha20-Poly1305
+///
+/// Every file gets a random nonce, and is bound to a context string (the
+/// entry ID and file name) so an encrypted file can't be swapped into another
+/// entry without failing to decrypt.
+#[derive(Clone)]
+pub struct Cipher {
+    key: Key,
+}
+
+impl Cipher {
+    /// Create a cipher from a raw 256-bit key
+    pub fn from_key(key: [u8; 32]) -> Self {
+        Cipher { key: key.into() }
+    }
+
+    /// Derive a cipher from a passphrase with Argon2id
+    ///
+    /// # Arguments
+    ///
+    /// * `passphrase` - The passphrase to derive the key from
+    /// * `salt` - At least 8 bytes of salt; the same salt gives the same key
+    pub fn from_passphrase(passphrase: &str, salt: &[u8]) -> io::Result<Self> {
+        let mut key = [0u8; 32];
+        argon2::Argon2::default()
+            .hash_password_into(passphrase.as_bytes(), salt, &mut key)
+            .map_err(|e| Error::new(ErrorKind::InvalidInput, format!("Failed to derive encryption key: {}", e)))?;
+        Ok(Cipher::from_key(key))
+    }
+
+    /// Load the cipher configured for this machine
+    ///
+    /// Uses the passphrase in `CACHER_ENCRYPTION_KEY` if it is set, salted with
+    /// a random salt kept in `cache_dir`. Otherwise the key is read from the OS
+    /// keychain (macOS Keychain via `security`, or the Secret Service via
+    /// `secret-tool`), and a new key is generated and stored there on first use.
+    pub fn from_environment(cache_dir: &Path) -> io::Result<Self> {
+        if let Some(passphrase) = env::var(PASSPHRASE_ENV_VAR).ok().filter(|p| !p.is_empty()) {
+            let salt = load_or_create_salt(&cache_dir.join("encryption.salt"))?;
+            return Cipher::from_passphrase(&passphrase, &salt);
+        }
+
+        if let Some(key) = keychain_lookup() {
+            return parse_hex_key(&key).map(Cipher::from_key);
+        }
+
+        let key = XChaCha20Poly1305::generate_key(&mut OsRng);
+        let key_hex = to_hex(&key);
+        if keychain_store(&key_hex) {
+            return Ok(Cipher { key });
+        }
+
+        Err(Error::new(
+            ErrorKind::NotFound,
+            format!(
+                "No encryption key available: set {} or install a keychain (security or secret-tool)",
+                PASSPHRASE_ENV_VAR
+            ),
+        ))
+    }
+
+    /// Encrypt `plaintext`, binding it to `context`
+    pub fn encrypt(&self, plaintext: &[u8], context: &str) -> io::Result<Vec<u8>> {
+        let nonce = XChaCha20Poly1305::generate_nonce(&mut OsRng);
+        let ciphertext = XChaCha20Poly1305::new(&self.key)
+            .encrypt(&nonce, Payload { msg: plaintext, aad: context.as_bytes() })
+            .map_err(|_| Error::other("Encryption failed"))?;
+
+        let mut data = Vec::with_capacity(MAGIC.len() + NONCE_LEN + ciphertext.len());
+        data.extend_from_slice(MAGIC);
+        data.extend_from_slice(&nonce);
+        data.extend_from_slice(&ciphertext);
+        Ok(data)
+    }
+
+    /// Decrypt data produced by [`Cipher::encrypt`] with the same `context`
+    ///
+    /// Fails with `InvalidData` if the data was encrypted with another key or
+    /// context, or has been tampered with.
+    pub fn decrypt(&self, data: &[u8], context: &str) -> io::Result<Vec<u8>> {
+        if !is_encrypted(data) || data.len() < MAGIC.len() + NONCE_LEN {
+            return Err(Error::new(ErrorKind::InvalidData, "Not an encrypted cache file"));
+        }
+
+        let (nonce, ciphertext) = data[MAGIC.len()..].split_at(NONCE_LEN);
+        XChaCha20Poly1305::new(&self.key)
+            .decrypt(XNonce::from_slice(nonce), Payload { msg: ciphertext, aad: context.as_bytes() })
+            .map_err(|_| Error::new(ErrorKind::InvalidData, "Failed to decrypt cached entry (wrong key?)"))
+    }
+}
+
+/// Whether `data` was written by [`Cipher::encrypt`]
+pub fn is_encrypted(data: &[u8]) -> bool {
+    data.starts_with(MAGIC)
+}
+
+/// Read the salt for passphrase-derived keys, creating it on first use
+///
+/// A new salt is written to a temporary file and only published if no other
+/// process published one first, in which case theirs is used. Replacing a
+/// salt would make every entry encrypted with it unreadable, so a malformed
+/// one is an error rather than regenerated.
+fn load_or_create_salt(path: &Path) -> io::Result<Vec<u8>> {
+    match fs::read(path) {
+        Ok(salt) => return check_salt(path, salt),
+        Err(e) if e.kind() != ErrorKind::NotFound => return Err(e),
+        Err(_) => {},
+    }
+
+    let salt = XChaCha20Poly1305::generate_nonce(&mut OsRng).to_vec();
+    let dir = path.parent().unwrap_or_else(|| Path::new("."));
+    fs::create_dir_all(dir)?;
+    let mut file = tempfile::NamedTempFile::new_in(dir)?;
+    file.write_all(&salt)?;
+    file.as_file().sync_all()?;
+    match file.persist_noclobber(path) {
+        Ok(_) => Ok(salt),
+        // Another process created it first
+        Err(e) if e.error.kind() == ErrorKind::AlreadyExists => check_salt(path, fs::read(path)?),
+        Err(e) => Err(e.error),
+    }
+}
+
+fn check_salt(path: &Path, salt: Vec<u8>) -> io::Result<Vec<u8>> {
+    if salt.len() < MIN_SALT_LEN {
+        return Err(Error::new(
+            ErrorKind::InvalidData,
+            format!(
+                "Encryption salt {} is malformed ({} bytes, expected at least {}); restore it, or delete it to give up the entries encrypted with it",
+                path.display(), salt.len(), MIN_SALT_LEN,
+            ),
+        ));
+    }
+    Ok(salt)
+}
+
+/// Look up the stored key in the OS keychain
+fn keychain_lookup() -> Option<String> {
+    let output = if cfg!(target_os = "macos") {
+        Command::new("security")
+            .args(["find-generic-password", "-s", KEYCHAIN_SERVICE, "-a", KEYCHAIN_ACCOUNT, "-w"])
+            .stderr(Stdio::null())
+            .output()
+    } else {
+        Command::new("secret-tool")
+            .args(["lookup", "service", KEYCHAIN_SERVICE, "account", KEYCHAIN_ACCOUNT])
+            .stderr(Stdio::null())
+            .output()
+    };
+
+    let output = output.ok().filter(|output| output.status.success())?;
+    let key = String::from_utf8_lossy(&output.stdout).trim().to_string();
+    if key.is_empty() {
+        None
+    } else {
+        Some(key)
+    }
+}
+
+/// Store a key in the OS keychain, returning whether it worked
+///
+/// The key is passed on stdin, never on the command line, where any local
+/// user could read it from the process list.
+fn keychain_store(key_hex: &str) -> bool {
+    if cfg!(target_os = "macos") {
+        // `security -i` reads its commands from stdin
+        let command = format!(
+            "add-generic-password -s {} -a {} -w {}\n",
+            KEYCHAIN_SERVICE, KEYCHAIN_ACCOUNT, key_hex,
+        );
+        run_with_stdin(Command::new("security").arg("-i"), &command)
+    } else {
+        // secret-tool reads the secret from stdin
+        run_with_stdin(
+            Command::new("secret-tool")
+                .args(["store", "--label", "cacher encryption key", "service", KEYCHAIN_SERVICE, "account", KEYCHAIN_ACCOUNT]),
+            key_hex,
+        )
+    }
+}
+
+/// Run a command with `input` on its stdin, returning whether it succeeded
+fn run_with_stdin(command: &mut Command, input: &str) -> bool {
+    let child = command
+        .stdin(Stdio::piped())
+        .stdout(Stdio::null())
+        .stderr(Stdio::null())
+        .spawn();
+
+    match child {
+        Ok(mut child) => {
+            let written = child.stdin.take().is_some_and(|mut stdin| stdin.write_all(input.as_bytes()).is_ok());
+            child.wait().is_ok_and(|status| status.success()) && written
+        },
+        Err(_) => false,
+    }
+}
+
+fn parse_hex_key(hex: &str) -> io::Result<[u8; 32]> {
+    let invalid = || Error::new(ErrorKind::InvalidData, "Keychain entry is not a 256-bit hex key");
+    // Checking every byte first also keeps the slicing below on character boundaries
+    if hex.len() != 64 || !hex.bytes().all(|b| b.is_ascii_hexdigit()) {
+        return Err(invalid());
+    }
+
+    let mut key = [0u8; 32];
+    for (i, byte) in key.iter_mut().enumerate() {
+        *byte = u8::from_str_radix(&hex[i * 2..i * 2 + 2], 16).map_err(|_| invalid())?;
+    }
+    Ok(key)
+}
+
+fn to_hex(bytes: &[u8]) -> String {
+    bytes.iter().map(|b| format!("{:02x}", b)).collect()
+}
@@ -0,0 +1,55 @@
+use similar::TextDiff;
+use crate::CommandOutput;
+
+/// Differences between a cached command result and a fresh run
+#[derive(Debug, Clone, PartialEq)]
+pub struct CommandDiff {
+    /// Unified diff of stdout, or None if it is unchanged
+    pub stdout: Option<String>,
+
+    /// Unified diff of stderr, or None if it is unchanged
+    pub stderr: Option<String>,
+
+    /// Cached and fresh exit codes, or None if they match
+    pub exit_code: Option<(i32, i32)>,
+}
+
+impl CommandDiff {
+    /// Compare a cached result with a fresh one
+    ///
+    /// # Arguments
+    ///
+    /// * `cached` - The output stored in the cache
+    /// * `fresh` - The output of running the command again
+    pub fn between(cached: &CommandOutput, fresh: &CommandOutput) -> Self {
+        CommandDiff {
+            stdout: unified_diff(&cached.stdout, &fresh.stdout, "stdout"),
+            stderr: unified_diff(&cached.stderr, &fresh.stderr, "stderr"),
+            exit_code: if cached.exit_code != fresh.exit_code {
+                Some((cached.exit_code, fresh.exit_code))
+            } else {
+                None
+            },
+        }
+    }
+
+    /// Whether the fresh run reproduced the cached result exactly
+    pub fn is_identical(&self) -> bool {
+        self.stdout.is_none() && self.stderr.is_none() && self.exit_code.is_none()
+    }
+}
+
+/// Produce a unified diff between two texts, or None if they are equal
+fn unified_diff(cached: &str, fresh: &str, stream: &str) -> Option<String> {
+    if cached == fresh {
+        return None;
+    }
+
+    let diff = TextDiff::from_lines(cached, fresh)
+        .unified_diff()
+        .context_radius(3)
+        .header(&format!("cached/{}", stream), &format!("fresh/{}", stream))
+        .to_string();
+
+    Some(diff)
+}
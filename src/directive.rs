@@ -0,0 +1,33 @@
+use std::time::Duration;
+
+/// Prefix of the line a command prints to set the TTL of its entry, e.g. `#cacher: ttl=300`
+pub const DIRECTIVE_PREFIX: &str = "#cacher:";
+
+/// Take a TTL directive off the end of a command's output
+///
+/// The directive has to be the last line of the output. Its value is in
+/// seconds, or has a unit like `ttl=10m`. Lines that merely look like a
+/// directive but don't parse are left in the output.
+///
+/// # Returns
+///
+/// The output without the directive line, and the TTL it set; None if the
+/// output doesn't end with a directive
+pub fn take_ttl(stdout: &str) -> Option<(String, Duration)> {
+    let body = stdout.strip_suffix('\n').unwrap_or(stdout);
+    let body = body.strip_suffix('\r').unwrap_or(body);
+    let start = body.rfind('\n').map_or(0, |i| i + 1);
+
+    let value = body[start..]
+        .trim()
+        .strip_prefix(DIRECTIVE_PREFIX)?
+        .trim()
+        .strip_prefix("ttl=")?
+        .trim();
+    let ttl = match value.parse::<u64>() {
+        Ok(seconds) => Duration::from_secs(seconds),
+        Err(_) => humantime::parse_duration(value).ok()?,
+    };
+
+    Some((stdout[..start].to_string(), ttl))
+}
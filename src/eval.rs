@@ -0,0 +1,84 @@
+use std::io::{self, Error, ErrorKind};
+use std::path::Path;
+use std::process::Command;
+use serde::{Deserialize, Serialize};
+
+/// How `cacher eval` runs snippets for a language
+///
+/// Configured under `interpreters:` in the hint file, keyed by language name;
+/// `python`, `node`, `ruby` and `perl` work without any configuration.
+#[derive(Debug, Deserialize, Serialize, Clone, PartialEq)]
+pub struct Interpreter {
+    /// Program and arguments the snippet is passed after, e.g. `["python3", "-c"]`
+    pub command: Vec<String>,
+
+    /// Arguments that make the program print its version, which is part of the cache key
+    #[serde(default = "default_version_args")]
+    pub version_args: Vec<String>,
+
+    /// Glob patterns of files the snippets import; changing them invalidates the cache
+    #[serde(default)]
+    pub imports: Vec<String>,
+}
+
+fn default_version_args() -> Vec<String> {
+    vec!["--version".to_string()]
+}
+
+impl Interpreter {
+    /// The built-in interpreter for a language, if there is one
+    pub fn builtin(lang: &str) -> Option<Self> {
+        let command: &[&str] = match lang {
+            "python" => &["python3", "-c"],
+            "node" | "javascript" => &["node", "-e"],
+            "ruby" => &["ruby", "-e"],
+            "perl" => &["perl", "-e"],
+            _ => return None,
+        };
+
+        Some(Interpreter {
+            command: command.iter().map(|arg| arg.to_string()).collect(),
+            version_args: default_version_args(),
+            imports: Vec::new(),
+        })
+    }
+
+    /// The command line a snippet runs as, used for display and hint file matching
+    pub fn display_command(&self, snippet: &str) -> String {
+        format!("{} {}", self.command.join(" "), snippet)
+    }
+
+    /// Build the process that runs a snippet in `dir`
+    pub fn snippet_process(&self, snippet: &str, dir: &Path) -> io::Result<Command> {
+        let (program, args) = self.command.split_first().ok_or_else(|| {
+            Error::new(ErrorKind::InvalidInput, "Interpreter command is empty")
+        })?;
+
+        let mut process = Command::new(program);
+        process.args(args).arg(snippet).current_dir(dir);
+        Ok(process)
+    }
+
+    /// Ask the interpreter for its version
+    ///
+    /// Some interpreters print their version to stderr, so both streams are used.
+    pub fn version(&self, dir: &Path) -> io::Result<String> {
+        let program = self.command.first().ok_or_else(|| {
+            Error::new(ErrorKind::InvalidInput, "Interpreter command is empty")
+        })?;
+
+        let output = Command::new(program)
+            .args(&self.version_args)
+            .current_dir(dir)
+            .output()
+            .map_err(|e| Error::new(e.kind(), format!("Failed to run {}: {}", program, e)))?;
+
+        if !output.status.success() {
+            return Err(Error::other(format!("Failed to get the version of {}", program)));
+        }
+
+        let mut version = String::from_utf8_lossy(&output.stdout).trim().to_string();
+        version.push_str(String::from_utf8_lossy(&output.stderr).trim());
+        Ok(version)
+    }
+}
@@ -1,10 +1,19 @@
-use std::path::Path;
+use std::path::{Path, PathBuf};
 use std::fs;
-use std::collections::HashSet;
+use std::time::Duration;
+use std::collections::{BTreeMap, HashSet};
 use serde::{Deserialize, Serialize};
 use glob::Pattern;
 use anyhow::{Result, Context};
 use crate::artifact::ArtifactType;
+use crate::normalize::NormalizeRule;
+use crate::hooks::{Hook, HookStage};
+use crate::eval::Interpreter;
+use crate::ignore::IgnoreRules;
+use crate::remote::{RemoteSettings, WritePolicy};
+
+/// Names a hint file can have, in the order they are looked for in each directory
+pub const HINT_FILE_NAMES: &[&str] = &[".cacher", ".cacher.yaml", ".cacher.yml"];
 
 /// Represents a .cacher hint file that configures caching behavior
 ///
@@ -20,6 +29,41 @@ pub struct HintFile {
     /// Command-specific settings that override defaults
     #[serde(default)]
     pub commands: Vec<CommandHint>,
+    
+    /// Built-in command hints to add after `commands`
+    #[serde(default)]
+    pub presets: Vec<Preset>,
+    
+    /// Commands run by `cacher run-all` when no list is given
+    #[serde(default)]
+    pub tasks: Vec<String>,
+    
+    /// Interpreters `cacher eval` can run snippets with, keyed by language
+    #[serde(default)]
+    pub interpreters: BTreeMap<String, Interpreter>,
+    
+    /// Shared store entries are copied to and fetched from
+    pub remote: Option<RemoteSettings>,
+}
+
+/// A named set of built-in command hints
+#[derive(Debug, Deserialize, Serialize, Clone, Copy, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub enum Preset {
+    /// Read-only `docker compose` commands such as `ps`, `config` and `logs --tail`
+    DockerCompose,
+    /// Slow, read-only PowerShell cmdlets such as `Get-Module -ListAvailable` and `Get-ComputerInfo`
+    Powershell,
+}
+
+impl Preset {
+    /// The command hints this preset adds
+    pub fn hints(&self) -> Vec<CommandHint> {
+        match self {
+            Preset::DockerCompose => crate::compose::preset_hints(),
+            Preset::Powershell => crate::powershell::preset_hints(),
+        }
+    }
 }
 
 /// Default settings that apply to all commands
@@ -28,13 +72,87 @@ pub struct DefaultSettings {
     /// Default time-to-live in seconds for cached entries
     pub ttl: Option<u64>,
     
+    /// Default time-to-live in seconds depending on the exit code, taking precedence over `ttl`
+    pub ttl_by_exit: Option<TtlByExit>,
+    
+    /// Cache the output of commands that exit with a non-zero status
+    #[serde(default)]
+    pub cache_failures: bool,
+    
+    /// Let commands set the TTL of their entry with a trailing `#cacher: ttl=N` line
+    #[serde(default)]
+    pub ttl_from_output: bool,
+    
+    /// Number of earlier outputs to keep for each command
+    #[serde(default)]
+    pub history: usize,
+    
+    /// Run commands through the shell, so pipes, `&&` and `;` work
+    #[serde(default)]
+    pub shell: bool,
+    
+    /// Match dependency globs and hash dependency paths without regard to case,
+    /// for projects shared with case-insensitive filesystems
+    #[serde(default)]
+    pub case_insensitive_keys: bool,
+    
     /// Environment variables to include in the cache key
     #[serde(default)]
     pub include_env: HashSet<String>,
+    
+    /// Environment variables that must be set for any command to run
+    #[serde(default)]
+    pub require_env: HashSet<String>,
+    
+    /// Include the time zone and locale (TZ, LANG, LC_ALL) in the cache key
+    #[serde(default)]
+    pub include_locale: bool,
+    
+    /// Include the binary the command's program resolves to in the cache key
+    #[serde(default)]
+    pub include_binary: bool,
+    
+    /// Share entries with other checkouts of the same git repository (default: true)
+    pub share_across_checkouts: Option<bool>,
+    
+    /// Salt cache keys with this key schema version instead of the current one
+    pub key_schema: Option<u32>,
+    
+    /// Print a one-line cache summary after each run
+    #[serde(default)]
+    pub summary: bool,
+    
+    /// Default number of seconds after which a running command is killed
+    pub timeout: Option<u64>,
+    
+    /// Default limit on the bytes a command may write to stdout and stderr
+    pub max_output_bytes: Option<u64>,
+    
+    /// Bypass the cache for every command while any of these variables is set
+    #[serde(default)]
+    pub no_cache_if_env: Vec<String>,
+    
+    /// Bypass the cache for every command while any of these files exists
+    #[serde(default)]
+    pub no_cache_if_file_exists: Vec<String>,
+    
+    /// Encrypt cached output and artifacts at rest
+    #[serde(default)]
+    pub encrypt: bool,
+    
+    /// Key-value pairs recorded in the metadata of every entry, e.g. `team: infra`
+    #[serde(default)]
+    pub meta: BTreeMap<String, String>,
+    
+    /// Seconds before their TTL runs out at which `cacher expiring` re-runs entries
+    pub refresh_before_expiry: Option<u64>,
+    
+    /// Hook run once for each entry `cacher expiring` finds about to expire
+    pub on_expiring: Option<Hook>,
 }
 
 /// Configuration for a specific command pattern
-#[derive(Debug, Deserialize, Serialize, Clone)]
+#[derive(Debug, Deserialize, Serialize, Clone, Default)]
 pub struct CommandHint {
     /// Glob pattern to match commands
     pub pattern: String,
@@ -42,10 +160,74 @@ pub struct CommandHint {
     /// Time-to-live in seconds for this command
     pub ttl: Option<u64>,
     
+    /// Time-to-live in seconds depending on the exit code, taking precedence over `ttl`
+    pub ttl_by_exit: Option<TtlByExit>,
+    
+    /// Cache the output of the command when it fails, overriding the default
+    pub cache_failures: Option<bool>,
+    
+    /// Let the command set the TTL of its entry in its output, overriding the default
+    pub ttl_from_output: Option<bool>,
+    
     /// Environment variables to include in the cache key
     #[serde(default)]
     pub include_env: HashSet<String>,
     
+    /// Environment variables that must be set for this command to run
+    #[serde(default)]
+    pub require_env: HashSet<String>,
+    
+    /// Include the time zone and locale in the cache key, overriding the default
+    pub include_locale: Option<bool>,
+    
+    /// Include the binary the program resolves to in the cache key, overriding the default
+    pub include_binary: Option<bool>,
+    
+    /// Print a one-line cache summary after each run, overriding the default
+    pub summary: Option<bool>,
+    
+    /// Seconds after which the running command is killed and nothing is cached
+    pub timeout: Option<u64>,
+    
+    /// Bytes of stdout and stderr after which the command is killed and nothing is cached
+    pub max_output_bytes: Option<u64>,
+    
+    /// Bypass the cache for this command while any of these variables is set
+    #[serde(default)]
+    pub no_cache_if_env: Vec<String>,
+    
+    /// Bypass the cache for this command while any of these files exists
+    #[serde(default)]
+    pub no_cache_if_file_exists: Vec<String>,
+    
+    /// Encrypt cached output and artifacts at rest, overriding the default
+    pub encrypt: Option<bool>,
+    
+    /// When to copy entries to the remote store, overriding `remote.write`
+    pub write: Option<WritePolicy>,
+    
+    /// Number of earlier outputs to keep, overriding the default
+    pub history: Option<usize>,
+    
+    /// Run the command through the shell, overriding the default
+    pub shell: Option<bool>,
+    
+    /// Ignore case in dependency globs and paths, overriding the default
+    pub case_insensitive_keys: Option<bool>,
+    
+    /// Labels to find the command's entries by with `cacher find --tag`
+    #[serde(default)]
+    pub tags: Vec<String>,
+    
+    /// Key-value pairs recorded in the metadata of the command's entries, on top of the default ones
+    #[serde(default)]
+    pub meta: BTreeMap<String, String>,
+    
+    /// The command changes things, so it runs every time; its output is
+    /// still recorded for stats and history, but never served from the cache
+    #[serde(default)]
+    pub side_effects: bool,
+    
     /// Dependencies that should invalidate the cache when changed
     #[serde(default)]
     pub depends_on: Vec<Dependency>,
@@ -53,6 +235,25 @@ pub struct CommandHint {
     /// Artifacts produced by this command that should be cached
     #[serde(default)]
     pub artifacts: Vec<ArtifactType>,
+    
+    /// Regex substitutions applied to stdout before it is cached
+    #[serde(default)]
+    pub normalize: Vec<NormalizeRule>,
+    
+    /// Hook run before the command is executed on a cache miss
+    pub before: Option<Hook>,
+    
+    /// Hook run after the command is executed and cached on a cache miss
+    pub after_miss: Option<Hook>,
+    
+    /// Hook run after the command's output is served from the cache
+    pub after_hit: Option<Hook>,
+    
+    /// Seconds before its TTL runs out at which `cacher expiring` re-runs the entry, overriding the default
+    pub refresh_before_expiry: Option<u64>,
+    
+    /// Hook run once when `cacher expiring` finds the entry about to expire, overriding the default
+    pub on_expiring: Option<Hook>,
 }
 
 /// Types of dependencies that can invalidate the cache
@@ -71,6 +272,14 @@ pub enum Dependency {
     Lines {
         lines: LinePattern,
     },
+    /// A package manager lockfile, or `auto` to detect known lockfiles
+    Lockfile {
+        lockfile: String,
+    },
+    /// A docker compose file and the project name, or `auto` to detect the compose file
+    ComposeFile {
+        compose_file: String,
+    },
 }
 
 /// Configuration for matching specific lines in a file
@@ -83,6 +292,65 @@ pub struct LinePattern {
     pub pattern: String,
 }
 
+/// Time-to-live in seconds for each exit code, e.g. `{ 0: 3600, nonzero: 60 }`
+///
+/// Keys are exit codes or `nonzero`, which covers every failing exit code
+/// not listed on its own.
+#[derive(Debug, Deserialize, Serialize, Clone, PartialEq, Default)]
+#[serde(try_from = "BTreeMap<ExitKey, u64>", into = "BTreeMap<ExitKey, u64>")]
+pub struct TtlByExit {
+    pub codes: BTreeMap<i32, u64>,
+    pub nonzero: Option<u64>,
+}
+
+impl TtlByExit {
+    /// Time-to-live in seconds for an entry with this exit code, if one is set
+    pub fn ttl(&self, exit_code: i32) -> Option<u64> {
+        self.codes.get(&exit_code).copied()
+            .or(if exit_code != 0 { self.nonzero } else { None })
+    }
+}
+
+/// A key of `ttl_by_exit`
+#[derive(Debug, Deserialize, Serialize, Clone, PartialEq, Eq, PartialOrd, Ord)]
+#[serde(untagged)]
+enum ExitKey {
+    Code(i32),
+    Name(String),
+}
+
+impl TryFrom<BTreeMap<ExitKey, u64>> for TtlByExit {
+    type Error = String;
+    
+    fn try_from(map: BTreeMap<ExitKey, u64>) -> std::result::Result<Self, Self::Error> {
+        let mut ttl_by_exit = TtlByExit::default();
+        for (key, ttl) in map {
+            match key {
+                ExitKey::Code(code) => {
+                    ttl_by_exit.codes.insert(code, ttl);
+                },
+                ExitKey::Name(name) if name == "nonzero" => ttl_by_exit.nonzero = Some(ttl),
+                ExitKey::Name(name) => {
+                    return Err(format!("Invalid ttl_by_exit key {:?}, expected an exit code or \"nonzero\"", name));
+                },
+            }
+        }
+        Ok(ttl_by_exit)
+    }
+}
+
+impl From<TtlByExit> for BTreeMap<ExitKey, u64> {
+    fn from(ttl_by_exit: TtlByExit) -> Self {
+        let mut map: BTreeMap<ExitKey, u64> = ttl_by_exit.codes.into_iter()
+            .map(|(code, ttl)| (ExitKey::Code(code), ttl))
+            .collect();
+        if let Some(ttl) = ttl_by_exit.nonzero {
+            map.insert(ExitKey::Name("nonzero".to_string()), ttl);
+        }
+        map
+    }
+}
+
 impl HintFile {
     /// Load a hint file from the specified path
     ///
@@ -97,9 +365,36 @@ impl HintFile {
         let content = fs::read_to_string(path)
             .with_context(|| format!("Failed to read hint file: {}", path.display()))?;
         
-        let hint_file: HintFile = serde_yaml::from_str(&content)
+        let mut hint_file: HintFile = serde_yaml::from_str(&content)
             .with_context(|| format!("Failed to parse hint file: {}", path.display()))?;
         
+        if let Some(version) = hint_file.default.key_schema {
+            if !crate::key_schema_supported(version) {
+                anyhow::bail!(
+                    "Unsupported key_schema {} in hint file {} (supported: 1 to {})",
+                    version, path.display(), crate::KEY_SCHEMA_VERSION,
+                );
+            }
+        }
+        
+        // Paths written on Windows work everywhere, and the other way round
+        for command_hint in &mut hint_file.commands {
+            for dependency in &mut command_hint.depends_on {
+                dependency.normalize_separators();
+            }
+        }
+        
+        // The remote store is the same whichever subdirectory cacher runs in
+        if let (Some(remote), Some(dir)) = (&mut hint_file.remote, path.parent()) {
+            if Path::new(&remote.path).is_relative() {
+                remote.path = dir.join(&remote.path).to_string_lossy().into_owned();
+            }
+        }
+        
+        // Preset hints go last so the project's own patterns take precedence
+        let preset_hints: Vec<CommandHint> = hint_file.presets.iter().flat_map(|preset| preset.hints()).collect();
+        hint_file.commands.extend(preset_hints);
+        
         Ok(hint_file)
     }
     
@@ -121,9 +416,144 @@ impl HintFile {
         })
     }
     
+    /// Whether the time zone and locale should be part of the cache key for a command
+    ///
+    /// A matching command's `include_locale` takes precedence over the default.
+    pub fn include_locale(&self, command: &str) -> bool {
+        self.find_matching_command(command)
+            .and_then(|cmd| cmd.include_locale)
+            .unwrap_or(self.default.include_locale)
+    }
+    
+    /// Whether the binary a command runs should be part of its cache key
+    ///
+    /// A matching command's `include_binary` takes precedence over the default.
+    pub fn include_binary(&self, command: &str) -> bool {
+        self.find_matching_command(command)
+            .and_then(|cmd| cmd.include_binary)
+            .unwrap_or(self.default.include_binary)
+    }
+    
+    /// Whether a cache summary should be printed after running a command
+    ///
+    /// A matching command's `summary` takes precedence over the default.
+    pub fn summary(&self, command: &str) -> bool {
+        self.find_matching_command(command)
+            .and_then(|cmd| cmd.summary)
+            .unwrap_or(self.default.summary)
+    }
+    
+    /// How many earlier outputs of a command to keep
+    ///
+    /// A matching command's `history` takes precedence over the default.
+    pub fn history(&self, command: &str) -> usize {
+        self.find_matching_command(command)
+            .and_then(|cmd| cmd.history)
+            .unwrap_or(self.default.history)
+    }
+    
+    /// Whether a command should be run through the shell
+    ///
+    /// A matching command's `shell` takes precedence over the default.
+    pub fn shell(&self, command: &str) -> bool {
+        self.find_matching_command(command)
+            .and_then(|cmd| cmd.shell)
+            .unwrap_or(self.default.shell)
+    }
+    
+    /// Whether a command's output is cached when it exits with a non-zero status
+    ///
+    /// A matching command's `cache_failures` takes precedence over the default.
+    pub fn cache_failures(&self, command: &str) -> bool {
+        self.find_matching_command(command)
+            .and_then(|cmd| cmd.cache_failures)
+            .unwrap_or(self.default.cache_failures)
+    }
+    
+    /// Whether a command is declared to have side effects, so it must never be skipped
+    pub fn side_effects(&self, command: &str) -> bool {
+        self.find_matching_command(command).is_some_and(|cmd| cmd.side_effects)
+    }
+    
+    /// How long before its TTL runs out a command's entry should be re-run
+    ///
+    /// A matching command's `refresh_before_expiry` takes precedence over the default.
+    pub fn refresh_before_expiry(&self, command: &str) -> Option<Duration> {
+        self.find_matching_command(command)
+            .and_then(|cmd| cmd.refresh_before_expiry)
+            .or(self.default.refresh_before_expiry)
+            .map(Duration::from_secs)
+    }
+    
+    /// The hook to run for a command at a stage
+    ///
+    /// Only `on_expiring` can be set under the default settings; a matching
+    /// command's hook takes precedence over it.
+    pub fn hook(&self, command: &str, stage: HookStage) -> Option<&Hook> {
+        let command_hint = self.find_matching_command(command);
+        match stage {
+            HookStage::Before => command_hint?.before.as_ref(),
+            HookStage::AfterMiss => command_hint?.after_miss.as_ref(),
+            HookStage::AfterHit => command_hint?.after_hit.as_ref(),
+            HookStage::Expiring => command_hint
+                .and_then(|cmd| cmd.on_expiring.as_ref())
+                .or(self.default.on_expiring.as_ref()),
+        }
+    }
+    
+    /// Whether a command may set the TTL of its entry with a directive in its output
+    ///
+    /// A matching command's `ttl_from_output` takes precedence over the default.
+    pub fn ttl_from_output(&self, command: &str) -> bool {
+        self.find_matching_command(command)
+            .and_then(|cmd| cmd.ttl_from_output)
+            .unwrap_or(self.default.ttl_from_output)
+    }
+    
+    /// Key-value pairs to record in the metadata of a command's entries
+    ///
+    /// The matching command's pairs are added to the default ones, replacing
+    /// those with the same key.
+    pub fn meta(&self, command: &str) -> BTreeMap<String, String> {
+        let mut meta = self.default.meta.clone();
+        if let Some(command_hint) = self.find_matching_command(command) {
+            meta.extend(command_hint.meta.clone());
+        }
+        meta
+    }
+    
+    /// Whether dependency paths in a command's key ignore case
+    ///
+    /// A matching command's `case_insensitive_keys` takes precedence over the default.
+    pub fn case_insensitive_keys(&self, command: &str) -> bool {
+        self.find_matching_command(command)
+            .and_then(|cmd| cmd.case_insensitive_keys)
+            .unwrap_or(self.default.case_insensitive_keys)
+    }
+    
+    /// Whether a command's cached output and artifacts should be encrypted
+    ///
+    /// A matching command's `encrypt` takes precedence over the default.
+    pub fn encrypt(&self, command: &str) -> bool {
+        self.find_matching_command(command)
+            .and_then(|cmd| cmd.encrypt)
+            .unwrap_or(self.default.encrypt)
+    }
+    
+    /// When a command's entries are copied to the remote store
+    ///
+    /// A matching command's `write` takes precedence over `remote.write`.
+    pub fn write_policy(&self, command: &str) -> WritePolicy {
+        self.find_matching_command(command)
+            .and_then(|cmd| cmd.write)
+            .or(self.remote.as_ref().map(|remote| remote.write))
+            .unwrap_or_default()
+    }
+    
     /// Find a hint file by searching up from the given directory
     ///
-    /// Searches for a .cacher file in the given directory and its parents
+    /// Searches for a hint file named as in [`HINT_FILE_NAMES`] in the given
+    /// directory and its parents
     ///
     /// # Arguments
     ///
@@ -133,12 +563,27 @@ impl HintFile {
     ///
     /// An Option containing the parsed HintFile, or None if no hint file is found
     pub fn find_hint_file(start_dir: &Path) -> Option<Self> {
+        Self::find_hint_file_path(start_dir).and_then(|path| Self::from_file(&path).ok())
+    }
+    
+    /// Find the path of the hint file that applies to the given directory
+    ///
+    /// # Arguments
+    ///
+    /// * `start_dir` - Directory to start searching from
+    ///
+    /// # Returns
+    ///
+    /// An Option containing the path of the nearest hint file, or None if there is none
+    pub fn find_hint_file_path(start_dir: &Path) -> Option<PathBuf> {
         let mut current_dir = Some(start_dir);
         
         while let Some(dir) = current_dir {
-            let hint_file_path = dir.join(".cacher");
-            if hint_file_path.exists() {
-                return Self::from_file(&hint_file_path).ok();
+            for name in HINT_FILE_NAMES {
+                let hint_file_path = dir.join(name);
+                if hint_file_path.exists() {
+                    return Some(hint_file_path);
+                }
             }
             
             current_dir = dir.parent();
@@ -149,6 +594,21 @@ impl HintFile {
 }
 
 impl Dependency {
+    /// Use `/` as the path separator in the dependency's paths and patterns
+    ///
+    /// `/` works on every platform, including in globs on Windows, while `\`
+    /// only works there.
+    pub fn normalize_separators(&mut self) {
+        let path = match self {
+            Dependency::File { file } => file,
+            Dependency::Files { files } => files,
+            Dependency::Lines { lines } => &mut lines.file,
+            Dependency::Lockfile { lockfile } => lockfile,
+            Dependency::ComposeFile { compose_file } => compose_file,
+        };
+        *path = path.replace('\\', "/");
+    }
+    
     /// Get all files matching this dependency
     ///
     /// # Arguments
@@ -165,13 +625,16 @@ impl Dependency {
             },
             Dependency::Files { files } => {
                 let pattern = files;
+                let ignore = IgnoreRules::load(base_dir);
                 let mut matches = Vec::new();
                 
-                for entry in glob::glob(&format!("{}/{}", base_dir.display(), pattern))? {
-                    if let Ok(path) = entry {
-                        if let Some(path_str) = path.to_str() {
-                            matches.push(path_str.to_string());
-                        }
+                for path in glob::glob(&format!("{}/{}", base_dir.display(), pattern))?.flatten() {
+                    if ignore.is_ignored(&path, path.is_dir()) {
+                        continue;
+                    }
+                    
+                    if let Some(path_str) = path.to_str() {
+                        matches.push(path_str.to_string());
                     }
                 }
                 
@@ -179,6 +642,18 @@ impl Dependency {
             },
             Dependency::Lines { lines } => {
                 Ok(vec![lines.file.clone()])
+            },
+            Dependency::Lockfile { lockfile } => {
+                Ok(crate::lockfile::resolve(base_dir, lockfile)
+                    .into_iter()
+                    .map(|(name, _)| name)
+                    .collect())
+            },
+            Dependency::ComposeFile { compose_file } => {
+                Ok(crate::compose::resolve(base_dir, compose_file)
+                    .into_iter()
+                    .map(|(name, _)| name)
+                    .collect())
             }
         }
     }
@@ -250,6 +725,33 @@ impl Dependency {
                 
                 let mut hasher = Sha256::new();
                 hasher.update(matching_lines);
+                Ok(format!("{:x}", hasher.finalize()))
+            },
+            Dependency::Lockfile { lockfile } => {
+                let mut hasher = Sha256::new();
+                
+                for (name, path) in crate::lockfile::resolve(base_dir, lockfile) {
+                    let content = crate::lockfile::dependency_content(&path)
+                        .with_context(|| format!("Failed to read lockfile: {}", path.display()))?;
+                    
+                    hasher.update(format!("{}\n", name));
+                    hasher.update(content);
+                }
+                
+                Ok(format!("{:x}", hasher.finalize()))
+            },
+            Dependency::ComposeFile { compose_file } => {
+                let mut hasher = Sha256::new();
+                hasher.update(format!("project={}\n", crate::compose::project_name(base_dir)));
+                
+                for (name, path) in crate::compose::resolve(base_dir, compose_file) {
+                    let content = fs::read(&path)
+                        .with_context(|| format!("Failed to read compose file: {}", path.display()))?;
+                    
+                    hasher.update(format!("{}\n", name));
+                    hasher.update(content);
+                }
+                
                 Ok(format!("{:x}", hasher.finalize()))
             }
         }
@@ -0,0 +1,154 @@
+use std::io::{self, Error, ErrorKind};
+use std::time::{Duration, SystemTime};
+use similar::{Algorithm, DiffOp};
+
+/// Prefix of every delta
+const MAGIC: &[u8] = b"CACHERDELTA1";
+
+/// Copy a range of the base
+const OP_COPY: u8 = 1;
+
+/// Insert literal bytes
+const OP_INSERT: u8 = 2;
+
+/// Directory in an entry that holds earlier generations of its stdout
+pub const HISTORY_DIR: &str = "history";
+
+/// An earlier generation of a command's output
+#[derive(Debug, Clone, PartialEq)]
+pub struct HistoryEntry {
+    /// When this generation was cached
+    pub created_at: SystemTime,
+    pub stdout: String,
+}
+
+/// Encode `target` as a delta against `base`
+///
+/// Outputs are compared line by line; lines of `target` found in `base` are
+/// stored as copies of that range, the rest as literal bytes. `created_at` is
+/// stored in the header, so a generation can be dated without decoding it.
+pub fn diff(base: &[u8], target: &[u8], created_at: SystemTime) -> Vec<u8> {
+    let base_lines = split_lines(base);
+    let target_lines = split_lines(target);
+    let base_offsets = line_offsets(&base_lines);
+    let target_offsets = line_offsets(&target_lines);
+
+    let mut delta = MAGIC.to_vec();
+    let secs = created_at.duration_since(SystemTime::UNIX_EPOCH).unwrap_or_default().as_secs();
+    delta.extend_from_slice(&secs.to_le_bytes());
+
+    for op in similar::capture_diff_slices(Algorithm::Myers, &base_lines, &target_lines) {
+        match op {
+            DiffOp::Equal { old_index, len, .. } => {
+                let start = base_offsets[old_index];
+                let end = base_offsets[old_index + len];
+                delta.push(OP_COPY);
+                delta.extend_from_slice(&(start as u64).to_le_bytes());
+                delta.extend_from_slice(&((end - start) as u64).to_le_bytes());
+            },
+            DiffOp::Insert { new_index, new_len, .. } | DiffOp::Replace { new_index, new_len, .. } => {
+                let bytes = &target[target_offsets[new_index]..target_offsets[new_index + new_len]];
+                delta.push(OP_INSERT);
+                delta.extend_from_slice(&(bytes.len() as u64).to_le_bytes());
+                delta.extend_from_slice(bytes);
+            },
+            DiffOp::Delete { .. } => {},
+        }
+    }
+
+    delta
+}
+
+/// Rebuild the target of a delta produced by [`diff`] from its base
+pub fn apply(base: &[u8], delta: &[u8]) -> io::Result<Vec<u8>> {
+    let mut reader = Reader { data: delta, position: 0 };
+    if reader.take(MAGIC.len())? != MAGIC {
+        return Err(invalid());
+    }
+    reader.u64()?;
+
+    let mut target = Vec::with_capacity(base.len());
+    while reader.position < delta.len() {
+        match reader.take(1)?[0] {
+            OP_COPY => {
+                let start = reader.usize()?;
+                let len = reader.usize()?;
+                let end = start.checked_add(len).filter(|end| *end <= base.len()).ok_or_else(invalid)?;
+                target.extend_from_slice(&base[start..end]);
+            },
+            OP_INSERT => {
+                let len = reader.usize()?;
+                target.extend_from_slice(reader.take(len)?);
+            },
+            _ => return Err(invalid()),
+        }
+    }
+
+    Ok(target)
+}
+
+/// When the generation a delta encodes was cached
+pub fn created_at(delta: &[u8]) -> io::Result<SystemTime> {
+    let mut reader = Reader { data: delta, position: 0 };
+    if reader.take(MAGIC.len())? != MAGIC {
+        return Err(invalid());
+    }
+    Ok(SystemTime::UNIX_EPOCH + Duration::from_secs(reader.u64()?))
+}
+
+/// Name of the file holding generation `sequence`, relative to the entry
+///
+/// Generations are numbered in the order they were stored, so the highest
+/// number is the most recent earlier generation.
+pub fn delta_file_name(sequence: u64) -> String {
+    format!("{}/{}.delta", HISTORY_DIR, sequence)
+}
+
+/// Parse the sequence number out of a file name in the history directory
+pub fn parse_sequence(file_name: &str) -> Option<u64> {
+    file_name.strip_suffix(".delta")?.parse().ok()
+}
+
+/// Split into lines, each keeping its trailing newline
+fn split_lines(data: &[u8]) -> Vec<&[u8]> {
+    data.split_inclusive(|byte| *byte == b'\n').collect()
+}
+
+/// Byte offset where each line starts, plus the total length
+fn line_offsets(lines: &[&[u8]]) -> Vec<usize> {
+    let mut offsets = Vec::with_capacity(lines.len() + 1);
+    let mut offset = 0;
+    offsets.push(offset);
+    for line in lines {
+        offset += line.len();
+        offsets.push(offset);
+    }
+    offsets
+}
+
+fn invalid() -> Error {
+    Error::new(ErrorKind::InvalidData, "Corrupted history delta")
+}
+
+struct Reader<'a> {
+    data: &'a [u8],
+    position: usize,
+}
+
+impl<'a> Reader<'a> {
+    fn take(&mut self, len: usize) -> io::Result<&'a [u8]> {
+        let end = self.position.checked_add(len).filter(|end| *end <= self.data.len()).ok_or_else(invalid)?;
+        let bytes = &self.data[self.position..end];
+        self.position = end;
+        Ok(bytes)
+    }
+
+    fn u64(&mut self) -> io::Result<u64> {
+        let bytes = self.take(8)?;
+        Ok(u64::from_le_bytes(bytes.try_into().map_err(|_| invalid())?))
+    }
+
+    fn usize(&mut self) -> io::Result<usize> {
+        usize::try_from(self.u64()?).map_err(|_| invalid())
+    }
+}
@@ -0,0 +1,151 @@
+use std::path::Path;
+use std::io::{self, Error};
+use std::time::Duration;
+use serde::{Deserialize, Serialize};
+use crate::process;
+
+/// Timeout applied to hooks that don't set their own, in seconds
+pub const DEFAULT_HOOK_TIMEOUT: u64 = 60;
+
+/// A shell command run before or after a cached command
+///
+/// In a hint file a hook is either a plain command string or a mapping with
+/// `run`, `timeout` and `on_failure` keys.
+#[derive(Debug, Deserialize, Serialize, Clone, PartialEq)]
+#[serde(from = "HookDefinition")]
+pub struct Hook {
+    /// Shell command to run
+    pub run: String,
+
+    /// Seconds after which the hook is killed (default: 60)
+    pub timeout: Option<u64>,
+
+    /// What to do when the hook fails or times out
+    pub on_failure: Option<HookFailure>,
+}
+
+/// How a failing hook affects the cached command
+#[derive(Debug, Deserialize, Serialize, Clone, Copy, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub enum HookFailure {
+    /// Stop and return an error
+    Abort,
+    /// Print a warning and carry on
+    Warn,
+    /// Carry on silently
+    Ignore,
+}
+
+/// When a hook runs
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum HookStage {
+    /// Before executing the command on a cache miss
+    Before,
+    /// After executing and caching the command on a cache miss
+    AfterMiss,
+    /// After serving the command's output from the cache
+    AfterHit,
+    /// When `cacher expiring` finds the command's entry about to expire
+    Expiring,
+}
+
+impl HookStage {
+    /// Name of the hint file key for this stage
+    pub fn name(&self) -> &'static str {
+        match self {
+            HookStage::Before => "before",
+            HookStage::AfterMiss => "after_miss",
+            HookStage::AfterHit => "after_hit",
+            HookStage::Expiring => "on_expiring",
+        }
+    }
+
+    /// Failure policy used when a hook doesn't set `on_failure`
+    ///
+    /// A failed `before` hook usually means the command can't work (e.g. a
+    /// database didn't start), so it aborts. After hooks only warn, since the
+    /// command's output is already available, as do expiry notifications.
+    pub fn default_failure_policy(&self) -> HookFailure {
+        match self {
+            HookStage::Before => HookFailure::Abort,
+            HookStage::AfterMiss | HookStage::AfterHit | HookStage::Expiring => HookFailure::Warn,
+        }
+    }
+}
+
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum HookDefinition {
+    Command(String),
+    Detailed {
+        run: String,
+        timeout: Option<u64>,
+        on_failure: Option<HookFailure>,
+    },
+}
+
+impl From<HookDefinition> for Hook {
+    fn from(definition: HookDefinition) -> Self {
+        match definition {
+            HookDefinition::Command(run) => Hook { run, timeout: None, on_failure: None },
+            HookDefinition::Detailed { run, timeout, on_failure } => Hook { run, timeout, on_failure },
+        }
+    }
+}
+
+impl Hook {
+    /// Run the hook in `dir`, applying its timeout and failure policy
+    ///
+    /// The hook's output is forwarded to stderr so it never mixes with the
+    /// cached command's stdout. `CACHER_COMMAND` and `CACHER_HOOK` are set in
+    /// the hook's environment.
+    ///
+    /// # Returns
+    ///
+    /// An error only if the hook failed and its policy is `abort`
+    pub fn run(&self, stage: HookStage, command: &str, dir: &Path) -> io::Result<()> {
+        self.run_with_env(stage, command, dir, &[])
+    }
+
+    /// Run the hook like [`Hook::run`], with extra variables set in its environment
+    pub fn run_with_env(&self, stage: HookStage, command: &str, dir: &Path, env: &[(&str, String)]) -> io::Result<()> {
+        let timeout = Duration::from_secs(self.timeout.unwrap_or(DEFAULT_HOOK_TIMEOUT));
+        let policy = self.on_failure.unwrap_or_else(|| stage.default_failure_policy());
+
+        let mut hook_command = process::shell_command(&self.run);
+        hook_command
+            .current_dir(dir)
+            .env("CACHER_COMMAND", command)
+            .env("CACHER_HOOK", stage.name())
+            .envs(env.iter().map(|(name, value)| (name, value)));
+
+        let failure = match process::run_with_timeout(&mut hook_command, Some(timeout)) {
+            Ok(output) => {
+                eprint!("{}", String::from_utf8_lossy(&output.stdout));
+                eprint!("{}", String::from_utf8_lossy(&output.stderr));
+
+                if output.timed_out {
+                    Some(format!("timed out after {}s", timeout.as_secs()))
+                } else {
+                    match output.status {
+                        Some(status) if status.success() => None,
+                        Some(status) => Some(format!("exited with {}", status)),
+                        None => Some("was killed".to_string()),
+                    }
+                }
+            },
+            Err(e) => Some(format!("could not be started: {}", e)),
+        };
+
+        match (failure, policy) {
+            (None, _) | (Some(_), HookFailure::Ignore) => Ok(()),
+            (Some(reason), HookFailure::Warn) => {
+                eprintln!("Warning: {} hook `{}` {}", stage.name(), self.run, reason);
+                Ok(())
+            },
+            (Some(reason), HookFailure::Abort) => Err(Error::other(
+                format!("{} hook `{}` {}", stage.name(), self.run, reason)
+            )),
+        }
+    }
+}
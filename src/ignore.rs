@@ -0,0 +1,126 @@
+use std::fs;
+use std::path::{Component, Path, PathBuf};
+use glob::{MatchOptions, Pattern};
+
+/// Name of the file in the project directory listing paths to leave out of
+/// dependency globs and directory artifacts
+pub const IGNORE_FILE: &str = ".cacherignore";
+
+/// The patterns of a project's `.cacherignore`
+///
+/// The syntax is that of `.gitignore`: one glob pattern per line, `#` for
+/// comments, a trailing `/` to only match directories and a leading `!` to
+/// re-include what an earlier pattern ignored. Patterns containing a `/` are
+/// matched against the path from the project directory; others against the
+/// name of a file or directory at any depth. Everything inside an ignored
+/// directory is ignored too.
+#[derive(Debug, Clone, Default)]
+pub struct IgnoreRules {
+    root: PathBuf,
+    rules: Vec<Rule>,
+}
+
+#[derive(Debug, Clone)]
+struct Rule {
+    pattern: Pattern,
+    negated: bool,
+    dir_only: bool,
+    anchored: bool,
+}
+
+impl IgnoreRules {
+    /// Read the `.cacherignore` in `root`; without one, nothing is ignored
+    pub fn load(root: &Path) -> Self {
+        let content = fs::read_to_string(root.join(IGNORE_FILE)).unwrap_or_default();
+        Self::parse(root, &content)
+    }
+
+    /// Parse ignore patterns relative to `root`
+    ///
+    /// Invalid patterns are skipped with a warning.
+    pub fn parse(root: &Path, content: &str) -> Self {
+        let mut rules = Vec::new();
+
+        for line in content.lines() {
+            let line = line.trim_end();
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+
+            let (negated, line) = match line.strip_prefix('!') {
+                Some(line) => (true, line),
+                None => (false, line),
+            };
+            // `\#` and `\!` start patterns with a literal `#` or `!`
+            let line = line.strip_prefix('\\').unwrap_or(line);
+            let (dir_only, line) = match line.strip_suffix('/') {
+                Some(line) => (true, line),
+                None => (false, line),
+            };
+            let anchored = line.contains('/');
+            let line = line.strip_prefix('/').unwrap_or(line);
+
+            match Pattern::new(line) {
+                Ok(pattern) => rules.push(Rule { pattern, negated, dir_only, anchored }),
+                Err(e) => eprintln!("Warning: invalid pattern '{}' in {}: {}", line, IGNORE_FILE, e),
+            }
+        }
+
+        IgnoreRules { root: root.to_path_buf(), rules }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.rules.is_empty()
+    }
+
+    /// Whether a path is ignored
+    ///
+    /// Absolute paths outside the project directory never are.
+    ///
+    /// # Arguments
+    ///
+    /// * `path` - The path, under the project directory or relative to it
+    /// * `is_dir` - Whether the path is a directory
+    pub fn is_ignored(&self, path: &Path, is_dir: bool) -> bool {
+        if self.rules.is_empty() {
+            return false;
+        }
+
+        let relative = match path.strip_prefix(&self.root) {
+            Ok(relative) => relative,
+            Err(_) if path.is_absolute() => return false,
+            Err(_) => path,
+        };
+
+        let names: Vec<String> = relative.components()
+            .filter_map(|component| match component {
+                Component::Normal(name) => Some(name.to_string_lossy().into_owned()),
+                _ => None,
+            })
+            .collect();
+
+        // Check each directory on the way down, since ignoring one ignores its contents
+        (1..=names.len()).any(|depth| {
+            let is_last = depth == names.len();
+            self.matches(&names[..depth].join("/"), &names[depth - 1], !is_last || is_dir)
+        })
+    }
+
+    /// Whether the last pattern matching a path ignores it
+    fn matches(&self, path: &str, name: &str, is_dir: bool) -> bool {
+        let options = MatchOptions { require_literal_separator: true, ..MatchOptions::new() };
+
+        let mut ignored = false;
+        for rule in &self.rules {
+            if rule.dir_only && !is_dir {
+                continue;
+            }
+
+            let target = if rule.anchored { path } else { name };
+            if rule.pattern.matches_with(target, options) {
+                ignored = !rule.negated;
+            }
+        }
+        ignored
+    }
+}
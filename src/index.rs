@@ -0,0 +1,297 @@
+use std::collections::BTreeMap;
+use std::fs::{self, File};
+use std::io::{self, Error, ErrorKind, Write};
+use std::path::{Path, PathBuf};
+use std::time::{Duration, SystemTime};
+use glob::Pattern;
+use serde::{Deserialize, Serialize};
+use crate::metadata::{EntryInputs, EntryMetadata, MetaFilter};
+
+/// Name of the index file in the cache directory
+const INDEX_FILE: &str = "index.json";
+
+/// Name of the log of changes made since the index file was last written
+const LOG_FILE: &str = "index.log";
+
+/// Lock held shared while changes are appended to the log and the index is
+/// read, and exclusively while the log is compacted into the index file
+const LOCK_FILE: &str = ".index.lock";
+
+/// Size the log may grow to before a change compacts it into the index file
+const COMPACT_AFTER_BYTES: u64 = 256 * 1024;
+
+/// What the index knows about an entry
+#[derive(Debug, Deserialize, Serialize, Clone, PartialEq)]
+pub struct IndexRecord {
+    pub command: String,
+
+    /// When the entry was created, in seconds since the Unix epoch
+    pub timestamp: u64,
+
+    #[serde(default)]
+    pub inputs: EntryInputs,
+
+    #[serde(default)]
+    pub meta: BTreeMap<String, String>,
+}
+
+impl IndexRecord {
+    pub fn from_metadata(metadata: &EntryMetadata) -> Self {
+        IndexRecord {
+            command: metadata.command.clone(),
+            timestamp: metadata.timestamp,
+            inputs: metadata.inputs.clone(),
+            meta: metadata.meta.clone(),
+        }
+    }
+
+    /// When the entry was created
+    pub fn created_at(&self) -> SystemTime {
+        SystemTime::UNIX_EPOCH + Duration::from_secs(self.timestamp)
+    }
+}
+
+/// Criteria for `cacher find`; every one that is set has to match
+#[derive(Debug, Clone, Default)]
+pub struct FindQuery {
+    /// Environment variables the entry's key must include
+    pub env: Vec<String>,
+
+    /// Files, or glob patterns of files, the entry must depend on
+    pub dependencies: Vec<String>,
+
+    /// Tags the entry must have
+    pub tags: Vec<String>,
+
+    /// Glob pattern the pattern of the entry's command hint must match
+    pub pattern: Option<String>,
+
+    /// Only entries created at or after this time
+    pub since: Option<SystemTime>,
+
+    /// Conditions on the key-value pairs attached to the entry
+    pub meta: Vec<MetaFilter>,
+}
+
+impl FindQuery {
+    /// Check the query's glob patterns, so matching can't fail
+    pub fn validate(&self) -> io::Result<()> {
+        for pattern in self.dependencies.iter().chain(&self.pattern) {
+            Pattern::new(pattern)
+                .map_err(|e| Error::new(ErrorKind::InvalidInput, format!("Invalid pattern {}: {}", pattern, e)))?;
+        }
+        Ok(())
+    }
+
+    pub fn matches(&self, record: &IndexRecord) -> bool {
+        let inputs = &record.inputs;
+
+        self.env.iter().all(|name| inputs.env.contains(name))
+            && self.tags.iter().all(|tag| inputs.tags.contains(tag))
+            && self.dependencies.iter().all(|dependency| {
+                inputs.dependencies.iter().any(|recorded| dependency_matches(dependency, recorded))
+            })
+            && self.pattern.as_ref().is_none_or(|pattern| {
+                inputs.pattern.as_ref().is_some_and(|recorded| glob_matches(pattern, recorded))
+            })
+            && self.since.is_none_or(|since| record.created_at() >= since)
+            && self.meta.iter().all(|filter| filter.matches(&record.meta))
+    }
+}
+
+/// Whether a `--dep` value refers to a recorded dependency
+///
+/// Either side may be a glob: `--dep '*.json'` finds entries depending on
+/// `package.json`, and `--dep src/main.rs` finds entries declaring `src/**/*.rs`.
+/// A bare file name also matches the file in any directory.
+fn dependency_matches(query: &str, recorded: &str) -> bool {
+    query == recorded
+        || glob_matches(query, recorded)
+        || glob_matches(recorded, query)
+        || Path::new(recorded).file_name().is_some_and(|name| name.to_string_lossy() == query)
+}
+
+fn glob_matches(pattern: &str, value: &str) -> bool {
+    Pattern::new(pattern).is_ok_and(|pattern| pattern.matches(value))
+}
+
+/// One change to the index, as a line of its log
+#[derive(Debug, Deserialize, Serialize)]
+struct LogLine {
+    id: String,
+    /// The entry's new record, or None if it was removed
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    record: Option<IndexRecord>,
+}
+
+/// An index of the inputs of every entry in a cache directory, keyed by cache ID
+///
+/// Kept in `index.json` next to the entries, so searching doesn't have to
+/// read the metadata of every entry. The index is only a shortcut: it is
+/// rebuilt from the entries' metadata whenever it is missing or unreadable.
+///
+/// Changes are appended to `index.log` under a shared lock, so saving an
+/// entry costs the same however many there are, and concurrent writers don't
+/// wait for each other. Once the log grows past a limit, the writer that
+/// notices folds it into `index.json` under an exclusive lock; readers
+/// replay whatever is left of it on top of `index.json`.
+#[derive(Debug, Clone)]
+pub struct SearchIndex {
+    cache_dir: PathBuf,
+}
+
+impl SearchIndex {
+    /// The index of a cache directory
+    pub fn new(cache_dir: &Path) -> Self {
+        SearchIndex { cache_dir: cache_dir.to_path_buf() }
+    }
+
+    /// Record or update an entry
+    pub fn insert(&self, id: &str, record: IndexRecord) -> io::Result<()> {
+        self.append([LogLine { id: id.to_string(), record: Some(record) }])
+    }
+
+    /// Forget entries that have been deleted
+    pub fn remove<'a>(&self, ids: impl IntoIterator<Item = &'a str>) -> io::Result<()> {
+        self.append(ids.into_iter().map(|id| LogLine { id: id.to_string(), record: None }))
+    }
+
+    /// Every indexed entry
+    pub fn records(&self) -> io::Result<BTreeMap<String, IndexRecord>> {
+        {
+            let lock = self.lock()?;
+            lock.lock_shared()?;
+            if let Some(mut records) = self.load()? {
+                self.replay(&mut records)?;
+                return Ok(records);
+            }
+        }
+
+        self.rebuild()
+    }
+
+    /// Rebuild the index from the metadata of the entries on disk
+    pub fn rebuild(&self) -> io::Result<BTreeMap<String, IndexRecord>> {
+        let lock = self.lock()?;
+        lock.lock()?;
+        let records = self.scan()?;
+        self.write(&records)?;
+        Ok(records)
+    }
+
+    /// Append changes to the log, compacting it if it has grown too long
+    fn append(&self, lines: impl IntoIterator<Item = LogLine>) -> io::Result<()> {
+        let mut content = String::new();
+        for line in lines {
+            let line = serde_json::to_string(&line).map_err(|e| Error::new(ErrorKind::InvalidData, e))?;
+            content.push_str(&line);
+            content.push('\n');
+        }
+        if content.is_empty() {
+            return Ok(());
+        }
+
+        let lock = self.lock()?;
+        lock.lock_shared()?;
+        let mut log = File::options().create(true).append(true).open(self.cache_dir.join(LOG_FILE))?;
+        log.write_all(content.as_bytes())?;
+        let size = log.metadata()?.len();
+        lock.unlock()?;
+
+        // Someone else compacting means the log is about to shrink anyway
+        if size > COMPACT_AFTER_BYTES && lock.try_lock().is_ok() {
+            self.compact()?;
+        }
+        Ok(())
+    }
+
+    /// Fold the log into the index file
+    ///
+    /// Must be called with the lock held exclusively.
+    fn compact(&self) -> io::Result<()> {
+        let mut records = match self.load()? {
+            Some(records) => records,
+            None => self.scan()?,
+        };
+        self.replay(&mut records)?;
+        self.write(&records)
+    }
+
+    /// Read the index file, or None if it is missing or corrupted
+    ///
+    /// Must be called with the lock held.
+    fn load(&self) -> io::Result<Option<BTreeMap<String, IndexRecord>>> {
+        match fs::read_to_string(self.cache_dir.join(INDEX_FILE)) {
+            Ok(content) => Ok(serde_json::from_str(&content).ok()),
+            Err(e) if e.kind() == ErrorKind::NotFound => Ok(None),
+            Err(e) => Err(e),
+        }
+    }
+
+    /// Apply the changes in the log to `records`
+    ///
+    /// Lines that can't be parsed, such as one still being written, are skipped.
+    /// Must be called with the lock held.
+    fn replay(&self, records: &mut BTreeMap<String, IndexRecord>) -> io::Result<()> {
+        let content = match fs::read_to_string(self.cache_dir.join(LOG_FILE)) {
+            Ok(content) => content,
+            Err(e) if e.kind() == ErrorKind::NotFound => return Ok(()),
+            Err(e) => return Err(e),
+        };
+
+        for line in content.lines().filter_map(|line| serde_json::from_str::<LogLine>(line).ok()) {
+            match line.record {
+                Some(record) => records.insert(line.id, record),
+                None => records.remove(&line.id),
+            };
+        }
+        Ok(())
+    }
+
+    fn scan(&self) -> io::Result<BTreeMap<String, IndexRecord>> {
+        let mut records = BTreeMap::new();
+
+        let entries = match fs::read_dir(&self.cache_dir) {
+            Ok(entries) => entries,
+            Err(e) if e.kind() == ErrorKind::NotFound => return Ok(records),
+            Err(e) => return Err(e),
+        };
+
+        for entry in entries {
+            let entry = entry?;
+            let id = entry.file_name().to_string_lossy().into_owned();
+            if id.starts_with('.') || !entry.path().is_dir() {
+                continue;
+            }
+
+            if let Ok(Some(metadata)) = EntryMetadata::read(&entry.path().join("metadata.json")) {
+                records.insert(id, IndexRecord::from_metadata(&metadata));
+            }
+        }
+
+        Ok(records)
+    }
+
+    /// Replace the index file with `records` and empty the log
+    ///
+    /// Must be called with the lock held exclusively.
+    fn write(&self, records: &BTreeMap<String, IndexRecord>) -> io::Result<()> {
+        let content = serde_json::to_string(records)
+            .map_err(|e| Error::new(ErrorKind::InvalidData, e))?;
+
+        let mut file = tempfile::NamedTempFile::new_in(&self.cache_dir)?;
+        file.write_all(content.as_bytes())?;
+        file.persist(self.cache_dir.join(INDEX_FILE)).map_err(|e| e.error)?;
+
+        match fs::remove_file(self.cache_dir.join(LOG_FILE)) {
+            Err(e) if e.kind() != ErrorKind::NotFound => Err(e),
+            _ => Ok(()),
+        }
+    }
+
+    /// Open the lock file, without locking it yet
+    fn lock(&self) -> io::Result<File> {
+        fs::create_dir_all(&self.cache_dir)?;
+        File::options().create(true).truncate(false).write(true).open(self.cache_dir.join(LOCK_FILE))
+    }
+}
@@ -0,0 +1,79 @@
+use std::fs;
+use std::io::{self, Error, ErrorKind};
+use std::path::Path;
+use glob::Pattern;
+use serde::Deserialize;
+use crate::metadata::EntryMetadata;
+
+/// Which entries `cacher invalidate --manifest` purges, e.g. after a deploy
+///
+/// An entry is selected when any pattern, tag or namespace matches it.
+/// Unknown keys are rejected, so a typo can't quietly select nothing.
+#[derive(Debug, Deserialize, Clone, PartialEq, Default)]
+#[serde(deny_unknown_fields)]
+pub struct InvalidationManifest {
+    /// Glob patterns commands are matched against, like hint file patterns
+    #[serde(default)]
+    pub patterns: Vec<String>,
+
+    /// Tags of the command hints the entries were cached under
+    #[serde(default)]
+    pub tags: Vec<String>,
+
+    /// Glob patterns of project identities, as shown by `cacher stats --group-by project`
+    #[serde(default)]
+    pub namespaces: Vec<String>,
+
+    /// Purge pinned entries too; they are kept by default, as with `clear --all`
+    #[serde(default)]
+    pub include_pinned: bool,
+}
+
+impl InvalidationManifest {
+    /// Read a manifest from a YAML file
+    pub fn from_file(path: &Path) -> io::Result<Self> {
+        let content = fs::read_to_string(path)
+            .map_err(|e| Error::new(e.kind(), format!("Failed to read {}: {}", path.display(), e)))?;
+        Self::parse(&content)
+            .map_err(|e| Error::new(e.kind(), format!("Invalid manifest {}: {}", path.display(), e)))
+    }
+
+    /// Parse a manifest, checking that it selects something and its patterns are valid
+    pub fn parse(content: &str) -> io::Result<Self> {
+        let manifest: InvalidationManifest = serde_yaml::from_str(content)
+            .map_err(|e| Error::new(ErrorKind::InvalidData, e))?;
+
+        if manifest.patterns.is_empty() && manifest.tags.is_empty() && manifest.namespaces.is_empty() {
+            return Err(Error::new(ErrorKind::InvalidInput, "no patterns, tags or namespaces given"));
+        }
+
+        for pattern in manifest.patterns.iter().chain(&manifest.namespaces) {
+            Pattern::new(pattern)
+                .map_err(|e| Error::new(ErrorKind::InvalidInput, format!("invalid pattern {}: {}", pattern, e)))?;
+        }
+
+        Ok(manifest)
+    }
+
+    /// Whether the manifest selects an entry
+    ///
+    /// # Arguments
+    ///
+    /// * `metadata` - The entry's metadata
+    /// * `command` - The entry's full command, which `metadata` may hold cut off
+    pub fn selects(&self, metadata: &EntryMetadata, command: &str) -> bool {
+        if metadata.pinned && !self.include_pinned {
+            return false;
+        }
+
+        self.patterns.iter().any(|pattern| glob_matches(pattern, command))
+            || self.tags.iter().any(|tag| metadata.inputs.tags.contains(tag))
+            || self.namespaces.iter().any(|namespace| {
+                metadata.project.as_ref().is_some_and(|project| glob_matches(namespace, project))
+            })
+    }
+}
+
+fn glob_matches(pattern: &str, value: &str) -> bool {
+    Pattern::new(pattern).is_ok_and(|pattern| pattern.matches(value))
+}
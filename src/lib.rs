@@ -1,26 +1,360 @@
-use std::collections::HashMap;
-use std::io::{self, Error, ErrorKind, Read, Write};
-use std::fs::{self, File};
-use std::path::PathBuf;
+use std::collections::{BTreeMap, HashMap};
+use std::io::{self, Error, ErrorKind, Write};
+use std::fs;
+use std::path::{Path, PathBuf};
 use sha2::{Sha256, Digest};
 use dirs::cache_dir;
-use std::time::{Duration, SystemTime};
+use std::time::{Duration, Instant, SystemTime};
 use std::env;
+use std::sync::{Arc, OnceLock};
+use glob::Pattern;
 use crate::hint_file::{HintFile, Dependency};
 use crate::artifact::{ArtifactManager, ArtifactType};
+use crate::normalize::Normalizer;
+use crate::metadata::{display_command, EntryInputs, EntryMetadata, MetaFilter};
+use crate::diff::CommandDiff;
+use crate::hooks::HookStage;
+use crate::process::Limits;
+use crate::batch::TaskResult;
+use crate::crypto::Cipher;
+use crate::clock::{Clock, SystemClock};
+use crate::eval::Interpreter;
+use crate::remote::{RemoteStore, SyncReport, UploadQueue, WritePolicy};
+use crate::history::{HistoryEntry, HISTORY_DIR};
+use crate::index::{FindQuery, IndexRecord, SearchIndex};
+use crate::chain::{ChainPart, MarkWatcher};
+use crate::stats::{GroupBy, StatsRow};
+use crate::ignore::IgnoreRules;
+use crate::invalidate::InvalidationManifest;
 
+/// Environment variables mixed into the key when `include_locale` is enabled
+pub const LOCALE_ENV_VARS: &[&str] = &["TZ", "LANG", "LC_ALL"];
+
+/// Version of how cache keys are derived, mixed into every key
+///
+/// Bump it whenever a change to [`CommandCache::generate_id`] changes what a
+/// key means, e.g. which environment variables it includes by default, so
+/// entries cached under the old semantics are no longer served. The number is
+/// only a namespace salt: keys are always derived the current way.
+pub const KEY_SCHEMA_VERSION: u32 = 1;
+
+/// Version of the on-disk layout of a cache directory
+///
+/// Within one layout version, these paths keep their meaning and can be used
+/// by external tools such as backup scripts and CI cache steps:
+///
+/// - `<cache dir>/<id>/`, one directory per entry, named by its cache ID
+///   ([`CommandCache::entry_dir`])
+/// - `stdout` and `stderr`, the command's output, encrypted when encryption is set up
+/// - `metadata.json`, where fields may be added but are never renamed or removed;
+///   its hit count and last access leave out hits served since it was written
+/// - `complete`, present only once the entry is fully written
+/// - `command.gz`, the gzipped full command when `metadata.json` holds it cut off
+/// - `artifacts/`, the entry's artifacts ([`CommandCache::artifact_dir`]), to be
+///   copied or removed as a whole
+/// - `<cache dir>/layout-version` ([`LAYOUT_FILE`]), the layout version the cache
+///   dir was written with
+/// - `<cache dir>/encryption.salt`, the salt keys from `CACHER_ENCRYPTION_KEY`
+///   are derived with
+///
+/// Everything else is internal and may change in any release: the files inside
+/// `artifacts/`, `history/`, `hits`, and the `index.json`, `index.log`, `.index.lock`,
+/// `.upload-queue`, `.staging-*` and `.replaced-*` entries of the cache dir.
+/// Entry directories can be copied in and out while no cacher process writes
+/// to them; the index is only a search aid and doesn't need to be copied along.
+/// Encrypted entries can only be read with the key they were written with, so
+/// ones encrypted with a passphrase need `encryption.salt` copied along, and
+/// ones encrypted with a keychain key the same key on the other machine.
+pub const LAYOUT_VERSION: u32 = 1;
+
+/// File in the cache dir holding the [`LAYOUT_VERSION`] its entries were written with
+pub const LAYOUT_FILE: &str = "layout-version";
+
+/// File written last into every entry, holding a checksum of its output
+const COMPLETE_MARKER: &str = "complete";
+
+/// Prefix of the directories entries are written to before being renamed into place
+pub(crate) const STAGING_PREFIX: &str = ".staging-";
+
+/// Prefix of the directories replaced entries are moved to before being removed
+const REPLACED_PREFIX: &str = ".replaced-";
+
+/// How old a leftover staging directory must be before `gc` assumes its writer died
+const STAGING_GRACE_PERIOD: Duration = Duration::from_secs(3600);
+
+/// Everything a command produced when it was run
+#[derive(Debug, Clone, PartialEq, Default)]
+pub struct CommandOutput {
+    pub stdout: String,
+    pub stderr: String,
+    pub exit_code: i32,
+    pub duration: Duration,
+    /// Parts of a chained shell command, with how long each one ran; empty for other commands
+    pub parts: Vec<ChainPart>,
+    /// TTL the command set for its entry with a `#cacher: ttl=` directive, if any
+    pub ttl: Option<Duration>,
+}
+
+impl CommandOutput {
+    /// Whether the command exited successfully
+    pub fn success(&self) -> bool {
+        self.exit_code == 0
+    }
+}
+
+/// Details about a cached entry, used for the summary line printed after a run
+#[derive(Debug, Clone, PartialEq)]
+pub struct EntrySummary {
+    /// How long the command took when it was executed
+    pub duration: Duration,
+    /// Total size of the entry on disk, including artifacts
+    pub size: u64,
+    /// Time until the entry expires, or None if it never does
+    pub expires_in: Option<Duration>,
+}
+
+/// What a command's cache key is made of, as reported by [`CommandCache::explain`]
+#[derive(Debug, Clone, PartialEq)]
+pub struct Explanation {
+    /// The command's cache ID
+    pub id: String,
+    /// The key schema version the ID was derived with
+    pub key_schema: u32,
+    /// Whether the command is run through the shell
+    pub shell: bool,
+    /// What the hint file adds to the key
+    pub inputs: EntryInputs,
+    /// Metadata of the cached entry, if there is one
+    pub cached: Option<EntryMetadata>,
+    /// For a chained command without a current entry, the newest entry cached
+    /// for it under an earlier key, i.e. before one of its parts changed
+    pub previous: Option<EntryMetadata>,
+    /// The parts of a chained shell command; empty for other commands
+    pub parts: Vec<PartExplanation>,
+}
+
+/// One part of a chained command in an [`Explanation`]
+#[derive(Debug, Clone, PartialEq)]
+pub struct PartExplanation {
+    /// The part with its current cache ID, and the duration and exit code
+    /// recorded when the entry was cached
+    pub part: ChainPart,
+    /// What the hint file matching the part adds to the key
+    pub inputs: EntryInputs,
+    /// Whether the part's key differs from when the entry, or the previous
+    /// entry, was cached
+    pub changed: bool,
+}
+
+/// A cached entry on disk, as listed by [`CommandCache::list_cached_commands`]
+#[derive(Debug, Clone, PartialEq)]
+pub struct CacheEntrySummary {
+    /// The entry's ID, i.e. the name of its directory in the cache
+    pub id: String,
+    /// The command the entry was produced by
+    pub command: String,
+    /// When the entry was created
+    pub created_at: SystemTime,
+    /// When the entry was last served from the cache, if it ever was
+    pub last_accessed: Option<SystemTime>,
+    /// How long the command took when it was executed
+    pub duration: Duration,
+    /// Total size of the entry on disk, including artifacts
+    pub size: u64,
+    /// How many times the entry has been served from the cache
+    pub hit_count: u64,
+    /// Whether the entry is pinned
+    pub pinned: bool,
+    /// Key-value pairs attached to the entry
+    pub meta: BTreeMap<String, String>,
+}
+
+/// How [`CommandCache::list_cached_commands`] orders entries
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub enum SortBy {
+    /// Newest first
+    #[default]
+    Age,
+    /// Largest first
+    Size,
+    /// Most hit first
+    Hits,
+    /// Most recently served first
+    LastUsed,
+}
+
+impl std::str::FromStr for SortBy {
+    type Err = String;
+    
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "age" => Ok(SortBy::Age),
+            "size" => Ok(SortBy::Size),
+            "hits" => Ok(SortBy::Hits),
+            "last-used" => Ok(SortBy::LastUsed),
+            _ => Err(format!("Unknown sort order: {} (expected age, size, hits or last-used)", s)),
+        }
+    }
+}
+
+/// Which entries [`CommandCache::list_cached_commands`] returns, and in what order
+#[derive(Debug, Clone, PartialEq, Default)]
+pub struct ListOptions {
+    pub sort: SortBy,
+    /// Glob pattern commands must match, like a hint file pattern
+    pub filter: Option<String>,
+    /// Return at most this many entries
+    pub limit: Option<usize>,
+    /// Conditions on the key-value pairs attached to entries; all have to match
+    pub meta: Vec<MetaFilter>,
+}
+
+/// A cached entry whose TTL runs out soon, as reported by `cacher expiring`
+#[derive(Debug, Clone, PartialEq)]
+pub struct ExpiringEntry {
+    pub entry: CacheEntrySummary,
+    /// Time until the entry's TTL runs out; zero if it already has
+    pub expires_in: Duration,
+    /// Whether the hint file's `refresh_before_expiry` says to re-run the entry now
+    pub refresh: bool,
+}
+
+/// Error payload for a command that was killed for exceeding a limit
+///
+/// Returned inside an [`io::Error`] by [`CommandCache::run_command`] and the
+/// `execute_and_cache` family; retrieve it with
+/// `err.get_ref().and_then(|e| e.downcast_ref::<CommandKilled>())`.
+#[derive(Debug)]
+pub struct CommandKilled {
+    pub reason: KillReason,
+    /// Whatever the command wrote before it was killed
+    pub partial_output: CommandOutput,
+}
+
+/// Why a command was killed
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum KillReason {
+    /// It ran longer than the timeout
+    Timeout(Duration),
+    /// It wrote more than this many bytes to stdout and stderr
+    OutputLimit(u64),
+}
+
+impl std::fmt::Display for CommandKilled {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self.reason {
+            KillReason::Timeout(timeout) => {
+                write!(f, "Command timed out after {}s and was killed", timeout.as_secs_f64())
+            },
+            KillReason::OutputLimit(bytes) => {
+                write!(f, "Command exceeded the output limit of {} bytes and was killed", bytes)
+            },
+        }
+    }
+}
+
+impl std::error::Error for CommandKilled {}
+
+/// Error details for a command that exited with a non-zero status
+///
+/// Returned inside an [`io::Error`] by the `execute_and_cache` family, both
+/// when the command just failed and when a failure cached with
+/// `cache_failures` is served; retrieve it with
+/// `err.get_ref().and_then(|e| e.downcast_ref::<CommandFailed>())`.
+#[derive(Debug)]
+pub struct CommandFailed {
+    pub output: CommandOutput,
+    /// Whether the failure was served from the cache instead of being run
+    pub cached: bool,
+}
+
+impl std::fmt::Display for CommandFailed {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let cached = if self.cached { " (cached)" } else { "" };
+        write!(f, "Command failed with exit code {}{}: {}", self.output.exit_code, cached, self.output.stderr)
+    }
+}
+
+impl std::error::Error for CommandFailed {}
+
+/// Where the output returned by [`CommandCache::execute_with_decision`] came from
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum CacheSource {
+    /// A fresh entry already loaded into this `CommandCache`
+    MemoryHit,
+    /// A fresh entry read from the cache directory, or fetched from the remote store
+    DiskHit,
+    /// The entry had expired, or the run was forced, and running the command
+    /// again reproduced its output
+    Revalidated,
+    /// There was no entry, or the command's output changed; it was run and cached
+    Executed,
+    /// A `no_cache_if_*` condition held; the command was run without touching the cache
+    Bypassed,
+}
+
+impl CacheSource {
+    /// Whether the output was served without running the command
+    pub fn is_hit(&self) -> bool {
+        matches!(self, CacheSource::MemoryHit | CacheSource::DiskHit)
+    }
+}
+
+/// A command's output together with how the cache produced it
+#[derive(Debug, Clone, PartialEq)]
+pub struct CacheDecision {
+    pub output: String,
+    /// What the command wrote to stderr when it produced the output
+    pub stderr: String,
+    pub source: CacheSource,
+    /// How long the command took: just now if it was run, originally if it was a hit
+    pub run_time: Duration,
+    /// How long producing the output took, including any lookup and run
+    pub elapsed: Duration,
+}
+
+impl CacheDecision {
+    /// Whether the output was served without running the command
+    pub fn is_hit(&self) -> bool {
+        self.source.is_hit()
+    }
+}
+
+#[derive(Clone)]
 pub struct CacheEntry {
     pub command: String,
     pub output: String,
+    pub stderr: String,
     pub timestamp: SystemTime,
+    /// TTL the command set in its output, overriding the hint file
+    pub ttl: Option<Duration>,
+    /// How long the command took; zero for outputs stored with [`CommandCache::store`]
+    pub duration: Duration,
 }
 
+#[derive(Clone)]
 pub struct CommandCache {
-    cache: HashMap<String, String>,
+    cache: HashMap<String, CacheEntry>,
     cache_dir: PathBuf,
     hint_file: Option<HintFile>,
     current_dir: PathBuf,
     artifact_manager: ArtifactManager,
+    ignore: IgnoreRules,
+    max_age: Option<Duration>,
+    project_id: String,
+    limits: Limits,
+    shell: Option<bool>,
+    argv: Option<Vec<String>>,
+    key_schema: Option<u32>,
+    meta: BTreeMap<String, String>,
+    cipher: OnceLock<Cipher>,
+    clock: Arc<dyn Clock>,
+}
+
+impl Default for CommandCache {
+    fn default() -> Self {
+        Self::new()
+    }
 }
 
 impl CommandCache {
@@ -29,17 +363,34 @@ impl CommandCache {
         let mut cache_dir = cache_dir().unwrap_or_else(|| PathBuf::from("."));
         cache_dir.push("cacher");
         
-        // Create cache directory if it doesn't exist
-        let _ = fs::create_dir_all(&cache_dir);
-        
         // Get current directory
         let current_dir = std::env::current_dir().unwrap_or_else(|_| PathBuf::from("."));
         
+        Self::with_dirs(cache_dir, current_dir)
+    }
+    
+    /// Create a cache that stores entries in `cache_dir` and runs commands in `current_dir`
+    ///
+    /// The hint file is looked up from `current_dir` instead of the process's
+    /// working directory, so several caches for different projects can be used
+    /// side by side.
+    pub fn with_dirs(cache_dir: PathBuf, current_dir: PathBuf) -> Self {
+        // Create cache directory if it doesn't exist
+        let _ = fs::create_dir_all(&cache_dir);
+        let _ = write_layout_version(&cache_dir);
+        
         // Try to load hint file
         let hint_file = HintFile::find_hint_file(&current_dir);
         
+        // Namespace entries by project
+        let project_id = Self::derive_project_id(&current_dir, hint_file.as_ref());
+        
+        // Paths in .cacherignore are left out of dependency globs and directory artifacts
+        let ignore = IgnoreRules::load(&current_dir);
+        
         // Create artifact manager
-        let artifact_manager = ArtifactManager::new(cache_dir.clone());
+        let mut artifact_manager = ArtifactManager::new(cache_dir.clone());
+        artifact_manager.set_ignore(ignore.clone());
         
         CommandCache {
             cache: HashMap::new(),
@@ -47,15 +398,84 @@ impl CommandCache {
             hint_file,
             current_dir,
             artifact_manager,
+            ignore,
+            max_age: None,
+            project_id,
+            limits: Limits::default(),
+            shell: None,
+            argv: None,
+            key_schema: None,
+            meta: BTreeMap::new(),
+            cipher: OnceLock::new(),
+            clock: Arc::new(SystemClock),
         }
     }
 
     pub fn store(&mut self, command: &str, output: &str) {
-        self.cache.insert(command.to_string(), output.to_string());
+        self.cache.insert(command.to_string(), CacheEntry {
+            command: command.to_string(),
+            output: output.to_string(),
+            stderr: String::new(),
+            timestamp: self.clock.now(),
+            ttl: None,
+            duration: Duration::ZERO,
+        });
+    }
+    
+    /// Keep a command's output in memory, as if it had been created at `timestamp`
+    fn store_output(&mut self, command: &str, output: &CommandOutput, timestamp: SystemTime) {
+        self.cache.insert(command.to_string(), CacheEntry {
+            command: command.to_string(),
+            output: output.stdout.clone(),
+            stderr: output.stderr.clone(),
+            timestamp,
+            ttl: output.ttl,
+            duration: output.duration,
+        });
     }
 
     pub fn get(&self, command: &str) -> Option<&String> {
-        self.cache.get(command)
+        self.cache.get(command).map(|entry| &entry.output)
+    }
+    
+    fn derive_project_id(current_dir: &Path, hint_file: Option<&HintFile>) -> String {
+        let share_across_checkouts = hint_file
+            .and_then(|hint_file| hint_file.default.share_across_checkouts)
+            .unwrap_or(true);
+        project::project_identity(current_dir, share_across_checkouts)
+    }
+    
+    /// Get the identity of the project this cache namespaces entries by
+    ///
+    /// See [`project::project_identity`] for how it is derived.
+    pub fn project_id(&self) -> &str {
+        &self.project_id
+    }
+    
+    /// Pin the key schema version salted into every key
+    ///
+    /// Keys are still derived the current way; pinning only keeps the salt an
+    /// older cacher used, so its entries are found again where the derivation
+    /// gives the same hash for a command, e.g. when the change behind the
+    /// version bump concerns a setting the command doesn't use. Takes
+    /// precedence over `key_schema` in the hint file. Fails for versions this
+    /// version of cacher doesn't know.
+    pub fn set_key_schema(&mut self, version: u32) -> io::Result<()> {
+        if !key_schema_supported(version) {
+            return Err(Error::new(
+                ErrorKind::InvalidInput,
+                format!("Unsupported key schema {} (supported: 1 to {})", version, KEY_SCHEMA_VERSION),
+            ));
+        }
+        self.key_schema = Some(version);
+        Ok(())
+    }
+    
+    /// The key schema version salted into keys
+    pub fn key_schema(&self) -> u32 {
+        self.key_schema
+            .or_else(|| self.hint_file.as_ref().and_then(|hint_file| hint_file.default.key_schema))
+            .unwrap_or(KEY_SCHEMA_VERSION)
     }
     
     pub fn generate_id(&self, command: &str) -> String {
@@ -64,16 +484,20 @@ impl CommandCache {
         // Add the command itself to the hash
         hasher.update(command.as_bytes());
         
+        // Keep entries from different projects apart
+        hasher.update(format!("\nproject={}\n", self.project_id).as_bytes());
+        
+        // Keep entries keyed under other semantics apart
+        hasher.update(format!("key_schema={}\n", self.key_schema()).as_bytes());
+        
         // If we have a hint file, check for command-specific settings
         if let Some(hint_file) = &self.hint_file {
             // Check if there's a matching command pattern
             if let Some(command_hint) = hint_file.find_matching_command(command) {
                 // Include specified environment variables in the hash
-                for env_var in &command_hint.include_env {
-                    if let Ok(value) = env::var(env_var) {
-                        hasher.update(format!("{}={}", env_var, value).as_bytes());
-                    }
-                }
+                hash_env_vars(&mut hasher, command_hint.include_env.iter().map(String::as_str));
+                
+                let case_insensitive = hint_file.case_insensitive_keys(command);
                 
                 // Include file dependencies in the hash
                 for dependency in &command_hint.depends_on {
@@ -84,29 +508,35 @@ impl CommandCache {
                                 if let Ok(metadata) = fs::metadata(&path) {
                                     if let Ok(modified) = metadata.modified() {
                                         if let Ok(duration) = modified.duration_since(SystemTime::UNIX_EPOCH) {
-                                            hasher.update(format!("{}={}", file, duration.as_secs()).as_bytes());
+                                            hasher.update(format!("{}={}", key_path(file, case_insensitive), duration.as_secs()).as_bytes());
                                         }
                                     }
                                 }
                             }
                         },
                         Dependency::Files { files } => {
-                            // Use glob pattern to find matching files
-                            if let Ok(entries) = glob::glob(&format!("{}/{}", self.current_dir.display(), files)) {
-                                for entry in entries {
-                                    if let Ok(path) = entry {
-                                        if let Ok(metadata) = fs::metadata(&path) {
-                                            if let Ok(modified) = metadata.modified() {
-                                                if let Ok(duration) = modified.duration_since(SystemTime::UNIX_EPOCH) {
-                                                    if let Some(path_str) = path.to_str() {
-                                                        hasher.update(format!("{}={}", path_str, duration.as_secs()).as_bytes());
-                                                    }
-                                                }
+                            let mut matched = Vec::new();
+                            for path in self.glob_dependency(files, case_insensitive) {
+                                if let Ok(metadata) = fs::metadata(&path) {
+                                    if let Ok(modified) = metadata.modified() {
+                                        if let Ok(duration) = modified.duration_since(SystemTime::UNIX_EPOCH) {
+                                            // Hash the relative path so checkouts at different locations agree
+                                            let relative_path = path.strip_prefix(&self.current_dir).unwrap_or(&path);
+                                            if let Some(path_str) = relative_path.to_str() {
+                                                matched.push(format!("{}={}", key_path(path_str, case_insensitive), duration.as_secs()));
                                             }
                                         }
                                     }
                                 }
                             }
+                            
+                            // Lowercased paths may sort differently than the files did
+                            if case_insensitive {
+                                matched.sort();
+                            }
+                            for line in matched {
+                                hasher.update(line.as_bytes());
+                            }
                         },
                         Dependency::Lines { lines } => {
                             let path = self.current_dir.join(&lines.file);
@@ -124,247 +554,1712 @@ impl CommandCache {
                                     }
                                 }
                             }
+                        },
+                        Dependency::Lockfile { lockfile } => {
+                            // Hash lockfile content rather than mtime, so touching
+                            // the file without changing dependencies keeps the key
+                            for (name, path) in lockfile::resolve(&self.current_dir, lockfile) {
+                                if let Ok(content) = lockfile::dependency_content(&path) {
+                                    hasher.update(format!("{}=", key_path(&name, case_insensitive)).as_bytes());
+                                    hasher.update(content.as_bytes());
+                                }
+                            }
+                        },
+                        Dependency::ComposeFile { compose_file } => {
+                            // The project name decides which containers the command talks to
+                            hasher.update(format!("compose_project={}\n", compose::project_name(&self.current_dir)).as_bytes());
+                            for (name, path) in compose::resolve(&self.current_dir, compose_file) {
+                                if let Ok(content) = fs::read(&path) {
+                                    hasher.update(format!("{}=", key_path(&name, case_insensitive)).as_bytes());
+                                    hasher.update(&content);
+                                }
+                            }
                         }
                     }
                 }
             } else {
                 // No specific command match, use default environment variables
-                for env_var in &hint_file.default.include_env {
-                    if let Ok(value) = env::var(env_var) {
-                        hasher.update(format!("{}={}", env_var, value).as_bytes());
+                hash_env_vars(&mut hasher, hint_file.default.include_env.iter().map(String::as_str));
+            }
+            
+            // Include the time zone and locale so differently localized output isn't shared
+            if hint_file.include_locale(command) {
+                hasher.update(b"locale\n");
+                hash_env_vars(&mut hasher, LOCALE_ENV_VARS.iter().copied());
+            }
+            
+            // Include the program's binary, so rebuilding or reinstalling the tool re-runs the command
+            if hint_file.include_binary(command) {
+                let project_root = project::project_root(&self.current_dir);
+                let fingerprint = self.resolve_binary(command).and_then(|path| binary::fingerprint(&path, &project_root));
+                hasher.update(format!("binary={}\n", fingerprint.unwrap_or_default()).as_bytes());
+            }
+        }
+        
+        // Chained commands also depend on whatever each part depends on
+        if let Some(parts) = self.chain_parts(command) {
+            for part in parts {
+                hasher.update(format!("part={}\n", part.id.unwrap_or_default()).as_bytes());
+            }
+        }
+        
+        format!("{:x}", hasher.finalize())
+    }
+    
+    /// The files a `files:` dependency matches, minus ignored ones
+    fn glob_dependency(&self, files: &str, case_insensitive: bool) -> Vec<PathBuf> {
+        let options = glob::MatchOptions { case_sensitive: !case_insensitive, ..glob::MatchOptions::new() };
+        match glob::glob_with(&format!("{}/{}", self.current_dir.display(), files), options) {
+            Ok(entries) => entries.flatten().filter(|path| !self.ignore.is_ignored(path, path.is_dir())).collect(),
+            Err(_) => Vec::new(),
+        }
+    }
+    
+    /// Find the binary a command's program resolves to, for `include_binary`
+    fn resolve_binary(&self, command: &str) -> Option<PathBuf> {
+        binary::program(command).and_then(|program| binary::resolve(program, &self.current_dir))
+    }
+    
+    /// Explain what a command's cache key is made of
+    ///
+    /// For chained shell commands, dependencies and the durations recorded in
+    /// the cached entry are attributed to each part, and parts whose key has
+    /// changed since the entry was cached are flagged.
+    pub fn explain(&self, command: &str) -> io::Result<Explanation> {
+        let cached = self.load_entry(command)?.map(|(_, metadata)| metadata);
+        let chain_parts = self.chain_parts(command).unwrap_or_default();
+        let previous = match cached {
+            None if !chain_parts.is_empty() => self.previous_chain_entry(command, &chain_parts)?,
+            _ => None,
+        };
+        let recorded_parts = cached.as_ref().or(previous.as_ref()).map(|metadata| &metadata.parts);
+        
+        let mut parts = Vec::new();
+        for (i, mut part) in chain_parts.into_iter().enumerate() {
+            let recorded = recorded_parts
+                .and_then(|recorded_parts| recorded_parts.get(i))
+                .filter(|recorded| recorded.command == part.command);
+            
+            let changed = match recorded {
+                Some(recorded) => {
+                    part.duration_ms = recorded.duration_ms;
+                    part.exit_code = recorded.exit_code;
+                    recorded.id != part.id
+                },
+                None => recorded_parts.is_some(),
+            };
+            
+            parts.push(PartExplanation {
+                inputs: self.hint_inputs(&part.command),
+                part,
+                changed,
+            });
+        }
+        
+        Ok(Explanation {
+            id: self.generate_id(command),
+            key_schema: self.key_schema(),
+            shell: self.shell_enabled(command),
+            inputs: self.entry_inputs(command),
+            cached,
+            previous,
+            parts,
+        })
+    }
+    
+    /// Find the newest entry of a chained command cached under an earlier key
+    ///
+    /// Entries of the same command from other projects are told apart by
+    /// their parts: part IDs include the project, so an entry of this project
+    /// shares the ID of at least one part that didn't change.
+    fn previous_chain_entry(&self, command: &str, parts: &[ChainPart]) -> io::Result<Option<EntryMetadata>> {
+        let mut newest: Option<EntryMetadata> = None;
+        
+        for (id, record) in SearchIndex::new(&self.cache_dir).records()? {
+            if record.command != display_command(command) || newest.as_ref().is_some_and(|newest| newest.timestamp >= record.timestamp) {
+                continue;
+            }
+            
+            let entry_dir = self.cache_dir.join(&id);
+            if let Ok(Some(metadata)) = EntryMetadata::read(&entry_dir.join("metadata.json")) {
+                if metadata.full_command(&entry_dir).ok().as_deref() != Some(command) {
+                    continue;
+                }
+                
+                let same_project = metadata.parts.iter().any(|recorded| {
+                    parts.iter().any(|part| part.id.is_some() && part.id == recorded.id)
+                });
+                if same_project {
+                    newest = Some(metadata);
+                }
+            }
+        }
+        
+        Ok(newest)
+    }
+    
+    /// Describe what the hint file adds to a command's cache key, for the search index
+    ///
+    /// Mirrors [`generate_id`](Self::generate_id): dependencies are recorded both
+    /// as declared and as the files they resolved to, and a chained command
+    /// takes on the inputs of each of its parts.
+    pub fn entry_inputs(&self, command: &str) -> EntryInputs {
+        let mut inputs = self.hint_inputs(command);
+        
+        if let Some(parts) = self.chain_parts(command) {
+            for part in parts {
+                let part_inputs = self.hint_inputs(&part.command);
+                inputs.env.extend(part_inputs.env);
+                inputs.dependencies.extend(part_inputs.dependencies);
+                inputs.tags.extend(part_inputs.tags);
+            }
+            inputs.env.sort();
+            inputs.env.dedup();
+            inputs.dependencies.sort();
+            inputs.dependencies.dedup();
+            inputs.tags.dedup();
+        }
+        
+        inputs
+    }
+    
+    /// The inputs the hint matching `command` declares
+    fn hint_inputs(&self, command: &str) -> EntryInputs {
+        let mut inputs = EntryInputs::default();
+        
+        let hint_file = match &self.hint_file {
+            Some(hint_file) => hint_file,
+            None => return inputs,
+        };
+        
+        if hint_file.include_binary(command) {
+            let project_root = project::project_root(&self.current_dir);
+            inputs.dependencies.extend(self.resolve_binary(command).map(|path| key_path(&binary::display_path(&path, &project_root).to_string_lossy(), false)));
+        }
+        
+        let command_hint = match hint_file.find_matching_command(command) {
+            Some(command_hint) => command_hint,
+            None => {
+                inputs.env = hint_file.default.include_env.iter().cloned().collect();
+                inputs.env.sort();
+                return inputs;
+            }
+        };
+        
+        inputs.pattern = Some(command_hint.pattern.clone());
+        inputs.env = command_hint.include_env.iter().cloned().collect();
+        inputs.env.sort();
+        inputs.tags = command_hint.tags.clone();
+        
+        for dependency in &command_hint.depends_on {
+            match dependency {
+                Dependency::File { file } => inputs.dependencies.push(file.clone()),
+                Dependency::Files { files } => {
+                    inputs.dependencies.push(files.clone());
+                    for path in self.glob_dependency(files, hint_file.case_insensitive_keys(command)) {
+                        let relative_path = path.strip_prefix(&self.current_dir).unwrap_or(&path);
+                        inputs.dependencies.push(key_path(&relative_path.to_string_lossy(), false));
                     }
+                },
+                Dependency::Lines { lines } => inputs.dependencies.push(lines.file.clone()),
+                Dependency::Lockfile { lockfile } => {
+                    inputs.dependencies.push(lockfile.clone());
+                    inputs.dependencies.extend(lockfile::resolve(&self.current_dir, lockfile).into_iter().map(|(name, _)| name));
+                },
+                Dependency::ComposeFile { compose_file } => {
+                    inputs.dependencies.push(compose_file.clone());
+                    inputs.dependencies.extend(compose::resolve(&self.current_dir, compose_file).into_iter().map(|(name, _)| name));
+                },
+            }
+        }
+        inputs.dependencies.sort();
+        inputs.dependencies.dedup();
+        
+        inputs
+    }
+    
+    /// Check that every environment variable required by the hint file is set
+    ///
+    /// Requirements from the default settings apply to all commands and are
+    /// combined with those of the matching command hint.
+    pub fn check_required_env(&self, command: &str) -> io::Result<()> {
+        if let Some(hint_file) = &self.hint_file {
+            let mut required: Vec<&String> = hint_file.default.require_env.iter().collect();
+            if let Some(command_hint) = hint_file.find_matching_command(command) {
+                required.extend(command_hint.require_env.iter());
+            }
+            required.sort();
+            
+            for env_var in required {
+                if env::var_os(env_var).is_none() {
+                    return Err(Error::new(
+                        ErrorKind::NotFound,
+                        format!("Required environment variable {} is not set", env_var)
+                    ));
+                }
+            }
+        }
+        
+        Ok(())
+    }
+    
+    /// The version of the on-disk layout this cache uses, see [`LAYOUT_VERSION`]
+    pub fn layout_version() -> u32 {
+        LAYOUT_VERSION
+    }
+    
+    /// The layout version recorded in the cache dir's [`LAYOUT_FILE`]
+    ///
+    /// None for cache dirs written before the file was introduced, or restored
+    /// without it.
+    pub fn stored_layout_version(&self) -> io::Result<Option<u32>> {
+        match fs::read_to_string(self.cache_dir.join(LAYOUT_FILE)) {
+            Ok(content) => content.trim().parse().map(Some).map_err(|e| {
+                Error::new(ErrorKind::InvalidData, format!("Invalid {}: {}", self.cache_dir.join(LAYOUT_FILE).display(), e))
+            }),
+            Err(e) if e.kind() == ErrorKind::NotFound => Ok(None),
+            Err(e) => Err(e),
+        }
+    }
+    
+    /// The directory entries are stored in
+    pub fn cache_dir(&self) -> &Path {
+        &self.cache_dir
+    }
+    
+    /// The directory the entry with a cache ID is stored in, whether or not it exists
+    ///
+    /// Unlike [`CommandCache::get_cache_path`], this never creates the directory.
+    pub fn entry_dir(&self, id: &str) -> PathBuf {
+        self.cache_dir.join(id)
+    }
+    
+    /// The directory the artifacts of the entry with a cache ID are stored in,
+    /// whether or not it exists
+    pub fn artifact_dir(&self, id: &str) -> PathBuf {
+        self.entry_dir(id).join("artifacts")
+    }
+    
+    pub fn get_cache_path(&self, id: &str) -> PathBuf {
+        let cache_dir = self.cache_dir.join(id);
+        fs::create_dir_all(&cache_dir).unwrap_or(());
+        cache_dir
+    }
+    
+    pub fn get_stdout_path(&self, id: &str) -> PathBuf {
+        self.get_cache_path(id).join("stdout")
+    }
+    
+    pub fn get_stderr_path(&self, id: &str) -> PathBuf {
+        self.get_cache_path(id).join("stderr")
+    }
+    
+    pub fn get_metadata_path(&self, id: &str) -> PathBuf {
+        self.get_cache_path(id).join("metadata.json")
+    }
+    
+    pub fn save_to_disk(&self, command: &str, output: &str) -> io::Result<()> {
+        self.save_output_to_disk(command, &CommandOutput {
+            stdout: output.to_string(),
+            ..CommandOutput::default()
+        })
+    }
+    
+    /// Save stdout, stderr and the exit code of a command to the disk cache
+    ///
+    /// The entry is written to a staging directory and renamed into place, so
+    /// a save that is interrupted never leaves a partial entry behind. The
+    /// `complete` marker, holding a checksum of stdout and stderr, is written
+    /// last and checked whenever the entry is loaded.
+    pub fn save_output_to_disk(&self, command: &str, output: &CommandOutput) -> io::Result<()> {
+        self.save_entry(&self.generate_id(command), command, output)
+    }
+    
+    /// Save a command's output to the disk cache under a given cache ID
+    ///
+    /// Fails for cache dirs written with another layout version, which this
+    /// version of cacher might corrupt.
+    fn save_entry(&self, id: &str, command: &str, output: &CommandOutput) -> io::Result<()> {
+        match self.stored_layout_version()? {
+            Some(version) if version != LAYOUT_VERSION => {
+                return Err(Error::new(
+                    ErrorKind::Unsupported,
+                    format!("Cache directory {} uses layout version {}, but this cacher writes version {}", self.cache_dir.display(), version, LAYOUT_VERSION),
+                ));
+            },
+            Some(_) => {},
+            None => write_layout_version(&self.cache_dir)?,
+        }
+        
+        let staging = tempfile::Builder::new().prefix(STAGING_PREFIX).tempdir_in(&self.cache_dir)?;
+        
+        // Save stdout and stderr to separate files
+        let encrypt = self.encryption_enabled(command);
+        let stdout = self.encode_entry_file(id, "stdout", output.stdout.as_bytes(), encrypt)?;
+        let stderr = self.encode_entry_file(id, "stderr", output.stderr.as_bytes(), encrypt)?;
+        fs::write(staging.path().join("stdout"), &stdout)?;
+        fs::write(staging.path().join("stderr"), &stderr)?;
+        
+        // Save metadata to a JSON file, keeping the pin and usage of the entry it replaces
+        let mut metadata = EntryMetadata::new(command, self.clock.now(), output.exit_code, output.duration);
+        if let Ok(Some(old)) = EntryMetadata::read(&self.cache_dir.join(id).join("metadata.json")) {
+            metadata.pinned = old.pinned;
+            metadata.hit_count = old.hit_count;
+            metadata.last_accessed = old.last_accessed;
+        }
+        metadata.project = Some(self.project_id.clone());
+        metadata.encrypted = Some(encrypt);
+        metadata.ttl = output.ttl.map(|ttl| ttl.as_secs());
+        metadata.inputs = self.entry_inputs(command);
+        metadata.meta = self.entry_meta(command);
+        metadata.parts = output.parts.clone();
+        metadata.write(&staging.path().join("metadata.json"))?;
+        if metadata.command_bytes.is_some() {
+            crate::metadata::write_full_command(staging.path(), command)?;
+        }
+        
+        let history_depth = self.hint_file.as_ref().map_or(0, |hint_file| hint_file.history(command));
+        if history_depth > 0 {
+            self.store_history(id, staging.path(), output.stdout.as_bytes(), history_depth, encrypt)?;
+        }
+        
+        fs::write(staging.path().join(COMPLETE_MARKER), output_checksum(&stdout, &stderr))?;
+        replace_dir(&self.cache_dir, staging.path(), id)?;
+        
+        SearchIndex::new(&self.cache_dir).insert(id, IndexRecord::from_metadata(&metadata))
+    }
+    
+    /// Keep the output being replaced as a generation in the history of the new entry
+    ///
+    /// Generations are stored as reverse deltas: the newest one against the new
+    /// stdout, each older one against the generation after it. Only the
+    /// newest `depth` generations are kept.
+    fn store_history(&self, id: &str, staging: &Path, stdout: &[u8], depth: usize, encrypt: bool) -> io::Result<()> {
+        // Without a readable previous output there is nothing to keep
+        let (previous, previous_metadata) = match self.load_entry_by_id(id) {
+            Ok(Some(entry)) => entry,
+            _ => return Ok(()),
+        };
+        
+        fs::create_dir_all(staging.join(HISTORY_DIR))?;
+        
+        let sequences = self.history_sequences(id)?;
+        let next = sequences.last().map_or(1, |sequence| sequence + 1);
+        for sequence in &sequences[sequences.len().saturating_sub(depth - 1)..] {
+            let name = history::delta_file_name(*sequence);
+            let path = self.cache_dir.join(id).join(&name);
+            if previous_metadata.encrypted == Some(encrypt) {
+                fs::copy(&path, staging.join(&name))?;
+            } else {
+                // The whole entry is either encrypted or not, so older generations follow the new one
+                let delta = self.decode_entry_bytes(id, &name, fs::read(&path)?, &previous_metadata)?;
+                fs::write(staging.join(&name), self.encode_entry_file(id, &name, &delta, encrypt)?)?;
+            }
+        }
+        
+        let name = history::delta_file_name(next);
+        let delta = history::diff(stdout, previous.stdout.as_bytes(), previous_metadata.created_at());
+        fs::write(staging.join(&name), self.encode_entry_file(id, &name, &delta, encrypt)?)
+    }
+    
+    /// Sequence numbers of the generations stored in an entry's history, oldest first
+    fn history_sequences(&self, id: &str) -> io::Result<Vec<u64>> {
+        let mut sequences = Vec::new();
+        
+        let entries = match fs::read_dir(self.cache_dir.join(id).join(HISTORY_DIR)) {
+            Ok(entries) => entries,
+            Err(e) if e.kind() == ErrorKind::NotFound => return Ok(sequences),
+            Err(e) => return Err(e),
+        };
+        
+        for entry in entries {
+            if let Some(sequence) = history::parse_sequence(&entry?.file_name().to_string_lossy()) {
+                sequences.push(sequence);
+            }
+        }
+        
+        sequences.sort();
+        Ok(sequences)
+    }
+    
+    /// Earlier outputs of a command, newest first
+    ///
+    /// Only kept for commands with `history` set in the hint file. The
+    /// current output isn't included.
+    pub fn history(&self, command: &str) -> io::Result<Vec<HistoryEntry>> {
+        let id = self.generate_id(command);
+        let (output, metadata) = match self.load_entry_by_id(&id)? {
+            Some(entry) => entry,
+            None => return Ok(Vec::new()),
+        };
+        
+        let mut generations = Vec::new();
+        let mut stdout = output.stdout.into_bytes();
+        for sequence in self.history_sequences(&id)?.into_iter().rev() {
+            let name = history::delta_file_name(sequence);
+            let delta = self.decode_entry_bytes(&id, &name, fs::read(self.cache_dir.join(&id).join(&name))?, &metadata)?;
+            
+            stdout = history::apply(&stdout, &delta)?;
+            generations.push(HistoryEntry {
+                created_at: history::created_at(&delta)?,
+                stdout: String::from_utf8(stdout.clone()).map_err(|e| Error::new(ErrorKind::InvalidData, e))?,
+            });
+        }
+        
+        Ok(generations)
+    }
+    
+    /// The remote store configured in the hint file, if any
+    pub fn remote(&self) -> Option<RemoteStore> {
+        let settings = self.hint_file.as_ref()?.remote.as_ref()?;
+        Some(RemoteStore::new(self.current_dir.join(&settings.path)))
+    }
+    
+    /// Copy a freshly stored entry to the remote store, following the command's write policy
+    ///
+    /// With `write: back` the entry is added to the upload queue instead,
+    /// which [`CommandCache::sync`] drains. A failed upload only prints a
+    /// warning, since the entry is still cached locally. Encrypted entries
+    /// stay local, since other machines couldn't decrypt them.
+    fn replicate(&self, command: &str, id: &str) {
+        let (remote, hint_file) = match (self.remote(), &self.hint_file) {
+            (Some(remote), Some(hint_file)) => (remote, hint_file),
+            _ => return,
+        };
+        if self.encryption_enabled(command) {
+            return;
+        }
+        
+        let entry_dir = self.cache_dir.join(id);
+        match hint_file.write_policy(command) {
+            WritePolicy::Through => {
+                if let Err(e) = remote.upload(&entry_dir, id) {
+                    eprintln!("Warning: failed to upload to {}: {}", remote.root().display(), e);
+                }
+            },
+            WritePolicy::Back => {
+                if let Err(e) = UploadQueue::new(&self.cache_dir).push(id, &remote) {
+                    eprintln!("Warning: failed to queue upload to {}: {}", remote.root().display(), e);
+                }
+            },
+            WritePolicy::LocalOnly => {},
+        }
+    }
+    
+    /// IDs of the entries queued for upload by `write: back` commands
+    pub fn pending_uploads(&self) -> io::Result<Vec<String>> {
+        UploadQueue::new(&self.cache_dir).pending()
+    }
+    
+    /// Upload the entries queued by `write: back` commands to the remote store
+    ///
+    /// Entries that fail to upload stay queued, so the next sync retries them.
+    pub fn sync(&self) -> io::Result<SyncReport> {
+        UploadQueue::new(&self.cache_dir).drain(&self.cache_dir)
+    }
+    
+    pub fn load_from_disk(&self, command: &str) -> io::Result<Option<String>> {
+        Ok(self.load_entry(command)?.map(|(output, _)| output.stdout))
+    }
+    
+    /// Whether a command's entries should be encrypted at rest
+    pub fn encryption_enabled(&self, command: &str) -> bool {
+        self.hint_file.as_ref().is_some_and(|hint_file| hint_file.encrypt(command))
+    }
+    
+    /// Use `cipher` for encrypted entries instead of the key from the environment
+    pub fn set_cipher(&mut self, cipher: Cipher) {
+        self.cipher = OnceLock::from(cipher);
+    }
+    
+    /// Get the cipher for encrypted entries, loading the key on first use
+    ///
+    /// See [`Cipher::from_environment`] for where the key comes from.
+    fn cipher(&self) -> io::Result<&Cipher> {
+        if let Some(cipher) = self.cipher.get() {
+            return Ok(cipher);
+        }
+        
+        let cipher = Cipher::from_environment(&self.cache_dir)?;
+        Ok(self.cipher.get_or_init(|| cipher))
+    }
+    
+    /// Prepare the contents of one of an entry's files, encrypting them if requested
+    fn encode_entry_file(&self, id: &str, name: &str, contents: &[u8], encrypt: bool) -> io::Result<Vec<u8>> {
+        if encrypt {
+            self.cipher()?.encrypt(contents, &format!("{}/{}", id, name))
+        } else {
+            Ok(contents.to_vec())
+        }
+    }
+    
+    /// Decode the contents of one of an entry's files, decrypting them if they were encrypted
+    fn decode_entry_file(&self, id: &str, name: &str, contents: Vec<u8>, metadata: &EntryMetadata) -> io::Result<String> {
+        let contents = self.decode_entry_bytes(id, name, contents, metadata)?;
+        String::from_utf8(contents).map_err(|e| Error::new(ErrorKind::InvalidData, e))
+    }
+    
+    /// Decrypt the contents of one of an entry's files if its metadata says they are encrypted
+    fn decode_entry_bytes(&self, id: &str, name: &str, contents: Vec<u8>, metadata: &EntryMetadata) -> io::Result<Vec<u8>> {
+        if metadata.is_encrypted(&contents) {
+            self.cipher()?.decrypt(&contents, &format!("{}/{}", id, name))
+        } else {
+            Ok(contents)
+        }
+    }
+    
+    /// Load a cached entry's full output and metadata, regardless of its age
+    pub fn load_entry(&self, command: &str) -> io::Result<Option<(CommandOutput, EntryMetadata)>> {
+        self.load_entry_by_id(&self.generate_id(command))
+    }
+    
+    /// Load the entry stored under a cache ID, regardless of its age
+    ///
+    /// Entries that are incomplete or corrupted, i.e. whose `complete` marker
+    /// is missing or doesn't match their output, are treated as missing.
+    ///
+    /// Entries missing locally are fetched from the remote store, if one is configured.
+    pub fn load_entry_by_id(&self, id: &str) -> io::Result<Option<(CommandOutput, EntryMetadata)>> {
+        let mut output = read_complete_output(&self.cache_dir.join(id))?;
+        if output.is_none() {
+            if let Some(remote) = self.remote() {
+                match remote.download(id, &self.cache_dir) {
+                    Ok(true) => {
+                        output = read_complete_output(&self.cache_dir.join(id))?;
+                        if let Some(metadata) = EntryMetadata::read(&self.get_metadata_path(id))? {
+                            SearchIndex::new(&self.cache_dir).insert(id, IndexRecord::from_metadata(&metadata))?;
+                        }
+                    },
+                    Ok(false) => {},
+                    Err(e) => eprintln!("Warning: failed to download from {}: {}", remote.root().display(), e),
+                }
+            }
+        }
+        
+        let (stdout, stderr) = match output {
+            Some(output) => output,
+            None => return Ok(None),
+        };
+        
+        let metadata = match EntryMetadata::read(&self.get_metadata_path(id))? {
+            Some(metadata) => metadata,
+            None => return Ok(None),
+        };
+        
+        let stdout = self.decode_entry_file(id, "stdout", stdout, &metadata)?;
+        let stderr = self.decode_entry_file(id, "stderr", stderr, &metadata)?;
+        
+        let output = CommandOutput {
+            stdout,
+            stderr,
+            exit_code: metadata.exit_code,
+            duration: metadata.duration(),
+            parts: metadata.parts.clone(),
+            ttl: metadata.ttl.map(Duration::from_secs),
+        };
+        
+        Ok(Some((output, metadata)))
+    }
+    
+    /// Remove entries that were only partially written or have been corrupted
+    ///
+    /// Also removes staging directories left behind by saves that were
+    /// interrupted, once they are old enough that their writer can't still be
+    /// running. Pinned entries are removed too if they are incomplete, since
+    /// they can't be served anyway.
+    ///
+    /// # Returns
+    ///
+    /// The number of directories removed
+    pub fn gc(&self) -> io::Result<usize> {
+        let mut removed = 0;
+        let mut removed_entries = Vec::new();
+        
+        if !self.cache_dir.exists() {
+            return Ok(removed);
+        }
+        
+        for entry in fs::read_dir(&self.cache_dir)? {
+            let entry = entry?;
+            let path = entry.path();
+            let name = entry.file_name().to_string_lossy().into_owned();
+            
+            if !path.is_dir() {
+                continue;
+            }
+            
+            let orphaned = if name.starts_with(STAGING_PREFIX) || name.starts_with(REPLACED_PREFIX) {
+                let modified = entry.metadata()?.modified()?;
+                self.clock.now().duration_since(modified).unwrap_or_default() > STAGING_GRACE_PERIOD
+            } else if name.starts_with('.') {
+                // Not an entry, e.g. the upload queue
+                false
+            } else {
+                read_complete_output(&path)?.is_none()
+            };
+            
+            if orphaned {
+                match fs::remove_dir_all(&path) {
+                    Ok(()) => removed += 1,
+                    // Someone else cleaned it up first
+                    Err(e) if e.kind() == ErrorKind::NotFound => {},
+                    Err(e) => return Err(e),
+                }
+                if !name.starts_with('.') {
+                    removed_entries.push(name);
+                }
+            }
+        }
+        
+        SearchIndex::new(&self.cache_dir).remove(removed_entries.iter().map(String::as_str))?;
+        Ok(removed)
+    }
+    
+    /// Run a command and capture its output, without treating a non-zero exit as an error
+    ///
+    /// A command that exceeds its timeout or output limit is killed, and the
+    /// returned error carries a [`CommandKilled`] with its partial output.
+    pub fn run_command(&self, command: &str) -> io::Result<CommandOutput> {
+        if self.shell_enabled(command) {
+            return self.run_shell_command(command);
+        }
+        
+        // Use the arguments as given, or parse the command into program and arguments
+        let argv = match &self.argv {
+            Some(argv) if process::command_line(argv) == command => argv.clone(),
+            _ => process::split_command_line(command),
+        };
+        let (program, args) = argv.split_first().ok_or_else(|| {
+            Error::new(ErrorKind::InvalidInput, "Empty command")
+        })?;
+        
+        let mut process = std::process::Command::new(program);
+        process.args(args).current_dir(&self.current_dir);
+        self.run_process(command, &mut process)
+    }
+    
+    /// Run a command through the shell
+    ///
+    /// Chains like `npm ci && npm run build` are run as one script that marks
+    /// the end of each part, so the output records how long each part took.
+    fn run_shell_command(&self, command: &str) -> io::Result<CommandOutput> {
+        let mut parts = match self.chain_parts(command) {
+            Some(parts) if !cfg!(windows) => parts,
+            _ => {
+                let mut process = process::shell_command(command);
+                process.current_dir(&self.current_dir);
+                return self.run_process(command, &mut process);
+            }
+        };
+        
+        let marks = tempfile::NamedTempFile::new()?;
+        let mut process = process::shell_command(&chain::instrument(&parts));
+        process.current_dir(&self.current_dir).env(chain::MARKS_ENV, marks.path());
+        
+        let watcher = MarkWatcher::start(marks.path());
+        let result = self.run_process(command, &mut process);
+        let marks = watcher.finish();
+        
+        let mut output = result?;
+        chain::attribute(&mut parts, &marks);
+        output.parts = parts;
+        Ok(output)
+    }
+    
+    /// Run a prepared process with the limits that apply to `command`
+    fn run_process(&self, command: &str, process: &mut std::process::Command) -> io::Result<CommandOutput> {
+        let limits = self.get_effective_limits(command);
+        let result = process::run_with_limits(process, limits)
+            .map_err(|e| {
+                Error::other(format!("Failed to execute command: {}", e))
+            })?;
+        
+        let output = CommandOutput {
+            stdout: String::from_utf8_lossy(&result.stdout).to_string(),
+            stderr: String::from_utf8_lossy(&result.stderr).to_string(),
+            exit_code: result.status.and_then(|status| status.code()).unwrap_or(-1),
+            duration: result.duration,
+            parts: Vec::new(),
+            ttl: None,
+        };
+        
+        // A killed command's output is incomplete, so it must never be cached
+        let reason = match (result.timed_out, limits) {
+            (true, Limits { timeout: Some(timeout), .. }) => Some(KillReason::Timeout(timeout)),
+            (false, Limits { max_output_bytes: Some(bytes), .. }) if result.output_limit_exceeded => {
+                Some(KillReason::OutputLimit(bytes))
+            },
+            _ => None,
+        };
+        
+        if let Some(reason) = reason {
+            let kind = match reason {
+                KillReason::Timeout(_) => ErrorKind::TimedOut,
+                KillReason::OutputLimit(_) => ErrorKind::Other,
+            };
+            return Err(Error::new(kind, CommandKilled { reason, partial_output: output }));
+        }
+        
+        Ok(output)
+    }
+    
+    /// Get the timeout and output limit for a command
+    ///
+    /// Limits set with [`CommandCache::set_timeout`] and
+    /// [`CommandCache::set_max_output_bytes`] take precedence over the matching
+    /// command hint, which takes precedence over the hint file's defaults.
+    pub fn get_effective_limits(&self, command: &str) -> Limits {
+        let mut limits = Limits::default();
+        
+        if let Some(hint_file) = &self.hint_file {
+            let command_hint = hint_file.find_matching_command(command);
+            limits.timeout = command_hint.and_then(|cmd| cmd.timeout)
+                .or(hint_file.default.timeout)
+                .map(Duration::from_secs);
+            limits.max_output_bytes = command_hint.and_then(|cmd| cmd.max_output_bytes)
+                .or(hint_file.default.max_output_bytes);
+        }
+        
+        Limits {
+            timeout: self.limits.timeout.or(limits.timeout),
+            max_output_bytes: self.limits.max_output_bytes.or(limits.max_output_bytes),
+        }
+    }
+    
+    /// Kill commands that run longer than `timeout`, overriding the hint file
+    pub fn set_timeout(&mut self, timeout: Option<Duration>) {
+        self.limits.timeout = timeout;
+    }
+    
+    /// Kill commands that write more than `max_output_bytes`, overriding the hint file
+    pub fn set_max_output_bytes(&mut self, max_output_bytes: Option<u64>) {
+        self.limits.max_output_bytes = max_output_bytes;
+    }
+    
+    /// Run every command through the shell, overriding the hint file
+    pub fn set_shell(&mut self, shell: bool) {
+        self.shell = Some(shell);
+    }
+    
+    /// Run the command given by `argv` with exactly these arguments
+    ///
+    /// Its cache key is [`process::command_line`] of `argv`, which quotes
+    /// arguments containing spaces or quotes. Passing that string to the
+    /// `execute_and_cache` family runs `argv` as given instead of splitting
+    /// the string again, so an argument like `"%h %s"` reaches the program intact.
+    pub fn set_argv(&mut self, argv: Vec<String>) {
+        self.argv = Some(argv);
+    }
+    
+    /// Attach key-value pairs to the entries cached from now on, e.g. a build number
+    ///
+    /// They are added to the hint file's `meta`, replacing pairs with the same
+    /// key, and don't affect cache keys.
+    pub fn set_meta(&mut self, meta: BTreeMap<String, String>) {
+        self.meta = meta;
+    }
+    
+    /// The key-value pairs recorded in the metadata of a command's entries
+    pub fn entry_meta(&self, command: &str) -> BTreeMap<String, String> {
+        let mut meta = self.hint_file.as_ref().map(|hint_file| hint_file.meta(command)).unwrap_or_default();
+        meta.extend(self.meta.clone());
+        meta
+    }
+    
+    /// Whether a command is run through the shell rather than split on whitespace
+    pub fn shell_enabled(&self, command: &str) -> bool {
+        self.shell.unwrap_or_else(|| self.hint_file.as_ref().is_some_and(|hint_file| hint_file.shell(command)))
+    }
+    
+    /// The parts of a command run in shell mode, if it chains several with `&&` or `;`
+    ///
+    /// Each part gets the cache ID it would have on its own.
+    pub fn chain_parts(&self, command: &str) -> Option<Vec<ChainPart>> {
+        if !self.shell_enabled(command) {
+            return None;
+        }
+        
+        let mut parts = chain::split(command);
+        if parts.len() < 2 {
+            return None;
+        }
+        
+        for part in &mut parts {
+            part.id = Some(self.generate_id(&part.command));
+        }
+        Some(parts)
+    }
+    
+    pub fn execute_command(&self, command: &str) -> io::Result<String> {
+        let output = self.run_successful_command(command)?;
+        Ok(output.stdout)
+    }
+    
+    /// Run a command, returning an error if it exits with a non-zero status
+    fn run_successful_command(&self, command: &str) -> io::Result<CommandOutput> {
+        check_success(self.run_command(command)?)
+    }
+    
+    /// Get the interpreter `cacher eval` uses for a language
+    ///
+    /// Interpreters configured in the hint file take precedence over the built-in ones.
+    pub fn interpreter(&self, lang: &str) -> Option<Interpreter> {
+        self.hint_file.as_ref()
+            .and_then(|hint_file| hint_file.interpreters.get(lang).cloned())
+            .or_else(|| Interpreter::builtin(lang))
+    }
+    
+    /// Run a code snippet with a language's interpreter, caching its output
+    ///
+    /// The entry is keyed on the snippet, the interpreter's command and
+    /// version, and the contents of the files matched by the interpreter's
+    /// `imports` patterns and `extra_imports`, so upgrading the interpreter or
+    /// editing an imported module invalidates it. TTLs, limits and
+    /// normalization come from command hints matching the snippet's command
+    /// line, e.g. `python3 -c *`.
+    ///
+    /// # Arguments
+    ///
+    /// * `lang` - The language, e.g. `python`
+    /// * `snippet` - The code to run
+    /// * `extra_imports` - More glob patterns of files the snippet depends on
+    /// * `ttl` - Time-to-live for the cached entry
+    /// * `force` - Run the snippet even if it has a fresh entry
+    pub fn eval(&mut self, lang: &str, snippet: &str, extra_imports: &[String], ttl: Option<Duration>, force: bool) -> io::Result<String> {
+        let interpreter = self.interpreter(lang).ok_or_else(|| {
+            Error::new(ErrorKind::NotFound, format!("No interpreter configured for {}", lang))
+        })?;
+        
+        let command = interpreter.display_command(snippet);
+        self.check_required_env(&command)?;
+        let id = self.eval_id(lang, &interpreter, snippet, extra_imports)?;
+        
+        if !force {
+            if let Some((output, metadata)) = self.load_entry_by_id(&id)? {
+                if self.is_entry_fresh(&command, &metadata, ttl) {
+                    self.record_hit_by_id(&id);
+                    return Ok(output.stdout);
+                }
+            }
+        }
+        
+        let mut process = interpreter.snippet_process(snippet, &self.current_dir)?;
+        let mut output = check_success(self.run_process(&command, &mut process)?)?;
+        output.stdout = self.normalize_output(&command, &output.stdout)?;
+        self.save_entry(&id, &command, &output)?;
+        self.replicate(&command, &id);
+        
+        Ok(output.stdout)
+    }
+    
+    /// Generate the cache ID for an evaluated snippet
+    fn eval_id(&self, lang: &str, interpreter: &Interpreter, snippet: &str, extra_imports: &[String]) -> io::Result<String> {
+        let mut hasher = Sha256::new();
+        hasher.update(format!("project:{}\n", self.project_id).as_bytes());
+        hasher.update(format!("eval:{}\n", lang).as_bytes());
+        hasher.update(format!("key_schema:{}\n", self.key_schema()).as_bytes());
+        
+        // Length-prefix the free-form fields so no field can run into the next
+        let version = interpreter.version(&self.current_dir)?;
+        for (name, value) in [("interpreter", interpreter.command.join("\0")), ("version", version), ("snippet", snippet.to_string())] {
+            hasher.update(format!("{}:{}\n", name, value.len()).as_bytes());
+            hasher.update(value.as_bytes());
+            hasher.update(b"\n");
+        }
+        
+        let mut imports: Vec<&String> = interpreter.imports.iter().chain(extra_imports).collect();
+        imports.sort();
+        imports.dedup();
+        for pattern in imports {
+            let dependency = Dependency::Files { files: pattern.clone() };
+            let hash = dependency.get_content_hash(&self.current_dir)
+                .map_err(|e| Error::other(format!("{:#}", e)))?;
+            hasher.update(format!("{}:{}\n", pattern, hash).as_bytes());
+        }
+        
+        Ok(format!("{:x}", hasher.finalize()))
+    }
+    
+    /// Apply the command's `normalize` rules from the hint file to its output
+    pub fn normalize_output(&self, command: &str, output: &str) -> io::Result<String> {
+        if let Some(hint_file) = &self.hint_file {
+            if let Some(command_hint) = hint_file.find_matching_command(command) {
+                if !command_hint.normalize.is_empty() {
+                    let normalizer = Normalizer::new(&command_hint.normalize)
+                        .map_err(|e| Error::new(ErrorKind::InvalidInput, format!("{:#}", e)))?;
+                    return Ok(normalizer.apply(output));
+                }
+            }
+        }
+        
+        Ok(output.to_string())
+    }
+    
+    pub fn execute_and_cache(&mut self, command: &str, ttl: Option<Duration>, force: bool) -> io::Result<String> {
+        self.decide(command, ttl, force, None).map(|decision| decision.output)
+    }
+    
+    /// Execute a command and cache its output and artifacts, reporting where the output came from
+    ///
+    /// Behaves like [`CommandCache::execute_and_cache_with_artifacts`], but
+    /// says whether the output was a memory or disk hit, a re-run that
+    /// reproduced an expired entry, or a fresh execution, and how long it
+    /// took. Errors are the same, including [`CommandFailed`] with `cached`
+    /// set for a served failure.
+    pub fn execute_with_decision(&mut self, command: &str, ttl: Option<Duration>, force: bool) -> io::Result<CacheDecision> {
+        let artifacts = self.get_command_artifacts(command);
+        self.decide(command, ttl, force, artifacts)
+    }
+    
+    /// Serve a command from the cache or run it, restoring and caching `artifacts` as well
+    fn decide(&mut self, command: &str, ttl: Option<Duration>, force: bool, artifacts: Option<Vec<ArtifactType>>) -> io::Result<CacheDecision> {
+        let started = Instant::now();
+        self.check_required_env(command)?;
+        
+        if self.no_cache_reason(command).is_some() {
+            let output = self.execute_uncached(command)?;
+            return Ok(CacheDecision {
+                output: output.stdout,
+                stderr: output.stderr,
+                source: CacheSource::Bypassed,
+                run_time: output.duration,
+                elapsed: started.elapsed(),
+            });
+        }
+        
+        let id = self.generate_id(command);
+        
+        if !force {
+            if let Some((output, source)) = self.lookup_cached(command, &id, ttl) {
+                // Only serve the hit when its artifacts could be restored too
+                let restored = match &artifacts {
+                    Some(artifacts) => matches!(self.restore_artifacts(id.clone(), artifacts.clone()), Ok(true)),
+                    None => true,
+                };
+                if restored {
+                    self.run_hook(command, HookStage::AfterHit)?;
+                    return Ok(CacheDecision {
+                        output: output.stdout,
+                        stderr: output.stderr,
+                        source,
+                        run_time: output.duration,
+                        elapsed: started.elapsed(),
+                    });
+                }
+            }
+            
+            // A failed run produced no artifacts to restore
+            if let Some(output) = self.cached_failure(command, &id, ttl) {
+                self.run_hook(command, HookStage::AfterHit)?;
+                return Err(Error::other(CommandFailed { output, cached: true }));
+            }
+        }
+        
+        // The entry that wasn't served, to tell a revalidation from a change
+        let previous = self.local_stdout(&id);
+        let output = self.execute_and_store(command)?;
+        
+        if let Some(artifacts) = artifacts {
+            self.cache_artifacts(id.clone(), command, artifacts)?;
+        }
+        self.replicate(command, &id);
+        self.run_hook(command, HookStage::AfterMiss)?;
+        
+        let source = if previous.as_ref() == Some(&output.stdout) {
+            CacheSource::Revalidated
+        } else {
+            CacheSource::Executed
+        };
+        Ok(CacheDecision {
+            output: output.stdout,
+            stderr: output.stderr,
+            source,
+            run_time: output.duration,
+            elapsed: started.elapsed(),
+        })
+    }
+    
+    /// The stdout of a successful entry in the local cache directory, regardless of its age
+    fn local_stdout(&self, id: &str) -> Option<String> {
+        let (stdout, _) = read_complete_output(&self.cache_dir.join(id)).ok()??;
+        let metadata = EntryMetadata::read(&self.get_metadata_path(id)).ok()??;
+        if metadata.exit_code != 0 {
+            return None;
+        }
+        self.decode_entry_file(id, "stdout", stdout, &metadata).ok()
+    }
+    
+    /// Execute a command on a cache miss and store its output in memory and on disk
+    ///
+    /// A failure is only stored, on disk, when `cache_failures` is enabled for
+    /// the command; it is still returned as an error.
+    fn execute_and_store(&mut self, command: &str) -> io::Result<CommandOutput> {
+        self.run_hook(command, HookStage::Before)?;
+        
+        let mut output = self.run_command(command)?;
+        self.take_ttl_directive(command, &mut output);
+        if !output.success() {
+            if self.failure_caching_enabled(command) {
+                self.cache.remove(command);
+                self.save_output_to_disk(command, &output)?;
+            }
+            return check_success(output);
+        }
+        
+        output.stdout = self.normalize_output(command, &output.stdout)?;
+        self.store_output(command, &output, self.clock.now());
+        self.save_output_to_disk(command, &output)?;
+        
+        Ok(output)
+    }
+    
+    /// Move a trailing `#cacher: ttl=` directive from the output into its TTL,
+    /// if `ttl_from_output` is enabled for the command
+    fn take_ttl_directive(&self, command: &str, output: &mut CommandOutput) {
+        if !self.hint_file.as_ref().is_some_and(|hint_file| hint_file.ttl_from_output(command)) {
+            return;
+        }
+        
+        if let Some((stdout, ttl)) = directive::take_ttl(&output.stdout) {
+            output.stdout = stdout;
+            output.ttl = Some(ttl);
+        }
+    }
+    
+    /// Execute a command without reading or writing the cache
+    ///
+    /// Used when a `no_cache_if_*` condition holds. Hooks still run as they
+    /// would on a miss, since the command is really executed.
+    fn execute_uncached(&mut self, command: &str) -> io::Result<CommandOutput> {
+        self.run_hook(command, HookStage::Before)?;
+        
+        let mut output = self.run_successful_command(command)?;
+        self.take_ttl_directive(command, &mut output);
+        output.stdout = self.normalize_output(command, &output.stdout)?;
+        
+        self.run_hook(command, HookStage::AfterMiss)?;
+        Ok(output)
+    }
+    
+    /// Check the hint file's `no_cache_if_env` and `no_cache_if_file_exists` conditions
+    ///
+    /// Conditions under the default settings apply to all commands and are
+    /// combined with those of the matching command hint. An environment
+    /// variable counts as set when it has a non-empty value; files are
+    /// relative to the current directory.
+    ///
+    /// # Returns
+    ///
+    /// A description of the first condition that holds, or None if the cache may be used
+    pub fn no_cache_reason(&self, command: &str) -> Option<String> {
+        let hint_file = self.hint_file.as_ref()?;
+        let command_hint = hint_file.find_matching_command(command);
+        
+        let env_vars = hint_file.default.no_cache_if_env.iter()
+            .chain(command_hint.into_iter().flat_map(|cmd| cmd.no_cache_if_env.iter()));
+        for env_var in env_vars {
+            if env::var_os(env_var).is_some_and(|value| !value.is_empty()) {
+                return Some(format!("{} is set", env_var));
+            }
+        }
+        
+        let files = hint_file.default.no_cache_if_file_exists.iter()
+            .chain(command_hint.into_iter().flat_map(|cmd| cmd.no_cache_if_file_exists.iter()));
+        for file in files {
+            if self.current_dir.join(file).exists() {
+                return Some(format!("{} exists", file));
+            }
+        }
+        
+        None
+    }
+    
+    /// Run the matching command hint's hook for a stage, if it defines one
+    pub fn run_hook(&self, command: &str, stage: HookStage) -> io::Result<()> {
+        match self.hint_file.as_ref().and_then(|hint_file| hint_file.hook(command, stage)) {
+            Some(hook) => hook.run(stage, command, &self.current_dir),
+            None => Ok(()),
+        }
+    }
+    
+    /// Get the cached output for a command without executing it
+    ///
+    /// Checks the in-memory cache first, then the disk cache. Entries that are
+    /// older than the effective TTL, unless they are pinned, or than the
+    /// configured maximum age are ignored. Every hit is counted in the entry's metadata.
+    /// Commands declared with `side_effects: true` never have a fresh entry.
+    ///
+    /// Serving a hit takes no locks. Entries are only ever replaced by renaming
+    /// a fully written directory into place, and an entry whose `complete`
+    /// checksum doesn't match its output is treated as missing, so a reader
+    /// racing a writer sees either the old entry, the new one or a miss.
+    /// Hits are counted by appending a line to the entry's `hits` log (see
+    /// [`metadata::record_hit`]) rather than rewriting `metadata.json`, so
+    /// concurrent hits don't wait for each other; the log is folded into the
+    /// metadata the next time it is written. See `benches/hot_paths.rs` and
+    /// `cacher bench` for the latency of this path.
+    ///
+    /// # Returns
+    ///
+    /// An Option containing the cached output, or None if there is no fresh entry
+    pub fn get_cached(&mut self, command: &str, ttl: Option<Duration>) -> Option<String> {
+        let id = self.generate_id(command);
+        self.lookup_cached(command, &id, ttl).map(|(output, _)| output.stdout)
+    }
+    
+    /// Look up a command's fresh entry, stored under `id`, counting a hit
+    ///
+    /// # Returns
+    ///
+    /// The cached output, with how long the command took when it was run,
+    /// and where it was found, or None if there is no fresh entry
+    fn lookup_cached(&mut self, command: &str, id: &str, ttl: Option<Duration>) -> Option<(CommandOutput, CacheSource)> {
+        // Commands with side effects are recorded, but never served
+        if self.has_side_effects(command) {
+            return None;
+        }
+        
+        // First check in-memory cache
+        if let Some(entry) = self.cache.get(command) {
+            let ttl_duration = entry.ttl.or_else(|| self.get_effective_ttl(command, ttl));
+            if self.is_within_ttl(entry.timestamp, ttl_duration, || self.is_pinned_by_id(id)) {
+                let hit = CommandOutput {
+                    stdout: entry.output.clone(),
+                    stderr: entry.stderr.clone(),
+                    duration: entry.duration,
+                    ..CommandOutput::default()
+                };
+                self.record_hit_by_id(id);
+                return Some((hit, CacheSource::MemoryHit));
+            }
+        }
+        
+        // Then check disk cache, where failures cached with `cache_failures` aren't hits
+        if let Ok(Some((output, metadata))) = self.load_entry_by_id(id) {
+            if output.success() && self.is_entry_fresh(command, &metadata, ttl) {
+                self.store_output(command, &output, metadata.created_at());
+                self.record_hit_by_id(id);
+                return Some((output, CacheSource::DiskHit));
+            }
+        }
+        
+        None
+    }
+    
+    /// Get a command's fresh cached failure, if `cache_failures` is enabled for it
+    ///
+    /// Failures expire by the TTL for their exit code; see
+    /// [`CommandCache::get_effective_ttl_for_exit`].
+    pub fn get_cached_failure(&self, command: &str, ttl: Option<Duration>) -> Option<CommandOutput> {
+        self.cached_failure(command, &self.generate_id(command), ttl)
+    }
+    
+    /// Get a command's fresh cached failure stored under `id`
+    fn cached_failure(&self, command: &str, id: &str, ttl: Option<Duration>) -> Option<CommandOutput> {
+        if !self.failure_caching_enabled(command) || self.has_side_effects(command) {
+            return None;
+        }
+        
+        let (output, metadata) = self.load_entry_by_id(id).ok()??;
+        if output.success() || !self.is_entry_fresh(command, &metadata, ttl) {
+            return None;
+        }
+        
+        self.record_hit_by_id(id);
+        Some(output)
+    }
+    
+    /// Whether a command is declared with `side_effects: true`, so it runs every time
+    ///
+    /// Its output is still stored, so its run time shows up in `cacher stats`
+    /// and its earlier outputs in `cacher history`.
+    pub fn has_side_effects(&self, command: &str) -> bool {
+        self.hint_file.as_ref().is_some_and(|hint_file| hint_file.side_effects(command))
+    }
+    
+    /// Whether a command's output is cached when it fails
+    pub fn failure_caching_enabled(&self, command: &str) -> bool {
+        self.hint_file.as_ref().is_some_and(|hint_file| hint_file.cache_failures(command))
+    }
+    
+    /// Count a hit on the entry stored under a cache ID
+    ///
+    /// The hit is appended to the entry's hit log rather than rewriting its
+    /// metadata, so concurrent hits need no lock; see [`metadata::record_hit`].
+    /// Failing to record it never turns a hit into an error.
+    fn record_hit_by_id(&self, id: &str) {
+        let _ = metadata::record_hit(&self.entry_dir(id), self.clock.now());
+    }
+    
+    /// Check whether an entry created at `timestamp` can still be served
+    ///
+    /// An entry is fresh when it is within the effective TTL and, if a maximum
+    /// age has been set with [`CommandCache::set_max_age`], no older than that.
+    /// Pinned entries are fresh regardless of their TTL, but not past the maximum age.
+    pub fn is_fresh(&self, command: &str, timestamp: SystemTime, ttl: Option<Duration>) -> bool {
+        self.is_within_ttl(timestamp, self.get_effective_ttl(command, ttl), || self.is_pinned(command))
+    }
+    
+    /// Check whether a cached entry can still be served, going by the TTL it
+    /// set in its output or else the TTL for its exit code
+    ///
+    /// Whether it is pinned is read from `metadata`, so this also works for
+    /// entries not stored under the command's own ID, like those of `eval`.
+    fn is_entry_fresh(&self, command: &str, metadata: &EntryMetadata, ttl: Option<Duration>) -> bool {
+        self.is_within_ttl(metadata.created_at(), self.get_entry_ttl(command, metadata, ttl), || metadata.pinned)
+    }
+    
+    /// Check whether an entry created at `timestamp` is within `ttl_duration`,
+    /// or `pinned` says it is pinned, and within the maximum age
+    fn is_within_ttl(&self, timestamp: SystemTime, ttl_duration: Option<Duration>, pinned: impl FnOnce() -> bool) -> bool {
+        let age = self.clock.now().duration_since(timestamp).unwrap_or_default();
+        
+        let within_max_age = self.max_age.is_none_or(|max_age| age <= max_age);
+        let within_ttl = match ttl_duration {
+            Some(ttl_duration) => age <= ttl_duration,
+            // No TTL specified, use cache regardless of age
+            None => true,
+        };
+        
+        within_max_age && (within_ttl || pinned())
+    }
+    
+    /// Whether a command's cached entry is pinned
+    pub fn is_pinned(&self, command: &str) -> bool {
+        self.is_pinned_by_id(&self.generate_id(command))
+    }
+    
+    /// Whether the entry stored under a cache ID is pinned
+    fn is_pinned_by_id(&self, id: &str) -> bool {
+        EntryMetadata::read(&self.entry_dir(id).join("metadata.json"))
+            .ok()
+            .flatten()
+            .is_some_and(|metadata| metadata.pinned)
+    }
+    
+    /// Pin or unpin the entry stored under a cache ID
+    ///
+    /// Pinned entries are served regardless of their TTL, though never past
+    /// the maximum age, and are kept when the whole cache is cleared.
+    pub fn set_pinned(&self, id: &str, pinned: bool) -> io::Result<()> {
+        let metadata_path = self.entry_dir(id).join("metadata.json");
+        let mut metadata = EntryMetadata::read(&metadata_path)?.ok_or_else(|| {
+            Error::new(ErrorKind::NotFound, format!("No cached entry with ID {}", id))
+        })?;
+        
+        metadata.pinned = pinned;
+        metadata.write(&metadata_path)
+    }
+    
+    /// Re-extract cached artifacts on a hit even if they already match what was cached
+    pub fn set_force_restore(&mut self, force_restore: bool) {
+        self.artifact_manager.set_force_restore(force_restore);
+    }
+    
+    /// Use `clock` for entry timestamps and freshness checks instead of the system clock
+    pub fn set_clock(&mut self, clock: Arc<dyn Clock>) {
+        self.clock = clock;
+    }
+    
+    /// Treat entries older than `max_age` as misses, regardless of their TTL
+    ///
+    /// Nothing is removed from the cache; older entries are simply not served
+    /// and get replaced when the command is executed again.
+    pub fn set_max_age(&mut self, max_age: Option<Duration>) {
+        self.max_age = max_age;
+    }
+    
+    // Helper method to get effective TTL from hint file or fallback to provided TTL
+    pub fn get_effective_ttl(&self, command: &str, default_ttl: Option<Duration>) -> Option<Duration> {
+        self.get_effective_ttl_for_exit(command, default_ttl, 0)
+    }
+    
+    /// Get the TTL of a command's entry that exited with `exit_code`
+    ///
+    /// A matching `ttl_by_exit` entry takes precedence over `ttl` at the same
+    /// level, so successes and cached failures can expire at different times.
+    pub fn get_effective_ttl_for_exit(&self, command: &str, default_ttl: Option<Duration>, exit_code: i32) -> Option<Duration> {
+        if let Some(hint_file) = &self.hint_file {
+            // Check for command-specific TTL
+            if let Some(command_hint) = hint_file.find_matching_command(command) {
+                let by_exit = command_hint.ttl_by_exit.as_ref().and_then(|ttl_by_exit| ttl_by_exit.ttl(exit_code));
+                if let Some(ttl_seconds) = by_exit.or(command_hint.ttl) {
+                    return Some(Duration::from_secs(ttl_seconds));
                 }
             }
+            
+            // Fall back to default TTL from hint file
+            let by_exit = hint_file.default.ttl_by_exit.as_ref().and_then(|ttl_by_exit| ttl_by_exit.ttl(exit_code));
+            if let Some(ttl_seconds) = by_exit.or(hint_file.default.ttl) {
+                return Some(Duration::from_secs(ttl_seconds));
+            }
         }
         
-        format!("{:x}", hasher.finalize())
-    }
-    
-    pub fn get_cache_path(&self, id: &str) -> PathBuf {
-        let cache_dir = self.cache_dir.join(id);
-        fs::create_dir_all(&cache_dir).unwrap_or_else(|_| {});
-        cache_dir
+        // Fall back to provided TTL
+        default_ttl
     }
     
-    pub fn get_stdout_path(&self, id: &str) -> PathBuf {
-        self.get_cache_path(id).join("stdout")
+    /// Get the TTL of a cached entry
+    ///
+    /// A TTL the command set with a `#cacher: ttl=` directive takes precedence;
+    /// otherwise it is the TTL for the entry's exit code.
+    pub fn get_entry_ttl(&self, command: &str, metadata: &EntryMetadata, default_ttl: Option<Duration>) -> Option<Duration> {
+        metadata.ttl
+            .map(Duration::from_secs)
+            .or_else(|| self.get_effective_ttl_for_exit(command, default_ttl, metadata.exit_code))
     }
     
-    pub fn get_metadata_path(&self, id: &str) -> PathBuf {
-        self.get_cache_path(id).join("metadata.json")
+    pub fn load_from_disk_with_timestamp(&self, command: &str) -> io::Result<Option<(String, SystemTime)>> {
+        Ok(self.load_entry(command)?
+            .map(|(output, metadata)| (output.stdout, metadata.created_at())))
     }
     
-    pub fn save_to_disk(&self, command: &str, output: &str) -> io::Result<()> {
-        let id = self.generate_id(command);
+    /// Find the current project's high-value entries whose TTL runs out within `within`
+    ///
+    /// An entry is high-value when it is pinned or has been hit at least
+    /// `min_hits` times. Pinned entries whose TTL already ran out are included
+    /// too, since they are still being served. Entries without a TTL never
+    /// expire and are skipped. Entries of commands with `refresh_before_expiry`
+    /// in the hint file are also included, however often they were hit, once
+    /// their TTL runs out within that time, and are marked for refreshing.
+    ///
+    /// # Returns
+    ///
+    /// The matching entries, soonest to expire first
+    pub fn expiring_entries(&self, within: Duration, min_hits: u64, ttl: Option<Duration>) -> io::Result<Vec<ExpiringEntry>> {
+        let now = self.clock.now();
+        let mut expiring = Vec::new();
         
-        // Create cache directory for this command
-        let _ = self.get_cache_path(&id);
+        for entry in self.list_cached_commands(&ListOptions::default())? {
+            let metadata = match EntryMetadata::read(&self.entry_dir(&entry.id).join("metadata.json")) {
+                Ok(Some(metadata)) => metadata,
+                _ => continue,
+            };
+            let command = match metadata.full_command(&self.cache_dir.join(&entry.id)) {
+                Ok(command) => command,
+                Err(_) => continue,
+            };
+            
+            // The TTL comes from this project's hint file, so only its entries can be checked
+            if self.generate_id(&command) != entry.id {
+                continue;
+            }
+            
+            let ttl_duration = match self.get_entry_ttl(&command, &metadata, ttl) {
+                Some(ttl_duration) => ttl_duration,
+                None => continue,
+            };
+            
+            let age = now.duration_since(entry.created_at).unwrap_or_default();
+            if !entry.pinned && age > ttl_duration {
+                continue;
+            }
+            
+            let expires_in = ttl_duration.saturating_sub(age);
+            let refresh = self.hint_file.as_ref()
+                .and_then(|hint_file| hint_file.refresh_before_expiry(&command))
+                .is_some_and(|before| expires_in <= before);
+            let high_value = entry.pinned || entry.hit_count >= min_hits;
+            if refresh || (high_value && expires_in <= within) {
+                expiring.push(ExpiringEntry { entry, expires_in, refresh });
+            }
+        }
         
-        // Save stdout to a separate file
-        let stdout_path = self.get_stdout_path(&id);
-        let mut stdout_file = File::create(stdout_path)?;
-        stdout_file.write_all(output.as_bytes())?;
+        expiring.sort_by_key(|e| e.expires_in);
+        Ok(expiring)
+    }
+    
+    /// Run the `on_expiring` hook for an entry found by [`CommandCache::expiring_entries`]
+    ///
+    /// The hook runs once per entry, with `CACHER_ID` and `CACHER_EXPIRES_IN`
+    /// (in seconds) set besides the usual hook variables; re-caching the
+    /// command makes a new entry that is notified again. To call a webhook,
+    /// use a hook like `curl -d "$CACHER_ID" https://...`.
+    ///
+    /// # Returns
+    ///
+    /// Whether the hook ran
+    pub fn notify_expiring(&self, expiring: &ExpiringEntry) -> io::Result<bool> {
+        let entry_dir = self.entry_dir(&expiring.entry.id);
+        let metadata_path = entry_dir.join("metadata.json");
+        let mut metadata = match EntryMetadata::read(&metadata_path)? {
+            Some(metadata) if !metadata.expiry_notified => metadata,
+            _ => return Ok(false),
+        };
+        let command = metadata.full_command(&entry_dir)?;
         
-        // Save metadata to a JSON file
-        let metadata_path = self.get_metadata_path(&id);
-        let metadata = format!(
-            "{{\"command\":\"{}\",\"timestamp\":{}}}",
-            command.replace("\"", "\\\""),
-            SystemTime::now().duration_since(std::time::UNIX_EPOCH).unwrap().as_secs()
-        );
+        let hook = match self.hint_file.as_ref().and_then(|hint_file| hint_file.hook(&command, HookStage::Expiring)) {
+            Some(hook) => hook,
+            None => return Ok(false),
+        };
         
-        let mut metadata_file = File::create(metadata_path)?;
-        metadata_file.write_all(metadata.as_bytes())?;
+        let env = [
+            ("CACHER_ID", expiring.entry.id.clone()),
+            ("CACHER_EXPIRES_IN", expiring.expires_in.as_secs().to_string()),
+        ];
+        hook.run_with_env(HookStage::Expiring, &command, &self.current_dir, &env)?;
         
-        Ok(())
+        metadata.expiry_notified = true;
+        metadata.write(&metadata_path)?;
+        Ok(true)
     }
     
-    pub fn load_from_disk(&self, command: &str) -> io::Result<Option<String>> {
-        let id = self.generate_id(command);
-        let stdout_path = self.get_stdout_path(&id);
+    /// The entries an invalidation manifest selects, across all projects
+    pub fn invalidation_targets(&self, manifest: &InvalidationManifest) -> io::Result<Vec<CacheEntrySummary>> {
+        let mut targets = Vec::new();
         
-        if !stdout_path.exists() {
-            return Ok(None);
+        for entry in self.list_cached_commands(&ListOptions::default())? {
+            let entry_dir = self.cache_dir.join(&entry.id);
+            let metadata = match EntryMetadata::read(&entry_dir.join("metadata.json"))? {
+                Some(metadata) => metadata,
+                None => continue,
+            };
+            
+            if manifest.selects(&metadata, &metadata.full_command(&entry_dir)?) {
+                targets.push(entry);
+            }
         }
         
-        let mut file = File::open(stdout_path)?;
-        let mut contents = String::new();
-        file.read_to_string(&mut contents)?;
+        // Entries only the remote store has would be downloaded again on the next lookup
+        if let Some(remote) = self.remote() {
+            for (id, entry_dir) in remote.entries()? {
+                if self.cache_dir.join(&id).exists() {
+                    continue;
+                }
+                let metadata = match EntryMetadata::read(&entry_dir.join("metadata.json"))? {
+                    Some(metadata) if !metadata.command.is_empty() => metadata,
+                    _ => continue,
+                };
+                if manifest.selects(&metadata, &metadata.full_command(&entry_dir)?) {
+                    targets.push(summarize_entry(id, &entry_dir, metadata));
+                }
+            }
+        }
         
-        Ok(Some(contents))
+        Ok(targets)
     }
     
-    pub fn execute_command(&self, command: &str) -> io::Result<String> {
-        // Parse command into program and arguments
-        let mut parts = command.split_whitespace();
-        let program = parts.next().ok_or_else(|| {
-            Error::new(ErrorKind::InvalidInput, "Empty command")
-        })?;
-        
-        let args: Vec<&str> = parts.collect();
+    /// Delete every entry an invalidation manifest selects, all or nothing
+    ///
+    /// The entries are first moved into a staging directory inside the cache
+    /// directory, which readers never look in. If any of them can't be moved,
+    /// the ones already moved are put back and nothing is invalidated;
+    /// otherwise the staging directory is deleted. With a remote store
+    /// configured, the selected entries are removed from it the same way,
+    /// so they aren't downloaded again; that happens last, once nothing
+    /// local can fail any more.
+    ///
+    /// # Returns
+    ///
+    /// The entries that were deleted
+    pub fn invalidate(&mut self, manifest: &InvalidationManifest) -> io::Result<Vec<CacheEntrySummary>> {
+        let targets = self.invalidation_targets(manifest)?;
+        if targets.is_empty() {
+            return Ok(targets);
+        }
         
-        // Execute command
-        let output = std::process::Command::new(program)
-            .args(&args)
-            .output()
-            .map_err(|e| {
-                Error::new(ErrorKind::Other, format!("Failed to execute command: {}", e))
-            })?;
+        let staging = tempfile::Builder::new().prefix(STAGING_PREFIX).tempdir_in(&self.cache_dir)?;
+        let put_back = |moved: &[&str]| {
+            for id in moved.iter().rev() {
+                let _ = fs::rename(staging.path().join(id), self.cache_dir.join(id));
+            }
+        };
         
-        if !output.status.success() {
-            return Err(Error::new(
-                ErrorKind::Other,
-                format!(
-                    "Command failed with exit code {}: {}",
-                    output.status.code().unwrap_or(-1),
-                    String::from_utf8_lossy(&output.stderr)
-                )
-            ));
+        let mut moved: Vec<&str> = Vec::new();
+        for entry in &targets {
+            match fs::rename(self.cache_dir.join(&entry.id), staging.path().join(&entry.id)) {
+                Ok(()) => moved.push(&entry.id),
+                // Someone else deleted it first
+                Err(e) if e.kind() == ErrorKind::NotFound => {},
+                Err(e) => {
+                    put_back(&moved);
+                    return Err(Error::new(e.kind(), format!("Failed to invalidate {}: {}; no entries were removed", entry.command, e)));
+                }
+            }
         }
         
-        let output_str = String::from_utf8_lossy(&output.stdout).to_string();
+        let index = SearchIndex::new(&self.cache_dir);
+        if let Err(e) = index.remove(moved.iter().copied()) {
+            put_back(&moved);
+            return Err(Error::new(e.kind(), format!("Failed to update the index: {}; no entries were removed", e)));
+        }
         
-        Ok(output_str)
-    }
-    
-    pub fn execute_and_cache(&mut self, command: &str, ttl: Option<Duration>, force: bool) -> io::Result<String> {
-        if !force {
-            // First check in-memory cache
-            if let Some(output) = self.get(command) {
-                return Ok(output.clone());
-            }
-            
-            // Then check disk cache
-            if let Ok(Some((output, timestamp))) = self.load_from_disk_with_timestamp(command) {
-                // Get TTL from hint file if available
-                let effective_ttl = self.get_effective_ttl(command, ttl);
-                
-                // Check if cache is still valid based on TTL
-                if let Some(ttl_duration) = effective_ttl {
-                    if let Ok(age) = SystemTime::now().duration_since(timestamp) {
-                        if age > ttl_duration {
-                            // Cache is expired, don't use it
-                        } else {
-                            // Cache is still valid
-                            self.store(command, &output);
-                            return Ok(output);
+        // Removing remote entries can't be undone, so it comes last
+        let mut removed_remotely = Vec::new();
+        if let Some(remote) = self.remote() {
+            let ids: Vec<&str> = targets.iter().map(|entry| entry.id.as_str()).collect();
+            match remote.remove(&ids) {
+                Ok(removed) => removed_remotely = removed,
+                Err(e) => {
+                    put_back(&moved);
+                    for id in &moved {
+                        if let Ok(Some(metadata)) = EntryMetadata::read(&self.entry_dir(id).join("metadata.json")) {
+                            let _ = index.insert(id, IndexRecord::from_metadata(&metadata));
                         }
                     }
-                } else {
-                    // No TTL specified, use cache regardless of age
-                    self.store(command, &output);
-                    return Ok(output);
+                    return Err(Error::new(e.kind(), format!("Failed to invalidate in {}: {}; no entries were removed", remote.root().display(), e)));
                 }
             }
         }
         
-        // Execute command and cache result
-        let output = self.execute_command(command)?;
-        self.store(command, &output);
-        self.save_to_disk(command, &output)?;
-        
-        Ok(output)
-    }
-    
-    // Helper method to get effective TTL from hint file or fallback to provided TTL
-    pub fn get_effective_ttl(&self, command: &str, default_ttl: Option<Duration>) -> Option<Duration> {
-        if let Some(hint_file) = &self.hint_file {
-            // Check for command-specific TTL
-            if let Some(command_hint) = hint_file.find_matching_command(command) {
-                if let Some(ttl_seconds) = command_hint.ttl {
-                    return Some(Duration::from_secs(ttl_seconds));
+        for id in &moved {
+            let entry_dir = staging.path().join(id);
+            if let Ok(Some(metadata)) = EntryMetadata::read(&entry_dir.join("metadata.json")) {
+                if let Ok(command) = metadata.full_command(&entry_dir) {
+                    self.cache.remove(&command);
                 }
             }
-            
-            // Fall back to default TTL from hint file
-            if let Some(ttl_seconds) = hint_file.default.ttl {
-                return Some(Duration::from_secs(ttl_seconds));
-            }
         }
         
-        // Fall back to provided TTL
-        default_ttl
+        // Dropping the staging directory deletes the entries; anything left behind is removed by `gc`
+        drop(staging);
+        
+        let moved: Vec<String> = moved.into_iter().map(str::to_string).collect();
+        Ok(targets.into_iter().filter(|entry| moved.contains(&entry.id) || removed_remotely.contains(&entry.id)).collect())
     }
     
-    pub fn load_from_disk_with_timestamp(&self, command: &str) -> io::Result<Option<(String, SystemTime)>> {
-        let id = self.generate_id(command);
-        let stdout_path = self.get_stdout_path(&id);
-        let metadata_path = self.get_metadata_path(&id);
-        
-        if !stdout_path.exists() || !metadata_path.exists() {
-            return Ok(None);
+    pub fn remove_entry(&mut self, id: &str) -> io::Result<()> {
+        let entry_dir = self.cache_dir.join(id);
+        if let Ok(Some(metadata)) = EntryMetadata::read(&entry_dir.join("metadata.json")) {
+            if let Ok(command) = metadata.full_command(&entry_dir) {
+                self.cache.remove(&command);
+            }
         }
         
-        // Read stdout content
-        let mut stdout_file = File::open(stdout_path)?;
-        let mut stdout_content = String::new();
-        stdout_file.read_to_string(&mut stdout_content)?;
-        
-        // Read metadata
-        let mut metadata_file = File::open(metadata_path)?;
-        let mut metadata_content = String::new();
-        metadata_file.read_to_string(&mut metadata_content)?;
+        if entry_dir.exists() {
+            fs::remove_dir_all(entry_dir)?;
+        }
+        SearchIndex::new(&self.cache_dir).remove([id])
+    }
+    
+    /// Re-run the command of the entry stored under a cache ID and replace the entry
+    ///
+    /// Only entries of the current project can be refreshed, since the command
+    /// has to run in the directory it was cached for.
+    pub fn refresh_entry(&mut self, id: &str) -> io::Result<String> {
+        let metadata = EntryMetadata::read(&self.get_metadata_path(id))?.ok_or_else(|| {
+            Error::new(ErrorKind::NotFound, format!("No cached entry with ID {}", id))
+        })?;
         
-        // Parse timestamp from metadata
-        let mut timestamp = SystemTime::UNIX_EPOCH;
-        if let Some(start) = metadata_content.find("\"timestamp\":") {
-            if let Some(end) = metadata_content[start + 12..].find("}") {
-                if let Ok(secs) = metadata_content[start + 12..start + 12 + end].trim().parse::<u64>() {
-                    timestamp = SystemTime::UNIX_EPOCH + Duration::from_secs(secs);
-                }
-            }
+        let command = metadata.full_command(&self.cache_dir.join(id))?;
+        if self.generate_id(&command) != id {
+            return Err(Error::new(
+                ErrorKind::InvalidInput,
+                "Entry was cached by another project or with different dependencies; run it from there to refresh it",
+            ));
         }
         
-        Ok(Some((stdout_content, timestamp)))
+        self.execute_and_cache_with_artifacts(&command, None, true)
     }
     
-    pub fn list_cached_commands(&self) -> io::Result<Vec<(String, SystemTime)>> {
+    /// List the entries in the cache directory
+    ///
+    /// Unlike most methods, this includes entries from all projects. Entries
+    /// are filtered by `options.filter`, sorted by `options.sort` and cut off
+    /// after `options.limit`.
+    pub fn list_cached_commands(&self, options: &ListOptions) -> io::Result<Vec<CacheEntrySummary>> {
         let mut entries = Vec::new();
         
         if !self.cache_dir.exists() {
             return Ok(entries);
         }
         
+        let filter = options.filter.as_deref()
+            .map(Pattern::new)
+            .transpose()
+            .map_err(|e| Error::new(ErrorKind::InvalidInput, format!("Invalid filter pattern: {}", e)))?;
+        
         for entry in fs::read_dir(&self.cache_dir)? {
             let entry = entry?;
-            let cache_dir = entry.path();
+            let entry_dir = entry.path();
             
-            if cache_dir.is_dir() {
-                let metadata_path = cache_dir.join("metadata.json");
-                if metadata_path.exists() {
-                    if let Ok(mut file) = File::open(&metadata_path) {
-                        let mut contents = String::new();
-                        if file.read_to_string(&mut contents).is_ok() {
-                            // Parse command and timestamp from metadata
-                            let mut command = String::new();
-                            let mut timestamp = SystemTime::UNIX_EPOCH;
-                            
-                            if let Some(start) = contents.find("\"command\":\"") {
-                                if let Some(end) = contents[start + 11..].find("\"") {
-                                    command = contents[start + 11..start + 11 + end]
-                                        .replace("\\\"", "\"")
-                                        .to_string();
-                                }
-                            }
-                            
-                            if let Some(start) = contents.find("\"timestamp\":") {
-                                if let Some(end) = contents[start + 12..].find("}") {
-                                    if let Ok(secs) = contents[start + 12..start + 12 + end].trim().parse::<u64>() {
-                                        timestamp = SystemTime::UNIX_EPOCH + Duration::from_secs(secs);
-                                    }
-                                }
-                            }
-                            
-                            if !command.is_empty() {
-                                entries.push((command, timestamp));
-                            }
-                        }
-                    }
+            // Skip files and entries still being written
+            if !entry_dir.is_dir() || entry.file_name().to_string_lossy().starts_with('.') {
+                continue;
+            }
+            
+            let metadata = match EntryMetadata::read(&entry_dir.join("metadata.json")) {
+                Ok(Some(metadata)) if !metadata.command.is_empty() => metadata,
+                _ => continue,
+            };
+            
+            // Long commands are matched in full, not as they are shown
+            let matches_filter = filter.as_ref().is_none_or(|filter| {
+                metadata.full_command(&entry_dir).is_ok_and(|command| filter.matches(&command))
+            });
+            if !matches_filter || !options.meta.iter().all(|filter| filter.matches(&metadata.meta)) {
+                continue;
+            }
+            
+            entries.push(summarize_entry(entry.file_name().to_string_lossy().into_owned(), &entry_dir, metadata));
+        }
+        
+        match options.sort {
+            SortBy::Age => entries.sort_by_key(|e| std::cmp::Reverse(e.created_at)),
+            SortBy::Size => entries.sort_by_key(|e| std::cmp::Reverse(e.size)),
+            SortBy::Hits => entries.sort_by_key(|e| std::cmp::Reverse(e.hit_count)),
+            // Entries that were never hit sort last
+            SortBy::LastUsed => entries.sort_by_key(|e| std::cmp::Reverse(e.last_accessed)),
+        }
+        
+        if let Some(limit) = options.limit {
+            entries.truncate(limit);
+        }
+        
+        Ok(entries)
+    }
+    
+    /// Roll up hits, sizes and run times of every entry, across all projects
+    ///
+    /// See [`stats::rollup`] for the order of the rows.
+    pub fn stats(&self, group_by: &[GroupBy]) -> io::Result<Vec<StatsRow>> {
+        let mut entries = Vec::new();
+        
+        if self.cache_dir.exists() {
+            for entry in fs::read_dir(&self.cache_dir)? {
+                let entry = entry?;
+                let entry_dir = entry.path();
+                
+                // Skip files and entries still being written
+                if !entry_dir.is_dir() || entry.file_name().to_string_lossy().starts_with('.') {
+                    continue;
+                }
+                
+                if let Ok(Some(metadata)) = EntryMetadata::read(&entry_dir.join("metadata.json")) {
+                    entries.push((metadata, dir_size(&entry_dir)));
                 }
             }
         }
         
-        entries.sort_by(|a, b| b.1.cmp(&a.1)); // Sort by timestamp, newest first
+        Ok(stats::rollup(&entries, group_by))
+    }
+    
+    /// Find entries by the inputs recorded for them, newest first
+    ///
+    /// Like [`list_cached_commands`](Self::list_cached_commands), this searches
+    /// all projects. The search goes through the index, so only the metadata of
+    /// matching entries is read.
+    pub fn find(&self, query: &FindQuery) -> io::Result<Vec<CacheEntrySummary>> {
+        query.validate()?;
+        
+        let mut entries = Vec::new();
+        for (id, record) in SearchIndex::new(&self.cache_dir).records()? {
+            if !query.matches(&record) {
+                continue;
+            }
+            
+            // The index may be behind entries removed by hand or by older versions
+            let entry_dir = self.cache_dir.join(&id);
+            if !entry_dir.join(COMPLETE_MARKER).exists() {
+                continue;
+            }
+            
+            if let Ok(Some(metadata)) = EntryMetadata::read(&entry_dir.join("metadata.json")) {
+                entries.push(summarize_entry(id, &entry_dir, metadata));
+            }
+        }
+        
+        entries.sort_by_key(|e| std::cmp::Reverse(e.created_at));
         Ok(entries)
     }
     
@@ -377,23 +2272,120 @@ impl CommandCache {
             Some(cmd) => {
                 // Clear specific command
                 let id = self.generate_id(cmd);
-                let cache_dir = self.get_cache_path(&id);
+                let cache_dir = self.entry_dir(&id);
                 if cache_dir.exists() {
                     fs::remove_dir_all(cache_dir)?;
                 }
                 self.cache.remove(cmd);
+                SearchIndex::new(&self.cache_dir).remove([id.as_str()])?;
             },
             None => {
-                // Clear all cache
-                fs::remove_dir_all(&self.cache_dir)?;
-                fs::create_dir_all(&self.cache_dir)?;
-                self.cache.clear();
+                // Clear all entries except pinned ones, leaving alone other
+                // writers' staging directories and the upload queue
+                let mut removed = Vec::new();
+                for entry in fs::read_dir(&self.cache_dir)? {
+                    let entry = entry?;
+                    let path = entry.path();
+                    if !path.is_dir() || entry.file_name().to_string_lossy().starts_with('.') {
+                        continue;
+                    }
+                    
+                    let pinned = EntryMetadata::read(&path.join("metadata.json"))
+                        .ok()
+                        .flatten()
+                        .is_some_and(|metadata| metadata.pinned);
+                    if !pinned {
+                        fs::remove_dir_all(path)?;
+                        removed.push(entry.file_name().to_string_lossy().into_owned());
+                    }
+                }
+                SearchIndex::new(&self.cache_dir).remove(removed.iter().map(String::as_str))?;
+                
+                let pinned: Vec<String> = self.cache.keys()
+                    .filter(|command| self.is_pinned(command))
+                    .cloned()
+                    .collect();
+                self.cache.retain(|command, _| pinned.contains(command));
             }
         }
         
         Ok(())
     }
     
+    /// Summarize a command's cached entry: original run time, size on disk and time to expiry
+    ///
+    /// # Returns
+    ///
+    /// A Result containing the summary, or None if the command has no cached entry
+    pub fn entry_summary(&self, command: &str, ttl: Option<Duration>) -> io::Result<Option<EntrySummary>> {
+        let (_, metadata) = match self.load_entry(command)? {
+            Some(entry) => entry,
+            None => return Ok(None),
+        };
+        
+        let id = self.generate_id(command);
+        let age = self.clock.now().duration_since(metadata.created_at()).unwrap_or_default();
+        let expires_in = self.get_entry_ttl(command, &metadata, ttl)
+            .map(|ttl_duration| ttl_duration.saturating_sub(age));
+        
+        Ok(Some(EntrySummary {
+            duration: metadata.duration(),
+            size: dir_size(&self.cache_dir.join(id)),
+            expires_in,
+        }))
+    }
+    
+    /// Whether a summary line should be printed after running a command
+    pub fn summary_enabled(&self, command: &str) -> bool {
+        self.hint_file.as_ref().is_some_and(|hint_file| hint_file.summary(command))
+    }
+    
+    /// Run a command without touching the cache and compare the result with its cached entry
+    ///
+    /// The fresh stdout goes through the command's `normalize` rules first, so
+    /// it is compared the same way it would have been cached. Expired entries
+    /// are still compared.
+    ///
+    /// # Returns
+    ///
+    /// A Result containing the differences, or None if the command has no cached entry
+    pub fn diff_with_cache(&self, command: &str) -> io::Result<Option<CommandDiff>> {
+        let cached = match self.load_entry(command)? {
+            Some((output, _)) => output,
+            None => return Ok(None),
+        };
+        
+        let mut fresh = self.run_command(command)?;
+        self.take_ttl_directive(command, &mut fresh);
+        fresh.stdout = self.normalize_output(command, &fresh.stdout)?;
+        
+        Ok(Some(CommandDiff::between(&cached, &fresh)))
+    }
+    
+    /// Run several commands concurrently, using the cache for each
+    ///
+    /// Each worker thread gets its own copy of this cache, so entries created
+    /// by the batch are written to disk but not to this instance's memory cache.
+    ///
+    /// # Arguments
+    ///
+    /// * `commands` - The commands to run
+    /// * `ttl` - Time-to-live for the cached entries
+    /// * `force` - Execute every command even if it has a fresh entry
+    /// * `jobs` - Maximum number of commands to run at once
+    ///
+    /// # Returns
+    ///
+    /// A result for each command, in the same order as `commands`
+    pub fn run_all(&self, commands: &[String], ttl: Option<Duration>, force: bool, jobs: usize) -> Vec<TaskResult> {
+        batch::run_all(self, commands, ttl, force, jobs)
+    }
+    
+    /// Commands listed under `tasks:` in the hint file
+    pub fn tasks(&self) -> Vec<String> {
+        self.hint_file.as_ref().map(|hint_file| hint_file.tasks.clone()).unwrap_or_default()
+    }
+    
     /// Get artifacts defined for a command in the hint file
     pub fn get_command_artifacts(&self, command: &str) -> Option<Vec<ArtifactType>> {
         if let Some(hint_file) = &self.hint_file {
@@ -407,9 +2399,15 @@ impl CommandCache {
     }
     
     /// Cache artifacts for a command
-    pub fn cache_artifacts(&self, cache_id: String, _command: &str, artifacts: Vec<ArtifactType>) -> io::Result<()> {
+    pub fn cache_artifacts(&self, cache_id: String, command: &str, artifacts: Vec<ArtifactType>) -> io::Result<()> {
+        let artifact_manager = if self.encryption_enabled(command) {
+            self.artifact_manager.with_cipher(self.cipher()?.clone())
+        } else {
+            self.artifact_manager.clone()
+        };
+        
         for artifact in artifacts {
-            self.artifact_manager.cache_artifact(&artifact, &cache_id, &self.current_dir)?;
+            artifact_manager.cache_artifact(&artifact, &cache_id, &self.current_dir)?;
         }
         Ok(())
     }
@@ -420,9 +2418,16 @@ impl CommandCache {
         
         println!("Restoring artifacts for cache ID: {}", cache_id);
         
+        // Only load the key when there is something to decrypt
+        let artifact_manager = if self.artifact_manager.has_encrypted_archive(&cache_id) {
+            self.artifact_manager.with_cipher(self.cipher()?.clone())
+        } else {
+            self.artifact_manager.clone()
+        };
+        
         for artifact in artifacts {
             println!("Restoring artifact: {:?}", artifact);
-            if !self.artifact_manager.restore_artifact(&artifact, &cache_id, &self.current_dir)? {
+            if !artifact_manager.restore_artifact(&artifact, &cache_id, &self.current_dir)? {
                 println!("Failed to restore artifact");
                 all_restored = false;
             }
@@ -433,60 +2438,161 @@ impl CommandCache {
     }
     
     /// Execute a command and cache both its output and artifacts
+    ///
+    /// Cached output is only served when the command's artifacts can be
+    /// restored as well; see [`CommandCache::execute_with_decision`] to also
+    /// learn where the output came from.
     pub fn execute_and_cache_with_artifacts(&mut self, command: &str, ttl: Option<Duration>, force: bool) -> io::Result<String> {
-        let id = self.generate_id(command);
-        
-        if !force {
-            // Check if we have a cached result with artifacts
-            if let Some(artifacts) = self.get_command_artifacts(command) {
-                if self.restore_artifacts(id.clone(), artifacts.clone()).is_ok() {
-                    // If we successfully restored artifacts, also return the cached stdout
-                    if let Ok(Some((output, timestamp))) = self.load_from_disk_with_timestamp(command) {
-                        // Check TTL
-                        let effective_ttl = self.get_effective_ttl(command, ttl);
-                        
-                        let use_cache = if let Some(ttl_duration) = effective_ttl {
-                            if let Ok(age) = SystemTime::now().duration_since(timestamp) {
-                                age <= ttl_duration
-                            } else {
-                                true
-                            }
-                        } else {
-                            true
-                        };
-                        
-                        if use_cache {
-                            self.store(command, &output);
-                            return Ok(output);
-                        }
-                    }
-                }
-            }
+        self.execute_with_decision(command, ttl, force).map(|decision| decision.output)
+    }
+}
+
+/// Record the layout version in a cache dir that doesn't have it yet
+fn write_layout_version(cache_dir: &Path) -> io::Result<()> {
+    let path = cache_dir.join(LAYOUT_FILE);
+    if path.exists() {
+        return Ok(());
+    }
+    let mut file = tempfile::NamedTempFile::new_in(cache_dir)?;
+    file.write_all(format!("{}\n", LAYOUT_VERSION).as_bytes())?;
+    file.persist_noclobber(&path).map_err(|e| e.error)?;
+    Ok(())
+}
+
+/// Whether this version of cacher accepts a key schema version to salt keys with
+pub(crate) fn key_schema_supported(version: u32) -> bool {
+    (1..=KEY_SCHEMA_VERSION).contains(&version)
+}
+
+/// Spell a dependency path the same way on every platform for hashing into a key
+///
+/// Separators become `/`, and with `case_insensitive` the path is lowercased,
+/// so `Src\Main.rs` and `src/main.rs` give the same key.
+fn key_path(path: &str, case_insensitive: bool) -> String {
+    let path = path.replace('\\', "/");
+    if case_insensitive {
+        path.to_lowercase()
+    } else {
+        path
+    }
+}
+
+/// Build the listing of an entry from its metadata
+fn summarize_entry(id: String, entry_dir: &Path, metadata: EntryMetadata) -> CacheEntrySummary {
+    CacheEntrySummary {
+        id,
+        created_at: metadata.created_at(),
+        last_accessed: metadata.last_accessed(),
+        duration: metadata.duration(),
+        size: dir_size(entry_dir),
+        hit_count: metadata.hit_count,
+        pinned: metadata.pinned,
+        meta: metadata.meta,
+        command: metadata.command,
+    }
+}
+
+/// Mix environment variables into a hash, recording whether each one is set
+///
+/// Variables are hashed in sorted order so the key doesn't depend on set
+/// iteration order, and an unset variable hashes differently from one set to
+/// an empty string.
+fn hash_env_vars<'a>(hasher: &mut Sha256, env_vars: impl IntoIterator<Item = &'a str>) {
+    let mut names: Vec<&str> = env_vars.into_iter().collect();
+    names.sort();
+    
+    for env_var in names {
+        match env::var_os(env_var) {
+            Some(value) => hasher.update(format!("{}=set:{}\n", env_var, value.to_string_lossy()).as_bytes()),
+            None => hasher.update(format!("{}=unset\n", env_var).as_bytes()),
         }
-        
-        // Execute the command normally
-        let output = self.execute_command(command)?;
-        
-        // Cache the stdout
-        self.store(command, &output);
-        self.save_to_disk(command, &output)?;
-        
-        // Cache any artifacts defined for this command
-        if let Some(artifacts) = self.get_command_artifacts(command) {
-            self.cache_artifacts(id, command, artifacts)?;
+    }
+}
+
+/// Turn a non-zero exit into an error carrying the command's output
+fn check_success(output: CommandOutput) -> io::Result<CommandOutput> {
+    if !output.success() {
+        return Err(Error::other(CommandFailed { output, cached: false }));
+    }
+    
+    Ok(output)
+}
+
+/// Read the raw stdout and stderr of an entry, if its `complete` marker matches them
+pub(crate) fn read_complete_output(entry_dir: &Path) -> io::Result<Option<(Vec<u8>, Vec<u8>)>> {
+    let read = |name: &str| match fs::read(entry_dir.join(name)) {
+        Ok(contents) => Ok(Some(contents)),
+        Err(e) if e.kind() == ErrorKind::NotFound => Ok(None),
+        Err(e) => Err(e),
+    };
+    
+    let (Some(marker), Some(stdout), Some(stderr)) = (read(COMPLETE_MARKER)?, read("stdout")?, read("stderr")?) else {
+        return Ok(None);
+    };
+    
+    if marker != output_checksum(&stdout, &stderr).as_bytes() {
+        return Ok(None);
+    }
+    
+    Ok(Some((stdout, stderr)))
+}
+
+/// Move a fully written directory into `root` as `root/name`, replacing what is there
+pub(crate) fn replace_dir(root: &Path, staged: &Path, name: &str) -> io::Result<()> {
+    let target = root.join(name);
+    
+    // Directories can't be renamed over non-empty ones, so move the old one
+    // aside first; it is removed when `replaced` is dropped
+    let replaced = tempfile::Builder::new().prefix(REPLACED_PREFIX).tempdir_in(root)?;
+    match fs::rename(&target, replaced.path().join(name)) {
+        Err(e) if e.kind() != ErrorKind::NotFound => return Err(e),
+        _ => {}
+    }
+    
+    match fs::rename(staged, &target) {
+        Ok(()) => Ok(()),
+        // Another process stored the same entry in between, which is just as good
+        Err(_) if target.exists() => Ok(()),
+        Err(e) => Err(e),
+    }
+}
+
+/// Checksum of an entry's stored stdout and stderr, as written to its `complete` marker
+fn output_checksum(stdout: &[u8], stderr: &[u8]) -> String {
+    let mut hasher = Sha256::new();
+    hasher.update((stdout.len() as u64).to_le_bytes());
+    hasher.update(stdout);
+    hasher.update(stderr);
+    format!("{:x}", hasher.finalize())
+}
+
+/// Total size of the files under a directory
+fn dir_size(path: &Path) -> u64 {
+    let mut size = 0;
+    
+    if let Ok(entries) = fs::read_dir(path) {
+        for entry in entries.flatten() {
+            match entry.metadata() {
+                Ok(metadata) if metadata.is_dir() => size += dir_size(&entry.path()),
+                Ok(metadata) => size += metadata.len(),
+                Err(_) => {}
+            }
         }
-        
-        Ok(output)
     }
+    
+    size
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::clock::ManualClock;
+    use crate::testing::TestEnv;
     
     #[test]
     fn test_store_and_retrieve() {
-        let mut cache = CommandCache::new();
+        let env = TestEnv::new();
+        let mut cache = env.cache();
         let command = "echo hello";
         let output = "hello\n";
         
@@ -496,7 +2602,8 @@ mod tests {
     
     #[test]
     fn test_retrieve_nonexistent() {
-        let cache = CommandCache::new();
+        let env = TestEnv::new();
+        let cache = env.cache();
         let command = "echo nonexistent";
         
         assert_eq!(cache.get(command), None);
@@ -504,7 +2611,8 @@ mod tests {
     
     #[test]
     fn test_generate_id() {
-        let cache = CommandCache::new();
+        let env = TestEnv::new();
+        let cache = env.cache();
         let command = "echo hello";
         
         let id1 = cache.generate_id(command);
@@ -516,7 +2624,8 @@ mod tests {
     
     #[test]
     fn test_disk_cache() {
-        let cache = CommandCache::new();
+        let env = TestEnv::new();
+        let cache = env.cache();
         let command = "test_disk_cache_command";
         let output = "test output";
         
@@ -530,7 +2639,8 @@ mod tests {
     
     #[test]
     fn test_execute_and_cache() {
-        let mut cache = CommandCache::new();
+        let env = TestEnv::new();
+        let mut cache = env.cache();
         let command = "echo test_execute";
         
         // Execute and cache
@@ -547,14 +2657,19 @@ mod tests {
     
     #[test]
     fn test_ttl_and_force() {
-        let mut cache = CommandCache::new();
+        let clock = ManualClock::default();
+        let env = TestEnv::new();
+        let mut cache = env.cache();
+        cache.set_clock(Arc::new(clock.clone()));
         let command = "echo ttl_test";
         
         // Execute and cache with short TTL
         let result1 = cache.execute_and_cache(command, Some(Duration::from_secs(1)), false).unwrap();
+        assert!(cache.get_cached(command, Some(Duration::from_secs(1))).is_some());
         
-        // Wait for TTL to expire
-        std::thread::sleep(Duration::from_secs(2));
+        // Let the TTL expire
+        clock.advance(Duration::from_secs(2));
+        assert!(cache.get_cached(command, Some(Duration::from_secs(1))).is_none());
         
         // Execute again, should re-execute due to expired TTL
         let result2 = cache.execute_and_cache(command, Some(Duration::from_secs(1)), false).unwrap();
@@ -566,16 +2681,55 @@ mod tests {
         assert_eq!(result2, result3);
     }
     
+    #[test]
+    fn test_max_age() {
+        let env = TestEnv::new();
+        let mut cache = env.cache();
+        let command = "echo max_age_test";
+        
+        cache.execute_and_cache(command, None, false).unwrap();
+        assert!(cache.get_cached(command, None).is_some());
+        
+        // Any entry is older than a zero max age, in memory or on disk
+        cache.set_max_age(Some(Duration::ZERO));
+        assert!(cache.get_cached(command, None).is_none());
+        
+        // A generous max age serves the entry again
+        cache.set_max_age(Some(Duration::from_secs(3600)));
+        assert!(cache.get_cached(command, None).is_some());
+    }
+    
+    #[test]
+    fn test_entry_summary() {
+        let env = TestEnv::new();
+        let mut cache = env.cache();
+        let command = "echo summary_test";
+        
+        assert!(cache.entry_summary("echo summary_test_missing", None).unwrap().is_none());
+        
+        cache.execute_and_cache(command, None, true).unwrap();
+        
+        let summary = cache.entry_summary(command, Some(Duration::from_secs(3600))).unwrap().unwrap();
+        assert!(summary.size >= "summary_test\n".len() as u64);
+        let expires_in = summary.expires_in.unwrap();
+        assert!(expires_in <= Duration::from_secs(3600) && expires_in > Duration::from_secs(3500));
+        
+        // Without a TTL the entry never expires
+        let summary = cache.entry_summary(command, None).unwrap().unwrap();
+        assert_eq!(summary.expires_in, None);
+    }
+    
     #[test]
     fn test_list_and_clear_cache() {
-        let mut cache = CommandCache::new();
+        let env = TestEnv::new();
+        let mut cache = env.cache();
         let command = "echo list_test";
         
         // Execute and cache
         let _ = cache.execute_and_cache(command, None, false);
         
         // List cached commands
-        let entries = cache.list_cached_commands().unwrap();
+        let entries = cache.list_cached_commands(&ListOptions::default()).unwrap();
         assert!(!entries.is_empty());
         
         // Clear cache
@@ -586,6 +2740,67 @@ mod tests {
 pub mod hint_file;
 // Add the artifact module
 pub mod artifact;
+// Add the lockfile module
+pub mod lockfile;
+// Add the compose module
+pub mod compose;
+// Add the powershell module
+pub mod powershell;
+// Add the normalize module
+pub mod normalize;
+// Add the project module
+pub mod project;
+// Add the metadata module
+pub mod metadata;
+// Add the diff module
+pub mod diff;
+// Add the process module
+pub mod process;
+// Add the hooks module
+pub mod hooks;
+// Add the batch module
+pub mod batch;
+// Add the shell module
+pub mod shell;
+// Add the crypto module
+pub mod crypto;
+// Add the clock module
+pub mod clock;
+// Add the testing module
+pub mod testing;
+// Add the eval module
+pub mod eval;
+// Add the remote module
+pub mod remote;
+// Add the history module
+pub mod history;
+
+// Add the index module
+pub mod index;
+
+// Add the chain module
+pub mod chain;
+
+// Add the pager module
+pub mod pager;
+
+// Add the binary module
+pub mod binary;
+
+// Add the stats module
+pub mod stats;
+
+// Add the directive module
+pub mod directive;
+
+// Add the bench module
+pub mod bench;
+
+// Add the ignore module
+pub mod ignore;
+
+// Add the invalidate module
+pub mod invalidate;
 
 impl CommandCache {
     /// Reload the hint file from the current directory
@@ -596,6 +2811,9 @@ impl CommandCache {
         let current_dir = std::env::current_dir().unwrap_or_else(|_| PathBuf::from("."));
         self.current_dir = current_dir;
         self.hint_file = HintFile::find_hint_file(&self.current_dir);
+        self.project_id = Self::derive_project_id(&self.current_dir, self.hint_file.as_ref());
+        self.ignore = IgnoreRules::load(&self.current_dir);
+        self.artifact_manager.set_ignore(self.ignore.clone());
     }
     
     /// Get a reference to the current hint file, if one is loaded
@@ -0,0 +1,138 @@
+use std::path::{Path, PathBuf};
+use std::fs;
+use std::io;
+
+/// Lockfile names recognised by `lockfile: auto`
+pub const KNOWN_LOCKFILES: &[&str] = &[
+    "Cargo.lock",
+    "package-lock.json",
+    "npm-shrinkwrap.json",
+    "yarn.lock",
+    "pnpm-lock.yaml",
+    "poetry.lock",
+    "Pipfile.lock",
+    "Gemfile.lock",
+    "composer.lock",
+    "go.sum",
+];
+
+/// Resolve a `lockfile:` dependency value to the lockfiles it refers to
+///
+/// `auto` expands to every known lockfile present in `base_dir`; any other
+/// value is treated as a path relative to `base_dir`.
+///
+/// # Returns
+///
+/// The existing lockfiles, paired with the name used for them in cache keys
+pub fn resolve(base_dir: &Path, lockfile: &str) -> Vec<(String, PathBuf)> {
+    if lockfile == "auto" {
+        KNOWN_LOCKFILES
+            .iter()
+            .map(|name| (name.to_string(), base_dir.join(name)))
+            .filter(|(_, path)| path.is_file())
+            .collect()
+    } else {
+        let path = base_dir.join(lockfile);
+        if path.is_file() {
+            vec![(lockfile.to_string(), path)]
+        } else {
+            Vec::new()
+        }
+    }
+}
+
+/// Read the dependency-relevant content of a lockfile
+///
+/// Strips parts of the lockfile that change without the resolved dependency
+/// set changing, such as comments, the project's own name and version, and
+/// workspace-local packages in `Cargo.lock`.
+///
+/// # Arguments
+///
+/// * `path` - Path to the lockfile
+///
+/// # Returns
+///
+/// A Result containing the normalized lockfile content
+pub fn dependency_content(path: &Path) -> io::Result<String> {
+    let content = fs::read_to_string(path)?;
+    let file_name = path.file_name().and_then(|n| n.to_str()).unwrap_or("");
+
+    match file_name {
+        "package-lock.json" | "npm-shrinkwrap.json" => normalize_npm(&content),
+        "composer.lock" | "Pipfile.lock" => normalize_json(&content, &["_readme"]),
+        "Cargo.lock" => Ok(normalize_cargo(&content)),
+        _ => Ok(strip_comments(&content)),
+    }
+}
+
+/// Drop the project's own name and version from an npm lockfile
+fn normalize_npm(content: &str) -> io::Result<String> {
+    let mut value: serde_json::Value = serde_json::from_str(content)
+        .map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))?;
+
+    if let Some(root) = value.as_object_mut() {
+        root.remove("name");
+        root.remove("version");
+
+        // lockfileVersion 2+ repeats the root package under the "" key
+        if let Some(project) = root
+            .get_mut("packages")
+            .and_then(|packages| packages.get_mut(""))
+            .and_then(|project| project.as_object_mut())
+        {
+            project.remove("name");
+            project.remove("version");
+        }
+    }
+
+    Ok(value.to_string())
+}
+
+/// Drop top-level keys from a JSON lockfile
+fn normalize_json(content: &str, ignored_keys: &[&str]) -> io::Result<String> {
+    let mut value: serde_json::Value = serde_json::from_str(content)
+        .map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))?;
+
+    if let Some(root) = value.as_object_mut() {
+        for key in ignored_keys {
+            root.remove(*key);
+        }
+    }
+
+    Ok(value.to_string())
+}
+
+/// Keep only `[[package]]` entries that come from a registry or git source
+///
+/// Workspace members have no `source` line, and their versions change on
+/// every release without the fetched dependency set changing.
+fn normalize_cargo(content: &str) -> String {
+    let mut normalized = String::new();
+
+    for block in content.split("[[package]]").skip(1) {
+        let block = strip_comments(block);
+        if block.lines().any(|line| line.starts_with("source = ")) {
+            normalized.push_str("[[package]]\n");
+            normalized.push_str(&block);
+        }
+    }
+
+    normalized
+}
+
+/// Remove comment and blank lines
+fn strip_comments(content: &str) -> String {
+    let mut stripped = String::new();
+
+    for line in content.lines() {
+        let trimmed = line.trim();
+        if trimmed.is_empty() || trimmed.starts_with('#') {
+            continue;
+        }
+        stripped.push_str(line);
+        stripped.push('\n');
+    }
+
+    stripped
+}
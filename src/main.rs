@@ -1,17 +1,43 @@
-use cacher::CommandCache;
-use clap::{Parser, Subcommand};
+use cacher::{CacheDecision, CacheEntrySummary, CacheSource, CommandCache, CommandFailed, CommandKilled, CommandOutput, EntrySummary, ListOptions, SortBy};
+use cacher::index::FindQuery;
+use cacher::invalidate::InvalidationManifest;
+use cacher::metadata::{parse_meta_pair, EntryInputs, MetaFilter};
+use cacher::batch::{parse_task_list, TaskResult};
+use cacher::bench;
+use cacher::hint_file::HintFile;
+use cacher::pager;
+use cacher::shell::{self, Shell};
+use cacher::stats::{self, EntryStats, GroupBy, StatsRow};
+use clap::{CommandFactory, Parser, Subcommand};
+use std::collections::BTreeMap;
+use std::io::{self, IsTerminal, Read, Write};
+use std::path::PathBuf;
 use std::time::{Duration, SystemTime};
 
+mod ui;
+
 #[derive(Parser)]
 #[command(author, version, about, long_about = None)]
 struct Cli {
     #[command(subcommand)]
     command: Option<Commands>,
+    
+    /// Salt cache keys with this key schema version instead of the current
+    /// one; keys are still derived the current way, so this only finds an
+    /// older cacher's entries whose keys it derived the same way
+    #[arg(long, global = true, value_name = "VERSION")]
+    key_schema: Option<u32>,
 }
 
 #[derive(Subcommand)]
 enum Commands {
     /// Run a command with caching
+    ///
+    /// A single argument is a command line, split on whitespace with shell
+    /// quoting (`cacher run "ls -la"`). With several, they are the program and
+    /// its arguments, passed through unchanged (`cacher run -- git log --format="%h %s"`).
+    /// The command's stdout and stderr are written back as they were, and cacher
+    /// exits with the command's exit code.
     Run {
         /// The command to run
         #[arg(required = true)]
@@ -28,10 +54,274 @@ enum Commands {
         /// Force execution (ignore cache)
         #[arg(short, long)]
         force: bool,
+        
+        /// Treat cached entries older than this as misses (e.g. 30s, 10m, 2h)
+        #[arg(long, value_parser = parse_duration)]
+        max_age: Option<Duration>,
+        
+        /// Print a one-line cache summary to stderr after running
+        #[arg(long)]
+        summary: bool,
+        
+        /// Kill the command if it runs longer than this (e.g. 300, 5m); nothing is cached
+        #[arg(long, value_parser = parse_duration)]
+        timeout: Option<Duration>,
+        
+        /// Kill the command if it writes more than this many bytes; nothing is cached
+        #[arg(long)]
+        max_output_bytes: Option<u64>,
+        
+        /// Re-extract cached artifacts even if they already match what was cached
+        #[arg(long)]
+        force_restore: bool,
+        
+        /// Run the command through the shell, so pipes, `&&` and `;` work
+        #[arg(long)]
+        shell: bool,
+        
+        /// Attach a key=value pair to the entry, e.g. build=1234; may be repeated
+        #[arg(long, value_name = "KEY=VALUE", value_parser = parse_meta_pair)]
+        meta: Vec<(String, String)>,
+    },
+    
+    /// Run a code snippet with an interpreter, caching its output
+    ///
+    /// The cache key includes the interpreter's version and the contents of
+    /// the files the snippet imports, as configured under `interpreters:` in
+    /// the hint file or passed with --import.
+    Eval {
+        /// Language of the snippet (python, node, ruby, perl, or one from the hint file)
+        #[arg(short, long)]
+        lang: String,
+        
+        /// The code to run
+        #[arg(required = true)]
+        snippet: String,
+        
+        /// Glob pattern of files the snippet imports; may be repeated
+        #[arg(short, long = "import")]
+        imports: Vec<String>,
+        
+        /// Time-to-live for cache in seconds (default: no TTL)
+        #[arg(short, long)]
+        ttl: Option<u64>,
+        
+        /// Force execution (ignore cache)
+        #[arg(short, long)]
+        force: bool,
+    },
+    
+    /// Print the cached output for a command without running it
+    Get {
+        /// The command to look up
+        #[arg(required = true)]
+        command: String,
+        
+        /// Arguments for the command
+        #[arg(num_args = 0..)]
+        args: Vec<String>,
+        
+        /// Time-to-live for cache in seconds (default: no TTL)
+        #[arg(short, long)]
+        ttl: Option<u64>,
+        
+        /// Treat cached entries older than this as misses (e.g. 30s, 10m, 2h)
+        #[arg(long, value_parser = parse_duration)]
+        max_age: Option<Duration>,
+        
+        /// Only print the first N lines of the output
+        #[arg(long, value_name = "N", conflicts_with = "pager")]
+        preview: Option<usize>,
+        
+        /// Show the output in $PAGER (default: less -R)
+        #[arg(long)]
+        pager: bool,
+    },
+    
+    /// Show a command's cached entry: its metadata, stdout and stderr
+    ///
+    /// Expired entries are shown too. Nothing is run, and viewing an entry
+    /// doesn't count as a cache hit.
+    Inspect {
+        /// The command to look up
+        #[arg(required = true)]
+        command: String,
+        
+        /// Arguments for the command
+        #[arg(num_args = 0..)]
+        args: Vec<String>,
+        
+        /// Only print the first N lines of stdout and stderr
+        #[arg(long, value_name = "N", conflicts_with = "pager")]
+        preview: Option<usize>,
+        
+        /// Show the entry in $PAGER (default: less -R)
+        #[arg(long)]
+        pager: bool,
+    },
+    
+    /// Run a command without caching and diff the result against its cached entry
+    ///
+    /// Exits with status 1 if the output, stderr or exit code changed, and 2 if
+    /// the command has no cached entry.
+    Diff {
+        /// The command to run
+        #[arg(required = true)]
+        command: String,
+        
+        /// Arguments for the command
+        #[arg(num_args = 0..)]
+        args: Vec<String>,
+    },
+    
+    /// Run a list of commands concurrently, using the cache for each
+    ///
+    /// Commands are read one per line from FILE, from stdin if FILE is `-`,
+    /// or from the hint file's `tasks:` section if FILE is omitted. Exits with
+    /// status 1 if any command failed.
+    RunAll {
+        /// File listing the commands to run, or `-` for stdin
+        file: Option<String>,
+        
+        /// Maximum number of commands to run at once (default: number of CPUs)
+        #[arg(short, long)]
+        jobs: Option<usize>,
+        
+        /// Time-to-live for cache in seconds (default: no TTL)
+        #[arg(short, long)]
+        ttl: Option<u64>,
+        
+        /// Force execution (ignore cache)
+        #[arg(short, long)]
+        force: bool,
+        
+        /// Print each command's output, in list order, before the summary table
+        #[arg(short, long)]
+        output: bool,
+    },
+    
+    /// Print a completion script for cacher itself
+    Completions {
+        /// Shell to generate completions for
+        shell: clap_complete::Shell,
+    },
+    
+    /// Print shell functions that run this project's configured commands through cacher
+    ///
+    /// Add `eval "$(cacher shell-init bash)"` (or `cacher shell-init fish | source`)
+    /// to your shell's startup file, run from the project directory.
+    ShellInit {
+        /// Shell to generate functions for
+        #[arg(value_parser = ["bash", "zsh", "fish"])]
+        shell: String,
     },
     
     /// List cached commands
-    List,
+    List {
+        /// Order entries by age (newest first), size, hits or last-used
+        #[arg(short, long, default_value = "age")]
+        sort: SortBy,
+        
+        /// Only list commands matching this glob pattern (e.g. "npm *")
+        #[arg(long)]
+        filter: Option<String>,
+        
+        /// List at most this many entries
+        #[arg(short = 'n', long)]
+        limit: Option<usize>,
+        
+        /// Only list entries with this key=value pair; the value may be a glob pattern
+        #[arg(long, value_name = "KEY=VALUE")]
+        meta: Vec<MetaFilter>,
+    },
+    
+    /// Find cached entries by their inputs rather than their command
+    ///
+    /// Searches the environment variables, dependencies, tags and hint
+    /// patterns recorded for each entry. Every given criterion must match.
+    Find {
+        /// Entries whose key includes this environment variable
+        #[arg(long)]
+        env: Vec<String>,
+        
+        /// Entries depending on this file or glob pattern (e.g. package.json)
+        #[arg(long)]
+        dep: Vec<String>,
+        
+        /// Entries with this tag
+        #[arg(long)]
+        tag: Vec<String>,
+        
+        /// Entries whose command hint pattern matches this glob pattern
+        #[arg(long)]
+        pattern: Option<String>,
+        
+        /// Entries created within this long (e.g. 30m, 2d)
+        #[arg(long, value_parser = parse_duration)]
+        since: Option<Duration>,
+        
+        /// Entries with this key=value pair; the value may be a glob pattern
+        #[arg(long, value_name = "KEY=VALUE")]
+        meta: Vec<MetaFilter>,
+    },
+    
+    /// Show how effective the cache is: entries, hits, size and time saved
+    ///
+    /// Covers the entries of every project. With --group-by, the totals are
+    /// rolled up by each level in turn, e.g. `--group-by project,day` for each
+    /// project's entries per day of creation.
+    Stats {
+        /// Group by project, pattern or day; separate levels with commas to nest them
+        #[arg(long, value_delimiter = ',')]
+        group_by: Vec<GroupBy>,
+        
+        /// Print a table, or CSV of the innermost groups for spreadsheets and graphing tools
+        #[arg(long, default_value = "table", value_parser = ["table", "csv"])]
+        format: String,
+    },
+    
+    /// Browse and manage cached entries in an interactive terminal UI
+    ///
+    /// Shows every entry with its size, age, hit count and run time. Entries
+    /// can be viewed, pinned, deleted, refreshed and filtered by command.
+    Ui,
+    
+    /// List pinned or frequently hit entries that expire soon
+    ///
+    /// Runs the hint file's `on_expiring` hook once for each listed entry and
+    /// re-runs entries whose command sets `refresh_before_expiry`. Meant to be
+    /// run periodically, from cron or with --watch, with --refresh to re-run
+    /// every listed command before its entry goes stale.
+    Expiring {
+        /// How far ahead to look (e.g. 30s, 10m, 2h)
+        #[arg(short, long, value_parser = parse_duration, default_value = "5m")]
+        within: Duration,
+        
+        /// Also include unpinned entries hit at least this many times
+        #[arg(long, default_value_t = 5)]
+        min_hits: u64,
+        
+        /// Time-to-live in seconds for commands without one in the hint file
+        #[arg(short, long)]
+        ttl: Option<u64>,
+        
+        /// Re-run each listed command to refresh its entry
+        #[arg(short, long)]
+        refresh: bool,
+        
+        /// Keep running, checking again at this interval (e.g. 1m)
+        #[arg(long, value_parser = parse_duration, value_name = "INTERVAL")]
+        watch: Option<Duration>,
+    },
+    
+    /// Remove partially written and corrupted entries
+    Gc,
+    
+    /// Upload entries queued by `write: back` commands to the remote store
+    ///
+    /// Runs in the background automatically after a command queues uploads;
+    /// entries that fail to upload stay queued for the next sync.
+    Sync,
     
     /// Clear the cache
     Clear {
@@ -44,6 +334,73 @@ enum Commands {
         command: Option<String>,
     },
     
+    /// Purge the entries a manifest selects, all or nothing, e.g. after a deploy
+    ///
+    /// The manifest is a YAML file listing `patterns` of commands, hint `tags`
+    /// and project `namespaces`; entries matching any of them are purged
+    /// across all projects. Pinned entries are kept unless it sets
+    /// `include_pinned: true`.
+    Invalidate {
+        /// The manifest of entries to purge
+        #[arg(long, value_name = "FILE")]
+        manifest: PathBuf,
+        
+        /// List the entries that would be purged without purging them
+        #[arg(long)]
+        dry_run: bool,
+    },
+    
+    /// List earlier outputs of a command, or print one of them
+    ///
+    /// Only kept for commands with `history` set in the hint file.
+    History {
+        /// The command to show the history of
+        #[arg(required = true)]
+        command: String,
+        
+        /// Arguments for the command
+        #[arg(num_args = 0..)]
+        args: Vec<String>,
+        
+        /// Print this generation's output (1 is the one before the current output)
+        #[arg(short, long)]
+        generation: Option<usize>,
+    },
+    
+    /// Show what a command's cache key is made of
+    ///
+    /// For a chain like `npm ci && npm run build` run in shell mode, each part
+    /// is listed with its dependencies, how long it took when cached, and
+    /// whether its inputs changed since.
+    Explain {
+        /// The command to explain
+        #[arg(required = true)]
+        command: String,
+        
+        /// Arguments for the command
+        #[arg(num_args = 0..)]
+        args: Vec<String>,
+        
+        /// Explain the command as run with `cacher run --shell`
+        #[arg(long)]
+        shell: bool,
+    },
+    
+    /// Time the hot paths of the cache against their performance budgets
+    ///
+    /// Covers key generation with a large dependency set, serving a hit from
+    /// disk and checking a cached directory artifact. Budgets are for release
+    /// builds; `cargo bench` runs the same paths through criterion.
+    Bench {
+        /// Exit with status 1 if any path is over its budget
+        #[arg(long)]
+        assert: bool,
+        
+        /// How many times to run each path; the median is reported
+        #[arg(short = 'n', long, default_value_t = 20)]
+        iterations: usize,
+    },
+    
     /// Get the UUID (hash) for a command
     Hash {
         /// The command to get the hash for
@@ -59,38 +416,358 @@ enum Commands {
 fn main() {
     let cli = Cli::parse();
     let mut cache = CommandCache::new();
+    if let Some(version) = cli.key_schema {
+        if let Err(e) = cache.set_key_schema(version) {
+            eprintln!("Error: {}", e);
+            std::process::exit(1);
+        }
+    }
     
     match &cli.command {
-        Some(Commands::Run { command, args, ttl, force }) => {
-            // Combine command and args into a single string
-            let full_command = format!("{} {}", command, args.join(" ")).trim().to_string();
+        Some(Commands::Run { command, args, ttl, force, max_age, summary, timeout, max_output_bytes, force_restore, shell, meta }) => {
+            if *shell {
+                cache.set_shell(true);
+            }
+            let full_command = command_line(&mut cache, command, args);
             
             // Convert TTL to Duration if provided
-            let ttl_duration = ttl.map(|seconds| Duration::from_secs(seconds));
+            let ttl_duration = ttl.map(Duration::from_secs);
+            cache.set_max_age(*max_age);
+            cache.set_timeout(*timeout);
+            cache.set_max_output_bytes(*max_output_bytes);
+            cache.set_force_restore(*force_restore);
+            cache.set_meta(meta.iter().cloned().collect());
+            
+            let show_summary = *summary || cache.summary_enabled(&full_command);
+            
+            match cache.execute_with_decision(&full_command, ttl_duration, *force) {
+                Ok(decision) => {
+                    print!("{}", decision.output);
+                    eprint!("{}", decision.stderr);
+                    if show_summary {
+                        if decision.source == CacheSource::Bypassed {
+                            let reason = cache.no_cache_reason(&full_command).unwrap_or_default();
+                            eprintln!("cache bypassed, {}", reason);
+                        } else if let Ok(Some(entry)) = cache.entry_summary(&full_command, ttl_duration) {
+                            eprintln!("{}", format_summary(&decision, &entry));
+                        }
+                    }
+                },
+                Err(e) => {
+                    // A failed command's output is written back as it was, with its exit code
+                    if let Some(failed) = e.get_ref().and_then(|e| e.downcast_ref::<CommandFailed>()) {
+                        print!("{}", failed.output.stdout);
+                        eprint!("{}", failed.output.stderr);
+                        if show_summary && failed.cached {
+                            eprintln!("cache hit, cached failure (exit {})", failed.output.exit_code);
+                        }
+                        let _ = io::stdout().flush();
+                        std::process::exit(if failed.output.exit_code > 0 { failed.output.exit_code } else { 1 });
+                    }
+                    
+                    eprintln!("Error executing command: {}", e);
+                    
+                    // Show what a killed command managed to print, for debugging
+                    if let Some(killed) = e.get_ref().and_then(|e| e.downcast_ref::<CommandKilled>()) {
+                        print_partial_output(&killed.partial_output);
+                    }
+                    std::process::exit(1);
+                },
+            }
+        },
+        Some(Commands::Eval { lang, snippet, imports, ttl, force }) => {
+            let ttl_duration = ttl.map(Duration::from_secs);
+            
+            match cache.eval(lang, snippet, imports, ttl_duration, *force) {
+                Ok(output) => print!("{}", output),
+                Err(e) => {
+                    eprintln!("Error: {}", e);
+                    std::process::exit(1);
+                }
+            }
+        },
+        Some(Commands::Get { command, args, ttl, max_age, preview, pager }) => {
+            let full_command = command_line(&mut cache, command, args);
+            
+            let ttl_duration = ttl.map(Duration::from_secs);
+            cache.set_max_age(*max_age);
+            
+            match cache.get_cached(&full_command, ttl_duration) {
+                Some(output) => match preview {
+                    Some(lines) => print_preview(&output, *lines),
+                    None => show_output(&output, *pager),
+                },
+                None => {
+                    eprintln!("No fresh cache entry for command: {}", full_command);
+                    std::process::exit(1);
+                }
+            }
+        },
+        Some(Commands::Inspect { command, args, preview, pager }) => {
+            let full_command = command_line(&mut cache, command, args);
+            
+            let (output, metadata) = match cache.load_entry(&full_command) {
+                Ok(Some(entry)) => entry,
+                Ok(None) => {
+                    eprintln!("No cache entry for command: {}", full_command);
+                    std::process::exit(1);
+                },
+                Err(e) => {
+                    eprintln!("Error reading cache entry: {}", e);
+                    std::process::exit(1);
+                }
+            };
+            
+            let mut header = format!("Command: {}\nHash: {}\n", metadata.command, cache.generate_id(&full_command));
+            header.push_str(&format!(
+                "Cached: {}, took {}, exit {}\n",
+                format_time_ago(&metadata.created_at()), format_duration(&metadata.duration()), metadata.exit_code,
+            ));
+            if let Ok(Some(entry)) = cache.entry_summary(&full_command, None) {
+                header.push_str(&format!("Size: {}, {}\n", format_size(entry.size), format_expiry(entry.expires_in)));
+            }
+            header.push_str(&format!("Hits: {}\n", metadata.hit_count));
+            if !metadata.meta.is_empty() {
+                header.push_str(&format!("Meta: {}\n", format_meta(&metadata.meta)));
+            }
+            
+            match preview {
+                Some(lines) => {
+                    print!("{}", header);
+                    println!("--- stdout ---");
+                    print_preview(&output.stdout, *lines);
+                    if !output.stderr.is_empty() {
+                        println!("--- stderr ---");
+                        print_preview(&output.stderr, *lines);
+                    }
+                },
+                None => {
+                    let mut text = format!("{}--- stdout ---\n{}", header, output.stdout);
+                    if !output.stderr.is_empty() {
+                        text.push_str(&format!("--- stderr ---\n{}", output.stderr));
+                    }
+                    show_output(&text, *pager);
+                },
+            }
+        },
+        Some(Commands::Diff { command, args }) => {
+            let full_command = command_line(&mut cache, command, args);
+            
+            match cache.diff_with_cache(&full_command) {
+                Ok(Some(diff)) => {
+                    if diff.is_identical() {
+                        return;
+                    }
+                    if let Some(stdout_diff) = &diff.stdout {
+                        print!("{}", stdout_diff);
+                    }
+                    if let Some(stderr_diff) = &diff.stderr {
+                        print!("{}", stderr_diff);
+                    }
+                    if let Some((cached, fresh)) = diff.exit_code {
+                        println!("exit code: {} (cached) -> {} (fresh)", cached, fresh);
+                    }
+                    std::process::exit(1);
+                },
+                Ok(None) => {
+                    eprintln!("No cached entry for command: {}", full_command);
+                    std::process::exit(2);
+                },
+                Err(e) => {
+                    eprintln!("Error executing command: {}", e);
+                    std::process::exit(2);
+                },
+            }
+        },
+        Some(Commands::RunAll { file, jobs, ttl, force, output }) => {
+            let commands = match file.as_deref() {
+                Some("-") => {
+                    let mut content = String::new();
+                    match std::io::stdin().read_to_string(&mut content) {
+                        Ok(_) => parse_task_list(&content),
+                        Err(e) => {
+                            eprintln!("Error reading commands from stdin: {}", e);
+                            std::process::exit(1);
+                        }
+                    }
+                },
+                Some(path) => match std::fs::read_to_string(path) {
+                    Ok(content) => parse_task_list(&content),
+                    Err(e) => {
+                        eprintln!("Error reading {}: {}", path, e);
+                        std::process::exit(1);
+                    }
+                },
+                None => cache.tasks(),
+            };
+            
+            if commands.is_empty() {
+                eprintln!("No commands to run. Pass a file, `-` for stdin, or add `tasks:` to the hint file.");
+                std::process::exit(1);
+            }
+            
+            let jobs = jobs.unwrap_or_else(|| {
+                std::thread::available_parallelism().map(|n| n.get()).unwrap_or(1)
+            });
+            let results = cache.run_all(&commands, ttl.map(Duration::from_secs), *force, jobs);
+            
+            if *output {
+                for result in &results {
+                    if let Ok(stdout) = &result.result {
+                        println!("==> {} <==", result.command);
+                        println!("{}", stdout);
+                    }
+                }
+            }
+            
+            print_task_table(&results);
             
-            match cache.execute_and_cache_with_artifacts(&full_command, ttl_duration, *force) {
-                Ok(output) => println!("{}", output),
-                Err(e) => eprintln!("Error executing command: {}", e),
+            if !results.iter().all(TaskResult::success) {
+                start_background_sync(&cache);
+                std::process::exit(1);
             }
         },
-        Some(Commands::List) => {
-            match cache.list_cached_commands() {
+        Some(Commands::Completions { shell }) => {
+            let mut command = Cli::command();
+            let name = command.get_name().to_string();
+            clap_complete::generate(*shell, &mut command, name, &mut std::io::stdout());
+        },
+        Some(Commands::ShellInit { shell }) => {
+            let current_dir = std::env::current_dir().unwrap_or_else(|_| PathBuf::from("."));
+            let hint_file_path = match HintFile::find_hint_file_path(&current_dir) {
+                Some(path) => path,
+                None => {
+                    eprintln!("No .cacher hint file found in {} or its parents", current_dir.display());
+                    std::process::exit(1);
+                }
+            };
+            
+            let hint_file = match HintFile::from_file(&hint_file_path) {
+                Ok(hint_file) => hint_file,
+                Err(e) => {
+                    eprintln!("Error: {:#}", e);
+                    std::process::exit(1);
+                }
+            };
+            
+            let shell: Shell = shell.parse().unwrap_or_else(|e: String| {
+                eprintln!("{}", e);
+                std::process::exit(1);
+            });
+            let project_dir = hint_file_path.parent().unwrap_or(&current_dir);
+            print!("{}", shell::init_script(shell, &hint_file, project_dir));
+        },
+        Some(Commands::List { sort, filter, limit, meta }) => {
+            let options = ListOptions { sort: *sort, filter: filter.clone(), limit: *limit, meta: meta.clone() };
+            match cache.list_cached_commands(&options) {
                 Ok(entries) => {
                     if entries.is_empty() {
                         println!("No cached commands found.");
                     } else {
                         println!("Cached commands:");
-                        for (i, (command, timestamp)) in entries.iter().enumerate() {
-                            let age = format_time_ago(timestamp);
-                            let hash = cache.generate_id(command);
-                            println!("{}. {} ({})", i + 1, command, age);
-                            println!("   Hash: {}", hash);
-                        }
+                        print_entries(&entries);
                     }
                 },
                 Err(e) => eprintln!("Error listing cache: {}", e),
             }
         },
+        Some(Commands::Find { env, dep, tag, pattern, since, meta }) => {
+            let since = match since.map(|since| SystemTime::now().checked_sub(since)) {
+                Some(None) => {
+                    eprintln!("Error: --since is too far in the past");
+                    std::process::exit(1);
+                },
+                Some(Some(since)) => Some(since),
+                None => None,
+            };
+            let query = FindQuery {
+                env: env.clone(),
+                dependencies: dep.clone(),
+                tags: tag.clone(),
+                pattern: pattern.clone(),
+                since,
+                meta: meta.clone(),
+            };
+            
+            match cache.find(&query) {
+                Ok(entries) if entries.is_empty() => println!("No matching entries found."),
+                Ok(entries) => print_entries(&entries),
+                Err(e) => {
+                    eprintln!("Error searching cache: {}", e);
+                    std::process::exit(1);
+                }
+            }
+        },
+        Some(Commands::Stats { group_by, format }) => {
+            let rows = match cache.stats(group_by) {
+                Ok(rows) => rows,
+                Err(e) => {
+                    eprintln!("Error reading cache: {}", e);
+                    std::process::exit(1);
+                }
+            };
+            
+            if format == "csv" {
+                print!("{}", stats::to_csv(&rows, group_by));
+            } else {
+                print_stats_table(&rows);
+            }
+        },
+        Some(Commands::Ui) => {
+            if let Err(e) = ui::run(&mut cache) {
+                eprintln!("Error: {}", e);
+                std::process::exit(1);
+            }
+        },
+        Some(Commands::Expiring { within, min_hits, ttl, refresh, watch }) => {
+            let ttl_duration = ttl.map(Duration::from_secs);
+            
+            if let Some(interval) = watch {
+                let mut sync: Option<std::process::Child> = None;
+                loop {
+                    cache.reload_hint_file();
+                    check_expiring(&mut cache, *within, *min_hits, ttl_duration, *refresh);
+                    
+                    // Upload what the refreshes queued, once the previous upload is done
+                    if !sync.as_mut().is_some_and(|child| matches!(child.try_wait(), Ok(None))) {
+                        sync = start_background_sync(&cache);
+                    }
+                    std::thread::sleep(*interval);
+                }
+            }
+            
+            if !check_expiring(&mut cache, *within, *min_hits, ttl_duration, *refresh) {
+                start_background_sync(&cache);
+                std::process::exit(1);
+            }
+        },
+        Some(Commands::Sync) => {
+            match cache.sync() {
+                Ok(report) => {
+                    println!("Uploaded {} entries.", report.uploaded);
+                    for (id, e) in &report.failed {
+                        eprintln!("Failed to upload {}: {}", id, e);
+                    }
+                    if !report.failed.is_empty() {
+                        std::process::exit(1);
+                    }
+                },
+                Err(e) => {
+                    eprintln!("Error: {}", e);
+                    std::process::exit(1);
+                }
+            }
+        },
+        Some(Commands::Gc) => {
+            match cache.gc() {
+                Ok(0) => println!("No incomplete entries found."),
+                Ok(removed) => println!("Removed {} incomplete entries.", removed),
+                Err(e) => {
+                    eprintln!("Error: {}", e);
+                    std::process::exit(1);
+                }
+            }
+        },
         Some(Commands::Clear { all, command }) => {
             if *all {
                 match cache.clear_cache(None) {
@@ -98,7 +775,8 @@ fn main() {
                     Err(e) => eprintln!("Error clearing cache: {}", e),
                 }
             } else if let Some(cmd) = command {
-                match cache.clear_cache(Some(cmd)) {
+                let cmd = command_line(&mut cache, cmd, &[]);
+                match cache.clear_cache(Some(&cmd)) {
                     Ok(_) => println!("Cleared cache for command: {}", cmd),
                     Err(e) => eprintln!("Error clearing cache: {}", e),
                 }
@@ -106,9 +784,139 @@ fn main() {
                 println!("Please specify --all to clear all cache or --command to clear a specific command.");
             }
         },
+        Some(Commands::Invalidate { manifest, dry_run }) => {
+            let manifest = match InvalidationManifest::from_file(manifest) {
+                Ok(manifest) => manifest,
+                Err(e) => {
+                    eprintln!("Error: {}", e);
+                    std::process::exit(1);
+                }
+            };
+            
+            let result = if *dry_run {
+                cache.invalidation_targets(&manifest)
+            } else {
+                cache.invalidate(&manifest)
+            };
+            
+            match result {
+                Ok(entries) if entries.is_empty() => println!("No matching entries found."),
+                Ok(entries) => {
+                    let verb = if *dry_run { "Would invalidate" } else { "Invalidated" };
+                    println!("{} {} entries:", verb, entries.len());
+                    print_entries(&entries);
+                },
+                Err(e) => {
+                    eprintln!("Error invalidating cache: {}", e);
+                    std::process::exit(1);
+                }
+            }
+        },
+        Some(Commands::History { command, args, generation }) => {
+            let full_command = command_line(&mut cache, command, args);
+            
+            let history = match cache.history(&full_command) {
+                Ok(history) => history,
+                Err(e) => {
+                    eprintln!("Error reading history: {}", e);
+                    std::process::exit(1);
+                }
+            };
+            
+            match generation {
+                Some(generation) => match generation.checked_sub(1).and_then(|i| history.get(i)) {
+                    Some(entry) => print!("{}", entry.stdout),
+                    None => {
+                        eprintln!("No generation {} for command: {}", generation, full_command);
+                        std::process::exit(1);
+                    }
+                },
+                None if history.is_empty() => println!("No history for command: {}", full_command),
+                None => {
+                    for (i, entry) in history.iter().enumerate() {
+                        println!(
+                            "{}. {} ({})",
+                            i + 1, format_time_ago(&entry.created_at), format_size(entry.stdout.len() as u64),
+                        );
+                    }
+                },
+            }
+        },
+        Some(Commands::Explain { command, args, shell }) => {
+            if *shell {
+                cache.set_shell(true);
+            }
+            let full_command = command_line(&mut cache, command, args);
+            
+            let explanation = match cache.explain(&full_command) {
+                Ok(explanation) => explanation,
+                Err(e) => {
+                    eprintln!("Error: {}", e);
+                    std::process::exit(1);
+                }
+            };
+            
+            println!("Command: {}", full_command);
+            println!("Hash: {}", explanation.id);
+            println!("Key schema: {}", explanation.key_schema);
+            println!("Shell: {}", if explanation.shell { "yes" } else { "no" });
+            match (&explanation.cached, &explanation.previous) {
+                (Some(metadata), _) => println!(
+                    "Cached: {}, took {}",
+                    format_time_ago(&metadata.created_at()), format_duration(&metadata.duration()),
+                ),
+                (None, Some(metadata)) => println!(
+                    "Cached: no; last cached {} before its inputs changed",
+                    format_time_ago(&metadata.created_at()),
+                ),
+                (None, None) => println!("Cached: no"),
+            }
+            print_inputs("", &explanation.inputs);
+            
+            if !explanation.parts.is_empty() {
+                println!("Parts:");
+                for (i, part) in explanation.parts.iter().enumerate() {
+                    let separator = part.part.separator.map(|separator| format!("{} ", separator.as_str())).unwrap_or_default();
+                    let mut details = Vec::new();
+                    match (part.part.duration(), part.part.exit_code) {
+                        (Some(duration), Some(code)) => details.push(format!("took {}, exit {}", format_duration(&duration), code)),
+                        _ if explanation.cached.is_some() || explanation.previous.is_some() => details.push("didn't run".to_string()),
+                        _ => {},
+                    }
+                    if part.changed {
+                        details.push("changed".to_string());
+                    }
+                    let details = if details.is_empty() { String::new() } else { format!(" ({})", details.join(", ")) };
+                    
+                    println!("{}. {}{}{}", i + 1, separator, part.part.command, details);
+                    print_inputs("   ", &part.inputs);
+                }
+            }
+        },
+        Some(Commands::Bench { assert, iterations }) => {
+            let results = match bench::run(*iterations) {
+                Ok(results) => results,
+                Err(e) => {
+                    eprintln!("Error running benchmarks: {}", e);
+                    std::process::exit(1);
+                }
+            };
+            
+            println!("{:<18}  {:>10}  {:>10}  STATUS", "PATH", "MEDIAN", "BUDGET");
+            for result in &results {
+                println!(
+                    "{:<18}  {:>10}  {:>10}  {}",
+                    result.benchmark.name(), format!("{:.2?}", result.median), format!("{:.2?}", result.benchmark.budget()),
+                    if result.within_budget() { "ok" } else { "over budget" },
+                );
+            }
+            
+            if *assert && results.iter().any(|result| !result.within_budget()) {
+                std::process::exit(1);
+            }
+        },
         Some(Commands::Hash { command, args }) => {
-            // Combine command and args into a single string
-            let full_command = format!("{} {}", command, args.join(" ")).trim().to_string();
+            let full_command = command_line(&mut cache, command, args);
             
             // Generate and display the hash
             let hash = cache.generate_id(&full_command);
@@ -119,6 +927,293 @@ fn main() {
             println!("Use --help for usage information");
         }
     }
+    
+    if !matches!(cli.command, Some(Commands::Sync)) {
+        start_background_sync(&cache);
+    }
+}
+
+/// List the entries about to expire, notify their hooks and refresh those due
+///
+/// # Returns
+///
+/// Whether every entry could be checked, notified and refreshed
+fn check_expiring(cache: &mut CommandCache, within: Duration, min_hits: u64, ttl: Option<Duration>, refresh_all: bool) -> bool {
+    let entries = match cache.expiring_entries(within, min_hits, ttl) {
+        Ok(entries) => entries,
+        Err(e) => {
+            eprintln!("Error: {}", e);
+            return false;
+        }
+    };
+    
+    let mut ok = true;
+    for expiring in entries {
+        let entry = &expiring.entry;
+        let pin = if entry.pinned { ", pinned" } else { "" };
+        let due = if expiring.refresh { ", refresh due" } else { "" };
+        println!(
+            "{} (expires in {}, {} hits{}{})",
+            entry.command, format_duration(&expiring.expires_in), entry.hit_count, pin, due,
+        );
+        
+        if let Err(e) = cache.notify_expiring(&expiring) {
+            eprintln!("   Error notifying: {}", e);
+            ok = false;
+        }
+        
+        if refresh_all || expiring.refresh {
+            if let Err(e) = cache.refresh_entry(&entry.id) {
+                eprintln!("   Error refreshing: {}", e);
+                ok = false;
+            }
+        }
+    }
+    
+    ok
+}
+
+/// Start a detached `cacher sync` if uploads are queued, so they never delay this command
+///
+/// Returns the started process, for callers that outlive it to reap.
+fn start_background_sync(cache: &CommandCache) -> Option<std::process::Child> {
+    if !cache.pending_uploads().is_ok_and(|ids| !ids.is_empty()) {
+        return None;
+    }
+    
+    let started = std::env::current_exe().and_then(|exe| {
+        std::process::Command::new(exe)
+            .arg("sync")
+            .stdin(std::process::Stdio::null())
+            .stdout(std::process::Stdio::null())
+            .stderr(std::process::Stdio::null())
+            .spawn()
+    });
+    
+    match started {
+        Ok(child) => Some(child),
+        Err(e) => {
+            eprintln!("Warning: failed to start uploading to the remote store: {}", e);
+            None
+        }
+    }
+}
+
+/// Print the hint file inputs of a cache key, one kind per line
+fn print_inputs(indent: &str, inputs: &EntryInputs) {
+    if let Some(pattern) = &inputs.pattern {
+        println!("{}Pattern: {}", indent, pattern);
+    }
+    if !inputs.env.is_empty() {
+        println!("{}Environment: {}", indent, inputs.env.join(", "));
+    }
+    if !inputs.dependencies.is_empty() {
+        println!("{}Depends on: {}", indent, inputs.dependencies.join(", "));
+    }
+    if !inputs.tags.is_empty() {
+        println!("{}Tags: {}", indent, inputs.tags.join(", "));
+    }
+}
+
+/// Print entries as a numbered list with their age, size and usage
+fn print_entries(entries: &[CacheEntrySummary]) {
+    for (i, entry) in entries.iter().enumerate() {
+        let age = format_time_ago(&entry.created_at);
+        let last_used = entry.last_accessed
+            .map(|time| format!(", last used {}", format_time_ago(&time)))
+            .unwrap_or_default();
+        println!(
+            "{}. {} ({}, {}, {} hits{})",
+            i + 1, entry.command, age, format_size(entry.size), entry.hit_count, last_used,
+        );
+        println!("   Hash: {}", entry.id);
+        if !entry.meta.is_empty() {
+            println!("   Meta: {}", format_meta(&entry.meta));
+        }
+    }
+}
+
+/// Format key-value pairs attached to an entry as `key=value, key=value`
+fn format_meta(meta: &BTreeMap<String, String>) -> String {
+    meta.iter().map(|(key, value)| format!("{}={}", key, value)).collect::<Vec<_>>().join(", ")
+}
+
+/// Parse a duration given as plain seconds or with a unit suffix (e.g. 90, 30s, 10m, 2h, 1d)
+fn parse_duration(value: &str) -> Result<Duration, String> {
+    if let Ok(seconds) = value.parse::<u64>() {
+        return Ok(Duration::from_secs(seconds));
+    }
+    
+    humantime::parse_duration(value).map_err(|e| e.to_string())
+}
+
+/// Print the first lines of an output, noting how many were left out
+fn print_preview(text: &str, lines: usize) {
+    let preview = pager::preview(text, lines);
+    print!("{}", preview.text);
+    if !preview.text.is_empty() && !preview.text.ends_with('\n') {
+        println!();
+    }
+    if preview.omitted_lines > 0 {
+        println!("... {} more lines", preview.omitted_lines);
+    }
+}
+
+/// Print an output in full, or in the pager
+///
+/// Before dumping an output of hundreds of megabytes into a terminal, warn
+/// and, if someone is there to answer, ask whether to go ahead.
+fn show_output(text: &str, use_pager: bool) {
+    if use_pager {
+        if let Err(e) = pager::page(text) {
+            eprintln!("Error: {}", e);
+            std::process::exit(1);
+        }
+        return;
+    }
+    
+    if text.len() >= pager::LARGE_OUTPUT_BYTES && io::stdout().is_terminal() {
+        eprintln!(
+            "Warning: the output is {}; use --preview N or --pager to view it, or redirect it to a file",
+            format_size(text.len() as u64),
+        );
+        if io::stdin().is_terminal() {
+            eprint!("Print it anyway? [y/N] ");
+            let mut answer = String::new();
+            if io::stdin().read_line(&mut answer).is_err() || !answer.trim().eq_ignore_ascii_case("y") {
+                std::process::exit(1);
+            }
+        }
+    }
+    
+    print!("{}", text);
+}
+
+/// The command line a command is cached under, from the arguments it was given
+///
+/// A lone argument is the command line itself. Several are the program and
+/// its arguments, which are quoted where needed and also handed to the cache
+/// so they run unchanged; commands run through the shell get them joined as is.
+fn command_line(cache: &mut CommandCache, command: &str, args: &[String]) -> String {
+    let argv: Vec<String> = std::iter::once(command.to_string()).chain(args.iter().cloned()).collect();
+    let joined = argv.join(" ");
+    if args.is_empty() || cache.shell_enabled(&joined) {
+        return joined;
+    }
+    
+    cache.set_argv(argv.clone());
+    cacher::process::command_line(&argv)
+}
+
+/// Print the output a killed command produced before it was stopped
+fn print_partial_output(output: &CommandOutput) {
+    if !output.stdout.is_empty() {
+        eprintln!("--- partial stdout ---");
+        eprint!("{}", output.stdout);
+    }
+    if !output.stderr.is_empty() {
+        eprintln!("--- partial stderr ---");
+        eprint!("{}", output.stderr);
+    }
+}
+
+/// Print a stats rollup, nested groups indented under their parent and the total last
+fn print_stats_table(rows: &[StatsRow]) {
+    println!("{:<40}  {:>7}  {:>7}  {:>8}  {:>9}  {:>6}", "GROUP", "ENTRIES", "HITS", "HIT RATE", "SIZE", "SAVED");
+    
+    let print_row = |label: String, stats: &EntryStats| {
+        println!(
+            "{:<40}  {:>7}  {:>7}  {:>7.0}%  {:>9}  {:>6}",
+            label, stats.entries, stats.hits, stats.hit_rate() * 100.0, format_size(stats.size), format_duration(&stats.saved),
+        );
+    };
+    
+    for row in rows {
+        if let Some(group) = row.groups.last() {
+            print_row(format!("{}{}", "  ".repeat(row.groups.len() - 1), group), &row.stats);
+        }
+    }
+    if let Some(total) = rows.iter().find(|row| row.groups.is_empty()) {
+        print_row("Total".to_string(), &total.stats);
+    }
+}
+
+/// Print the hit/miss/duration table shown after `run-all`
+fn print_task_table(results: &[TaskResult]) {
+    println!("{:<6}  {:>8}  COMMAND", "STATUS", "TIME");
+    for result in results {
+        let status = match (&result.result, result.cached) {
+            (Err(_), _) => "failed",
+            (Ok(_), true) => "hit",
+            (Ok(_), false) => "miss",
+        };
+        println!("{:<6}  {:>8}  {}", status, format_duration(&result.duration), result.command);
+    }
+    
+    for result in results {
+        if let Err(e) = &result.result {
+            eprintln!("{}: {}", result.command, e);
+        }
+    }
+    
+    let hits = results.iter().filter(|r| r.success() && r.cached).count();
+    let failures = results.iter().filter(|r| !r.success()).count();
+    println!("{} commands, {} hits, {} misses, {} failed", results.len(), hits, results.len() - hits - failures, failures);
+}
+
+/// Format the summary line printed by `run --summary`
+fn format_summary(decision: &CacheDecision, entry: &EntrySummary) -> String {
+    let run_time = format_duration(&decision.run_time);
+    let outcome = match decision.source {
+        CacheSource::MemoryHit | CacheSource::DiskHit => format!("cache hit, saved ~{}", run_time),
+        CacheSource::Revalidated => format!("cache revalidated, ran in {}, output unchanged", run_time),
+        CacheSource::Executed | CacheSource::Bypassed => format!("cache miss, ran in {}", run_time),
+    };
+    
+    format!("{}, entry {}, {}", outcome, format_size(entry.size), format_expiry(entry.expires_in))
+}
+
+/// Format the time left until an entry expires
+fn format_expiry(expires_in: Option<Duration>) -> String {
+    match expires_in {
+        Some(remaining) if remaining.is_zero() => "expired".to_string(),
+        Some(remaining) => format!("expires in {}", format_duration(&remaining)),
+        None => "never expires".to_string(),
+    }
+}
+
+/// Format a duration in its largest whole unit (e.g. 850ms, 42s, 12m, 3h, 2d)
+fn format_duration(duration: &Duration) -> String {
+    let secs = duration.as_secs();
+    if secs == 0 {
+        format!("{}ms", duration.as_millis())
+    } else if secs < 60 {
+        format!("{}s", secs)
+    } else if secs < 3600 {
+        format!("{}m", secs / 60)
+    } else if secs < 86400 {
+        format!("{}h", secs / 3600)
+    } else {
+        format!("{}d", secs / 86400)
+    }
+}
+
+/// Format a size in bytes with a binary-scaled unit (e.g. 512 B, 3.1 MB)
+fn format_size(bytes: u64) -> String {
+    const UNITS: [&str; 4] = ["KB", "MB", "GB", "TB"];
+    
+    if bytes < 1024 {
+        return format!("{} B", bytes);
+    }
+    
+    let mut size = bytes as f64 / 1024.0;
+    let mut unit = 0;
+    while size >= 1024.0 && unit < UNITS.len() - 1 {
+        size /= 1024.0;
+        unit += 1;
+    }
+    
+    format!("{:.1} {}", size, UNITS[unit])
 }
 
 fn format_time_ago(timestamp: &SystemTime) -> String {
@@ -0,0 +1,335 @@
+use std::borrow::Cow;
+use std::collections::BTreeMap;
+use std::path::Path;
+use std::str::FromStr;
+use std::fs;
+use std::io::{self, Read, Write};
+use std::time::{Duration, SystemTime};
+use flate2::Compression;
+use flate2::read::GzDecoder;
+use flate2::write::GzEncoder;
+use glob::Pattern;
+use serde::{Deserialize, Serialize};
+use crate::chain::ChainPart;
+use crate::crypto;
+
+/// Commands longer than this many bytes are stored cut off in `metadata.json`,
+/// with the full command compressed in [`FULL_COMMAND_FILE`]
+pub const MAX_COMMAND_BYTES: usize = 1024;
+
+/// Name of the file holding the gzipped full command of an entry whose command was cut off
+pub const FULL_COMMAND_FILE: &str = "command.gz";
+
+/// Name of the file in an entry directory logging hits not yet folded into `metadata.json`
+///
+/// Each hit appends the time it was served, so hits never rewrite the
+/// metadata or wait for each other; [`EntryMetadata::read`] adds them in and
+/// [`EntryMetadata::write`] folds them into the file it writes, then clears it.
+pub const HITS_FILE: &str = "hits";
+
+/// Metadata stored alongside each cached entry in `metadata.json`
+#[derive(Debug, Deserialize, Serialize, Clone, PartialEq)]
+pub struct EntryMetadata {
+    /// The command the entry was produced by, cut off after [`MAX_COMMAND_BYTES`]
+    ///
+    /// Use [`EntryMetadata::full_command`] where the whole command matters.
+    pub command: String,
+
+    /// Length in bytes of the full command, if `command` was cut off
+    #[serde(default)]
+    pub command_bytes: Option<u64>,
+
+    /// Identity of the project the entry was cached in; None for entries from older versions
+    #[serde(default)]
+    pub project: Option<String>,
+
+    /// When the entry was created, in seconds since the Unix epoch
+    pub timestamp: u64,
+
+    /// Exit code of the command
+    #[serde(default)]
+    pub exit_code: i32,
+
+    /// How long the command took to run, in milliseconds
+    #[serde(default)]
+    pub duration_ms: u64,
+
+    /// How many times the entry has been served from the cache
+    #[serde(default)]
+    pub hit_count: u64,
+
+    /// When the entry was last served from the cache, in seconds since the Unix epoch
+    #[serde(default)]
+    pub last_accessed: Option<u64>,
+
+    /// TTL in seconds the command set with a directive in its output, overriding the hint file
+    #[serde(default)]
+    pub ttl: Option<u64>,
+
+    /// Pinned entries are served regardless of their TTL and survive `clear --all`
+    #[serde(default)]
+    pub pinned: bool,
+
+    /// Whether the `on_expiring` hook already ran for this entry
+    #[serde(default)]
+    pub expiry_notified: bool,
+
+    /// Whether the entry's output and history are encrypted; None for entries
+    /// from older versions, see [`EntryMetadata::is_encrypted`]
+    #[serde(default)]
+    pub encrypted: Option<bool>,
+
+    /// Key-value pairs attached with `--meta` or the hint file's `meta`,
+    /// e.g. a build number or commit SHA; not part of the key
+    #[serde(default)]
+    pub meta: BTreeMap<String, String>,
+
+    /// The hint file settings the entry's key was derived from
+    #[serde(default)]
+    pub inputs: EntryInputs,
+
+    /// Parts of a chained shell command, with how long each one ran
+    #[serde(default)]
+    pub parts: Vec<ChainPart>,
+
+    /// How many hits from the hit log [`EntryMetadata::read`] counted in
+    #[serde(skip)]
+    logged_hits: usize,
+}
+
+/// What went into an entry's cache key besides the command, as far as the
+/// hint file declares it
+///
+/// Recorded so entries can be found by their inputs with `cacher find`.
+#[derive(Debug, Deserialize, Serialize, Clone, PartialEq, Default)]
+pub struct EntryInputs {
+    /// Pattern of the matching command hint
+    #[serde(default)]
+    pub pattern: Option<String>,
+
+    /// Names of the environment variables included in the key
+    #[serde(default)]
+    pub env: Vec<String>,
+
+    /// Files the entry depends on, as declared and as resolved, relative to the project
+    #[serde(default)]
+    pub dependencies: Vec<String>,
+
+    /// Tags of the matching command hint
+    #[serde(default)]
+    pub tags: Vec<String>,
+}
+
+impl EntryMetadata {
+    /// Create metadata for a command executed at `timestamp`
+    pub fn new(command: &str, timestamp: SystemTime, exit_code: i32, duration: Duration) -> Self {
+        EntryMetadata {
+            command: display_command(command).into_owned(),
+            command_bytes: (command.len() > MAX_COMMAND_BYTES).then_some(command.len() as u64),
+            project: None,
+            timestamp: timestamp
+                .duration_since(SystemTime::UNIX_EPOCH)
+                .unwrap_or_default()
+                .as_secs(),
+            exit_code,
+            duration_ms: duration.as_millis() as u64,
+            hit_count: 0,
+            last_accessed: None,
+            ttl: None,
+            pinned: false,
+            expiry_notified: false,
+            encrypted: None,
+            meta: BTreeMap::new(),
+            inputs: EntryInputs::default(),
+            parts: Vec::new(),
+            logged_hits: 0,
+        }
+    }
+
+    /// The command the entry was produced by, read back from the entry's
+    /// directory if it was too long to store in the metadata
+    pub fn full_command(&self, entry_dir: &Path) -> io::Result<String> {
+        if self.command_bytes.is_none() {
+            return Ok(self.command.clone());
+        }
+
+        let mut command = String::new();
+        GzDecoder::new(fs::File::open(entry_dir.join(FULL_COMMAND_FILE))?).read_to_string(&mut command)?;
+        Ok(command)
+    }
+
+    /// How long the command took to run
+    pub fn duration(&self) -> Duration {
+        Duration::from_millis(self.duration_ms)
+    }
+
+    /// When the entry was created
+    pub fn created_at(&self) -> SystemTime {
+        SystemTime::UNIX_EPOCH + Duration::from_secs(self.timestamp)
+    }
+
+    /// When the entry was last served from the cache, if it ever was
+    pub fn last_accessed(&self) -> Option<SystemTime> {
+        self.last_accessed.map(|secs| SystemTime::UNIX_EPOCH + Duration::from_secs(secs))
+    }
+
+    /// Whether `contents`, one of the entry's files, is encrypted
+    ///
+    /// Goes by [`EntryMetadata::encrypted`], so plaintext output that happens
+    /// to start like an encrypted file is still read as plaintext. Entries
+    /// from older versions didn't record it and are recognized by the prefix
+    /// encrypted files start with.
+    pub fn is_encrypted(&self, contents: &[u8]) -> bool {
+        self.encrypted.unwrap_or_else(|| crypto::is_encrypted(contents))
+    }
+
+    /// Count in hits logged at `hits`, in seconds since the Unix epoch
+    fn add_hits(&mut self, hits: &[u64]) {
+        self.hit_count += hits.len() as u64;
+        self.last_accessed = self.last_accessed.max(hits.iter().max().copied());
+    }
+
+    /// Read metadata from a file
+    ///
+    /// Hits logged in [`HITS_FILE`] next to it are counted in.
+    ///
+    /// # Returns
+    ///
+    /// A Result containing the metadata, or None if the file doesn't exist or
+    /// can't be parsed
+    pub fn read(path: &Path) -> io::Result<Option<Self>> {
+        if !path.exists() {
+            return Ok(None);
+        }
+
+        let content = fs::read_to_string(path)?;
+        let mut metadata: Self = match serde_json::from_str(&content) {
+            Ok(metadata) => metadata,
+            Err(_) => return Ok(None),
+        };
+        if let Some(hits) = path.parent().and_then(|dir| fs::read_to_string(dir.join(HITS_FILE)).ok()) {
+            let hits = parse_hits(&hits);
+            metadata.add_hits(&hits);
+            metadata.logged_hits = hits.len();
+        }
+        Ok(Some(metadata))
+    }
+
+    /// Write metadata to a file
+    ///
+    /// The file is replaced atomically, so readers never see a partial write.
+    /// Hits logged in [`HITS_FILE`] since this metadata was read are added to
+    /// what is written, and the log is cleared, while holding a lock that
+    /// keeps new hits from being logged in between.
+    pub fn write(&self, path: &Path) -> io::Result<()> {
+        let dir = path.parent().unwrap_or_else(|| Path::new("."));
+        let hits_file = match fs::OpenOptions::new().read(true).write(true).open(dir.join(HITS_FILE)) {
+            Ok(file) => Some(file),
+            Err(e) if e.kind() == io::ErrorKind::NotFound => None,
+            Err(e) => return Err(e),
+        };
+
+        let mut metadata = Cow::Borrowed(self);
+        if let Some(mut file) = hits_file.as_ref() {
+            file.lock()?;
+            let mut hits = String::new();
+            file.read_to_string(&mut hits)?;
+            let hits = parse_hits(&hits);
+            metadata.to_mut().add_hits(&hits[self.logged_hits.min(hits.len())..]);
+        }
+
+        let content = serde_json::to_string(&metadata)
+            .map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))?;
+        let mut file = tempfile::NamedTempFile::new_in(dir)?;
+        file.write_all(content.as_bytes())?;
+        file.persist(path).map_err(|e| e.error)?;
+
+        // Dropping the file releases the lock
+        match hits_file {
+            Some(file) => file.set_len(0),
+            None => Ok(()),
+        }
+    }
+}
+
+/// A command as stored in metadata and shown in listings
+///
+/// Commands longer than [`MAX_COMMAND_BYTES`], e.g. ones with a large inline
+/// JSON argument, are cut off at a character boundary with a note of how
+/// much was left out.
+pub fn display_command(command: &str) -> Cow<'_, str> {
+    if command.len() <= MAX_COMMAND_BYTES {
+        return Cow::Borrowed(command);
+    }
+
+    let mut end = MAX_COMMAND_BYTES;
+    while !command.is_char_boundary(end) {
+        end -= 1;
+    }
+    Cow::Owned(format!("{}… [+{} bytes]", &command[..end], command.len() - end))
+}
+
+/// Log a hit on the entry in `entry_dir`, served at `time`
+///
+/// A single short append under a shared lock, so concurrent hits from several
+/// processes neither wait for nor lose each other; they only wait while
+/// [`EntryMetadata::write`] folds the log in. Entries that don't exist aren't
+/// created.
+pub fn record_hit(entry_dir: &Path, time: SystemTime) -> io::Result<()> {
+    if !entry_dir.is_dir() {
+        return Err(io::Error::new(io::ErrorKind::NotFound, "No such entry"));
+    }
+    let secs = time.duration_since(SystemTime::UNIX_EPOCH).unwrap_or_default().as_secs();
+    let mut file = fs::OpenOptions::new().create(true).append(true).open(entry_dir.join(HITS_FILE))?;
+    file.lock_shared()?;
+    file.write_all(format!("{}\n", secs).as_bytes())
+}
+
+/// The times of the hits in a hit log
+fn parse_hits(log: &str) -> Vec<u64> {
+    log.lines().filter_map(|line| line.parse().ok()).collect()
+}
+
+/// Store a command too long for the metadata, gzipped, in an entry's directory
+pub fn write_full_command(entry_dir: &Path, command: &str) -> io::Result<()> {
+    let mut encoder = GzEncoder::new(fs::File::create(entry_dir.join(FULL_COMMAND_FILE))?, Compression::default());
+    encoder.write_all(command.as_bytes())?;
+    encoder.finish()?;
+    Ok(())
+}
+
+/// Split a `key=value` pair as given to `--meta`
+pub fn parse_meta_pair(pair: &str) -> Result<(String, String), String> {
+    match pair.split_once('=') {
+        Some((key, value)) if !key.trim().is_empty() => Ok((key.trim().to_string(), value.to_string())),
+        _ => Err(format!("Invalid metadata {:?}, expected key=value", pair)),
+    }
+}
+
+/// A condition on an entry's `meta`, as given to `--meta` of `cacher list` and `cacher find`
+///
+/// Written `key=value`, where the value may be a glob pattern such as
+/// `ticket=PROJ-*`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct MetaFilter {
+    pub key: String,
+    pub value: String,
+}
+
+impl MetaFilter {
+    pub fn matches(&self, meta: &BTreeMap<String, String>) -> bool {
+        meta.get(&self.key).is_some_and(|value| {
+            *value == self.value || Pattern::new(&self.value).is_ok_and(|pattern| pattern.matches(value))
+        })
+    }
+}
+
+impl FromStr for MetaFilter {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let (key, value) = parse_meta_pair(s)?;
+        Pattern::new(&value).map_err(|e| format!("Invalid pattern {}: {}", value, e))?;
+        Ok(MetaFilter { key, value })
+    }
+}
@@ -0,0 +1,65 @@
+use regex::Regex;
+use serde::{Deserialize, Serialize};
+use anyhow::{Result, Context};
+
+/// A regex substitution applied to command output before it is cached
+///
+/// The replacement may refer to capture groups using `$1` or `${name}`.
+#[derive(Debug, Deserialize, Serialize, Clone, PartialEq)]
+pub struct NormalizeRule {
+    /// Regex pattern to search for
+    pub pattern: String,
+
+    /// Text to replace each match with
+    #[serde(default)]
+    pub replacement: String,
+}
+
+/// A compiled set of normalization rules
+///
+/// Rules are applied in order, each one to the output of the previous rule.
+#[derive(Debug, Clone)]
+pub struct Normalizer {
+    rules: Vec<(Regex, String)>,
+}
+
+impl Normalizer {
+    /// Compile a list of normalization rules
+    ///
+    /// # Arguments
+    ///
+    /// * `rules` - The rules to compile
+    ///
+    /// # Returns
+    ///
+    /// A Result containing the Normalizer, or an error if a pattern is not a valid regex
+    pub fn new(rules: &[NormalizeRule]) -> Result<Self> {
+        let mut compiled = Vec::with_capacity(rules.len());
+
+        for rule in rules {
+            let regex = Regex::new(&rule.pattern)
+                .with_context(|| format!("Invalid normalize pattern: {}", rule.pattern))?;
+            compiled.push((regex, rule.replacement.clone()));
+        }
+
+        Ok(Normalizer { rules: compiled })
+    }
+
+    /// Apply every rule to the output
+    pub fn apply(&self, output: &str) -> String {
+        let mut normalized = output.to_string();
+
+        for (regex, replacement) in &self.rules {
+            normalized = regex.replace_all(&normalized, replacement.as_str()).into_owned();
+        }
+
+        normalized
+    }
+}
+
+/// Normalize output with a list of rules
+///
+/// Convenience wrapper around [`Normalizer`] for one-off use.
+pub fn normalize_output(output: &str, rules: &[NormalizeRule]) -> Result<String> {
+    Ok(Normalizer::new(rules)?.apply(output))
+}
@@ -0,0 +1,86 @@
+use std::env;
+use std::io::{self, Error, ErrorKind, Write};
+use std::process::{Command, Stdio};
+
+/// Outputs at least this large get a warning before they're printed to a terminal
+pub const LARGE_OUTPUT_BYTES: usize = 100 * 1024 * 1024;
+
+/// Pager used when `PAGER` isn't set
+#[cfg(not(windows))]
+const DEFAULT_PAGER: &str = "less -R";
+#[cfg(windows)]
+const DEFAULT_PAGER: &str = "more";
+
+/// The first lines of an output, as printed by `--preview`
+#[derive(Debug, Clone, PartialEq)]
+pub struct Preview<'a> {
+    pub text: &'a str,
+
+    /// How many lines were left out
+    pub omitted_lines: usize,
+}
+
+/// Cut an output down to its first `lines` lines
+pub fn preview(text: &str, lines: usize) -> Preview<'_> {
+    let end = match lines {
+        0 => 0,
+        _ => text.match_indices('\n').nth(lines - 1).map_or(text.len(), |(i, _)| i + 1),
+    };
+
+    let rest = &text[end..];
+    Preview {
+        text: &text[..end],
+        omitted_lines: rest.lines().count(),
+    }
+}
+
+/// The pager to use: `PAGER` if it is set and not empty, otherwise `less -R` (`more` on Windows)
+pub fn pager_command() -> String {
+    env::var("PAGER")
+        .ok()
+        .filter(|pager| !pager.trim().is_empty())
+        .unwrap_or_else(|| DEFAULT_PAGER.to_string())
+}
+
+/// Show text in the user's pager, waiting for it to be closed
+pub fn page(text: &str) -> io::Result<()> {
+    page_with(&pager_command(), text)
+}
+
+/// Show text in a pager, given as a shell command line like `less -R`
+///
+/// The pager is run through the shell, like git does, so it may have
+/// arguments. Quitting the pager before reading everything isn't an error.
+pub fn page_with(pager: &str, text: &str) -> io::Result<()> {
+    #[cfg(not(windows))]
+    let mut command = {
+        let mut command = Command::new("sh");
+        command.arg("-c").arg(pager);
+        command
+    };
+    #[cfg(windows)]
+    let mut command = {
+        let mut command = Command::new("cmd");
+        command.arg("/C").arg(pager);
+        command
+    };
+
+    let mut child = command
+        .stdin(Stdio::piped())
+        .spawn()
+        .map_err(|e| Error::new(e.kind(), format!("Failed to start pager {}: {}", pager, e)))?;
+
+    if let Some(mut stdin) = child.stdin.take() {
+        match stdin.write_all(text.as_bytes()) {
+            Ok(()) => {},
+            Err(e) if e.kind() == ErrorKind::BrokenPipe => {},
+            Err(e) => return Err(e),
+        }
+    }
+
+    let status = child.wait()?;
+    if !status.success() {
+        return Err(Error::other(format!("Pager {} exited with {}", pager, status)));
+    }
+    Ok(())
+}
@@ -0,0 +1,56 @@
+use std::collections::HashSet;
+use crate::hint_file::CommandHint;
+
+/// Names PowerShell is started by, on Windows and elsewhere
+pub const PROGRAMS: &[&str] = &["pwsh", "pwsh.exe", "powershell", "powershell.exe"];
+
+/// Environment variables that decide which modules and commands PowerShell finds
+pub const POWERSHELL_ENV_VARS: &[&str] = &["PSModulePath", "PATH"];
+
+/// Cmdlets cached by the preset, with their TTL in seconds
+///
+/// All of them only read state, and are slow enough to be worth caching:
+/// they scan every module on `PSModulePath`, query WMI or go to the network.
+const CMDLETS: &[(&str, u64)] = &[
+    ("Get-Module -ListAvailable", 3600),
+    ("Get-InstalledModule", 3600),
+    ("Get-Command", 3600),
+    ("Get-Help", 86400),
+    ("Get-ComputerInfo", 3600),
+    ("Find-Module", 3600),
+    ("$PSVersionTable", 86400),
+];
+
+/// Command hints added by `presets: [powershell]`
+///
+/// Matches the cmdlets run through `pwsh` or Windows PowerShell with
+/// `-Command` (or any abbreviation of it, like `-c`), after any other options
+/// such as `-NoProfile`. PowerShell ignores case, so the patterns do too.
+pub fn preset_hints() -> Vec<CommandHint> {
+    let mut hints = Vec::new();
+    for program in PROGRAMS {
+        for (cmdlet, ttl) in CMDLETS {
+            hints.push(CommandHint {
+                pattern: format!("{} *-{}* {}*", program, any_case("c"), any_case(cmdlet)),
+                ttl: Some(*ttl),
+                include_env: POWERSHELL_ENV_VARS.iter().map(|v| v.to_string()).collect::<HashSet<_>>(),
+                ..CommandHint::default()
+            });
+        }
+    }
+
+    hints
+}
+
+/// Turn text into a glob pattern matching it in any case, e.g. `Get` into `[gG][eE][tT]`
+fn any_case(text: &str) -> String {
+    text.chars()
+        .map(|c| {
+            if c.is_ascii_alphabetic() {
+                format!("[{}{}]", c.to_ascii_lowercase(), c.to_ascii_uppercase())
+            } else {
+                c.to_string()
+            }
+        })
+        .collect()
+}
@@ -0,0 +1,262 @@
+use std::io::{self, Read};
+use std::process::{Child, Command, ExitStatus, Stdio};
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+use std::sync::{Arc, Mutex};
+use std::thread;
+use std::time::{Duration, Instant};
+
+/// How often a running child is polled while waiting with a timeout
+const POLL_INTERVAL: Duration = Duration::from_millis(10);
+
+/// How long to keep reading output after the child exits
+///
+/// Background processes started by the child inherit its pipes and can keep
+/// them open indefinitely, so we stop waiting for EOF after this grace period.
+const DRAIN_GRACE_PERIOD: Duration = Duration::from_millis(200);
+
+/// Output captured from a child process that may have been killed
+#[derive(Debug)]
+pub struct ProcessOutput {
+    pub status: Option<ExitStatus>,
+    pub stdout: Vec<u8>,
+    pub stderr: Vec<u8>,
+    pub duration: Duration,
+    /// Whether the process was killed for running past its timeout
+    pub timed_out: bool,
+    /// Whether the process was killed for writing more than the output limit
+    pub output_limit_exceeded: bool,
+}
+
+/// Limits enforced while a child process runs
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct Limits {
+    /// Kill the process once it has run this long
+    pub timeout: Option<Duration>,
+    /// Kill the process once stdout and stderr together exceed this many bytes
+    pub max_output_bytes: Option<u64>,
+}
+
+/// Build a command that runs `script` through the platform shell
+pub fn shell_command(script: &str) -> Command {
+    if cfg!(windows) {
+        let mut command = Command::new("cmd");
+        command.arg("/C").arg(script);
+        command
+    } else {
+        let mut command = Command::new("sh");
+        command.arg("-c").arg(script);
+        command
+    }
+}
+
+/// Join a program and its arguments into one command line
+///
+/// Arguments that are empty or contain whitespace, quotes or backslashes are
+/// single-quoted, so [`split_command_line`] gives back exactly `argv`.
+pub fn command_line(argv: &[String]) -> String {
+    argv.iter()
+        .map(|arg| {
+            let needs_quotes = arg.is_empty()
+                || arg.chars().any(|c| c.is_whitespace() || matches!(c, '\'' | '"' | '\\'));
+            if needs_quotes {
+                format!("'{}'", arg.replace('\'', "'\\''"))
+            } else {
+                arg.clone()
+            }
+        })
+        .collect::<Vec<_>>()
+        .join(" ")
+}
+
+/// Split a command line into a program and its arguments
+///
+/// Arguments are separated by whitespace. As in a POSIX shell, single quotes
+/// keep everything up to the closing quote, double quotes keep everything
+/// but a backslash-escaped `"` or `\`, and outside quotes a backslash escapes
+/// a following quote, backslash or space. Other backslashes are kept, so
+/// Windows paths pass through unchanged.
+pub fn split_command_line(command: &str) -> Vec<String> {
+    let mut args = Vec::new();
+    let mut current: Option<String> = None;
+    let mut chars = command.chars().peekable();
+
+    while let Some(c) = chars.next() {
+        match c {
+            c if c.is_whitespace() => {
+                if let Some(arg) = current.take() {
+                    args.push(arg);
+                }
+            },
+            '\'' => {
+                let arg = current.get_or_insert_with(String::new);
+                for c in chars.by_ref() {
+                    if c == '\'' {
+                        break;
+                    }
+                    arg.push(c);
+                }
+            },
+            '"' => {
+                let arg = current.get_or_insert_with(String::new);
+                while let Some(c) = chars.next() {
+                    match c {
+                        '"' => break,
+                        '\\' if matches!(chars.peek(), Some('"' | '\\')) => arg.push(chars.next().unwrap_or(c)),
+                        c => arg.push(c),
+                    }
+                }
+            },
+            '\\' => {
+                let arg = current.get_or_insert_with(String::new);
+                match chars.peek() {
+                    Some(&next) if next.is_whitespace() || matches!(next, '\'' | '"' | '\\') => {
+                        arg.push(next);
+                        chars.next();
+                    },
+                    _ => arg.push(c),
+                }
+            },
+            c => current.get_or_insert_with(String::new).push(c),
+        }
+    }
+
+    args.extend(current);
+    args
+}
+
+/// Run a command to completion, killing it if it runs longer than `timeout`
+///
+/// stdout and stderr are captured on background threads so a chatty child
+/// can't block on a full pipe while we wait for it. Output produced before a
+/// timeout is kept.
+pub fn run_with_timeout(command: &mut Command, timeout: Option<Duration>) -> io::Result<ProcessOutput> {
+    run_with_limits(command, Limits { timeout, max_output_bytes: None })
+}
+
+/// Run a command to completion, killing it if it exceeds any of `limits`
+///
+/// Output produced before the process was killed is kept, truncated to the
+/// output limit.
+pub fn run_with_limits(command: &mut Command, limits: Limits) -> io::Result<ProcessOutput> {
+    let started = Instant::now();
+    let mut child = command
+        .stdin(Stdio::null())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()?;
+
+    let budget = Arc::new(OutputBudget::new(limits.max_output_bytes));
+    let stdout = Capture::start(child.stdout.take(), Arc::clone(&budget));
+    let stderr = Capture::start(child.stderr.take(), Arc::clone(&budget));
+
+    let deadline = limits.timeout.map(|t| started + t);
+    let (status, timed_out) = wait_with_deadline(&mut child, deadline, &budget)?;
+
+    let drain_deadline = Instant::now() + DRAIN_GRACE_PERIOD;
+    Ok(ProcessOutput {
+        status,
+        stdout: stdout.finish(drain_deadline),
+        stderr: stderr.finish(drain_deadline),
+        duration: started.elapsed(),
+        timed_out,
+        output_limit_exceeded: budget.exceeded.load(Ordering::SeqCst),
+    })
+}
+
+/// Wait for a child to exit, killing it once `deadline` has passed or it
+/// has used up its output budget
+fn wait_with_deadline(child: &mut Child, deadline: Option<Instant>, budget: &OutputBudget) -> io::Result<(Option<ExitStatus>, bool)> {
+    if deadline.is_none() && budget.limit.is_none() {
+        return Ok((Some(child.wait()?), false));
+    }
+
+    loop {
+        if let Some(status) = child.try_wait()? {
+            return Ok((Some(status), false));
+        }
+
+        let timed_out = deadline.is_some_and(|deadline| Instant::now() >= deadline);
+        if timed_out || budget.exceeded.load(Ordering::SeqCst) {
+            // The child may exit between the check and the kill
+            let _ = child.kill();
+            let _ = child.wait();
+            return Ok((None, timed_out));
+        }
+
+        thread::sleep(POLL_INTERVAL);
+    }
+}
+
+/// Output bytes shared between the stdout and stderr readers
+struct OutputBudget {
+    limit: Option<u64>,
+    used: AtomicU64,
+    exceeded: AtomicBool,
+}
+
+impl OutputBudget {
+    fn new(limit: Option<u64>) -> Self {
+        OutputBudget {
+            limit,
+            used: AtomicU64::new(0),
+            exceeded: AtomicBool::new(false),
+        }
+    }
+
+    /// Claim up to `len` bytes, returning how many may be kept
+    fn claim(&self, len: usize) -> usize {
+        let limit = match self.limit {
+            Some(limit) => limit,
+            None => return len,
+        };
+
+        let used = self.used.fetch_add(len as u64, Ordering::SeqCst);
+        let remaining = limit.saturating_sub(used);
+        if (len as u64) > remaining {
+            self.exceeded.store(true, Ordering::SeqCst);
+        }
+        len.min(remaining as usize)
+    }
+}
+
+/// A pipe being read to the end on a background thread
+struct Capture {
+    buffer: Arc<Mutex<Vec<u8>>>,
+    handle: thread::JoinHandle<()>,
+}
+
+impl Capture {
+    fn start<R: Read + Send + 'static>(pipe: Option<R>, budget: Arc<OutputBudget>) -> Self {
+        let buffer = Arc::new(Mutex::new(Vec::new()));
+        let thread_buffer = Arc::clone(&buffer);
+
+        let handle = thread::spawn(move || {
+            let mut pipe = match pipe {
+                Some(pipe) => pipe,
+                None => return,
+            };
+            let mut chunk = [0u8; 8192];
+            loop {
+                match pipe.read(&mut chunk) {
+                    Ok(0) | Err(_) => break,
+                    Ok(n) => {
+                        let keep = budget.claim(n);
+                        thread_buffer.lock().unwrap().extend_from_slice(&chunk[..keep]);
+                    },
+                }
+            }
+        });
+
+        Capture { buffer, handle }
+    }
+
+    /// Wait until EOF or `deadline`, whichever comes first, and take what was read
+    fn finish(self, deadline: Instant) -> Vec<u8> {
+        while !self.handle.is_finished() && Instant::now() < deadline {
+            thread::sleep(POLL_INTERVAL);
+        }
+
+        let buffer = self.buffer.lock().unwrap();
+        buffer.clone()
+    }
+}
@@ -0,0 +1,120 @@
+use std::path::{Path, PathBuf};
+use std::process::Command;
+use crate::hint_file::HINT_FILE_NAMES;
+
+/// Find the root directory of the project containing `dir`
+///
+/// Uses the git work tree root if `dir` is inside a git checkout or worktree,
+/// otherwise the nearest directory containing a hint file, otherwise `dir`.
+pub fn project_root(dir: &Path) -> PathBuf {
+    if let Some(root) = git_output(dir, &["rev-parse", "--show-toplevel"]) {
+        return PathBuf::from(root);
+    }
+
+    let mut current_dir = Some(dir);
+    while let Some(candidate) = current_dir {
+        if HINT_FILE_NAMES.iter().any(|name| candidate.join(name).exists()) {
+            return candidate.to_path_buf();
+        }
+        current_dir = candidate.parent();
+    }
+
+    dir.to_path_buf()
+}
+
+/// Get the URL of the `origin` remote for the git repository containing `dir`
+pub fn remote_url(dir: &Path) -> Option<String> {
+    git_output(dir, &["config", "--get", "remote.origin.url"])
+}
+
+/// Normalize a git remote URL so different spellings of the same repository match
+///
+/// `git@github.com:owner/repo.git`, `ssh://git@github.com/owner/repo` and
+/// `https://github.com/owner/repo.git` all normalize to `github.com/owner/repo`.
+pub fn normalize_remote_url(url: &str) -> String {
+    let mut url = url.trim();
+
+    // Drop the scheme, if any
+    let has_scheme = match url.find("://") {
+        Some(index) => {
+            url = &url[index + 3..];
+            true
+        }
+        None => false,
+    };
+
+    // Drop credentials or the ssh user
+    if let Some(index) = url.find('@') {
+        if url[..index].find('/').is_none() {
+            url = &url[index + 1..];
+        }
+    }
+
+    let url = url.trim_end_matches('/');
+    let url = url.strip_suffix(".git").unwrap_or(url);
+
+    // URLs separate host and path with a slash, scp-like syntax with a colon
+    let separator: &[char] = if has_scheme { &['/'] } else { &['/', ':'] };
+    let (host, path) = match url.find(separator) {
+        Some(index) => (&url[..index], url[index + 1..].trim_start_matches('/')),
+        None => (url, ""),
+    };
+
+    // Ignore an explicit port, it doesn't change which repository this is
+    let host = match host.rsplit_once(':') {
+        Some((name, port)) if port.chars().all(|c| c.is_ascii_digit()) => name,
+        _ => host,
+    };
+
+    if path.is_empty() {
+        host.to_lowercase()
+    } else {
+        format!("{}/{}", host.to_lowercase(), path)
+    }
+}
+
+/// Derive the identity used to namespace cache entries for a directory
+///
+/// When `share_across_checkouts` is true and the project has an `origin`
+/// remote, the identity is the normalized remote URL, so clones and worktrees
+/// of the same repository share entries. Otherwise it is the absolute path of
+/// the project root. Either way the directory's path relative to the project
+/// root is included, since commands often depend on where they are run.
+pub fn project_identity(dir: &Path, share_across_checkouts: bool) -> String {
+    // git reports the root with symlinks resolved, so compare like with like
+    let dir = dir.canonicalize().unwrap_or_else(|_| dir.to_path_buf());
+    let dir = dir.as_path();
+    let root = project_root(dir);
+    let relative_dir = dir
+        .strip_prefix(&root)
+        .map(|p| p.to_string_lossy().replace('\\', "/"))
+        .unwrap_or_default();
+
+    let namespace = match remote_url(dir).filter(|_| share_across_checkouts) {
+        Some(url) => format!("git:{}", normalize_remote_url(&url)),
+        None => format!("path:{}", root.display()),
+    };
+
+    format!("{}:{}", namespace, relative_dir)
+}
+
+/// Run a git command in `dir` and return its trimmed stdout if it succeeded
+fn git_output(dir: &Path, args: &[&str]) -> Option<String> {
+    let output = Command::new("git")
+        .arg("-C")
+        .arg(dir)
+        .args(args)
+        .output()
+        .ok()?;
+
+    if !output.status.success() {
+        return None;
+    }
+
+    let stdout = String::from_utf8_lossy(&output.stdout).trim().to_string();
+    if stdout.is_empty() {
+        None
+    } else {
+        Some(stdout)
+    }
+}
@@ -0,0 +1,262 @@
+use std::fs;
+use std::io::{self, Write};
+use std::path::{Path, PathBuf};
+use serde::{Deserialize, Serialize};
+use crate::metadata::EntryMetadata;
+use crate::{read_complete_output, replace_dir, STAGING_PREFIX};
+
+/// When entries are copied to the remote store
+#[derive(Debug, Deserialize, Serialize, Clone, Copy, PartialEq, Default)]
+#[serde(rename_all = "kebab-case")]
+pub enum WritePolicy {
+    /// Upload before the command's result is returned
+    #[default]
+    Through,
+    /// Queue the upload, so a later `cacher sync` does it without delaying the result
+    Back,
+    /// Never upload; the entry stays in the local cache
+    LocalOnly,
+}
+
+/// The `remote:` section of a hint file
+#[derive(Debug, Deserialize, Serialize, Clone, PartialEq)]
+pub struct RemoteSettings {
+    /// Directory of the shared store, e.g. a network mount; relative paths are
+    /// relative to the hint file's directory
+    pub path: String,
+
+    /// Default write policy, overridable per command
+    #[serde(default)]
+    pub write: WritePolicy,
+}
+
+/// A cache directory shared between machines, layered behind the local cache
+///
+/// Entries are stored in the same layout as the local cache, and copied in
+/// both directions the same way local entries are written: into a staging
+/// directory first, then renamed into place.
+///
+/// Encrypted entries are never shared: their keys are derived from a salt
+/// kept in each local cache directory or held in each machine's keychain,
+/// so no other machine could decrypt them.
+#[derive(Debug, Clone)]
+pub struct RemoteStore {
+    root: PathBuf,
+}
+
+impl RemoteStore {
+    pub fn new(root: PathBuf) -> Self {
+        RemoteStore { root }
+    }
+
+    /// The directory the store lives in
+    pub fn root(&self) -> &Path {
+        &self.root
+    }
+
+    /// Copy a local entry, including its artifacts, to the store
+    pub fn upload(&self, local_entry_dir: &Path, id: &str) -> io::Result<()> {
+        copy_entry(local_entry_dir, &self.root, id)
+    }
+
+    /// Copy an entry from the store into `local_cache_dir`
+    ///
+    /// # Returns
+    ///
+    /// Whether the store had a complete, unencrypted entry for `id`
+    pub fn download(&self, id: &str, local_cache_dir: &Path) -> io::Result<bool> {
+        let remote_entry_dir = self.root.join(id);
+        let (stdout, stderr) = match read_complete_output(&remote_entry_dir)? {
+            Some(output) => output,
+            None => return Ok(false),
+        };
+        match EntryMetadata::read(&remote_entry_dir.join("metadata.json"))? {
+            Some(metadata) if !metadata.is_encrypted(&stdout) && !metadata.is_encrypted(&stderr) => {},
+            _ => return Ok(false),
+        }
+
+        copy_entry(&remote_entry_dir, local_cache_dir, id)?;
+        Ok(true)
+    }
+
+    /// IDs of the entries in the store, with their directories
+    pub fn entries(&self) -> io::Result<Vec<(String, PathBuf)>> {
+        let mut entries = Vec::new();
+
+        let dir = match fs::read_dir(&self.root) {
+            Ok(dir) => dir,
+            Err(e) if e.kind() == io::ErrorKind::NotFound => return Ok(entries),
+            Err(e) => return Err(e),
+        };
+
+        for entry in dir {
+            let entry = entry?;
+            let name = entry.file_name().to_string_lossy().into_owned();
+            // Skip entries still being written
+            if entry.file_type()?.is_dir() && !name.starts_with('.') {
+                entries.push((name, entry.path()));
+            }
+        }
+
+        Ok(entries)
+    }
+
+    /// Delete the entries stored under `ids`, all or nothing
+    ///
+    /// As in the local cache, the entries are first moved into a staging
+    /// directory; if any of them can't be moved, the ones already moved are
+    /// put back.
+    ///
+    /// # Returns
+    ///
+    /// The IDs the store had an entry for
+    pub fn remove(&self, ids: &[&str]) -> io::Result<Vec<String>> {
+        let mut removed = Vec::new();
+        if !self.root.exists() {
+            return Ok(removed);
+        }
+
+        let staging = tempfile::Builder::new().prefix(STAGING_PREFIX).tempdir_in(&self.root)?;
+        for id in ids {
+            match fs::rename(self.root.join(id), staging.path().join(id)) {
+                Ok(()) => removed.push(id.to_string()),
+                Err(e) if e.kind() == io::ErrorKind::NotFound => {},
+                Err(e) => {
+                    for id in removed.iter().rev() {
+                        let _ = fs::rename(staging.path().join(id), self.root.join(id));
+                    }
+                    return Err(io::Error::new(e.kind(), format!("Failed to remove {}: {}", id, e)));
+                }
+            }
+        }
+
+        Ok(removed)
+    }
+}
+
+/// Entries waiting to be uploaded by `write: back` commands
+///
+/// Kept on disk, one file per entry holding the remote store it goes to, so
+/// queued uploads survive the process that queued them.
+#[derive(Debug, Clone)]
+pub struct UploadQueue {
+    dir: PathBuf,
+}
+
+/// Outcome of draining the upload queue
+#[derive(Debug, Default)]
+pub struct SyncReport {
+    /// Number of entries uploaded
+    pub uploaded: usize,
+    /// Entries that failed to upload and remain queued, with the reason
+    pub failed: Vec<(String, io::Error)>,
+}
+
+impl UploadQueue {
+    /// The queue kept in a cache directory
+    pub fn new(cache_dir: &Path) -> Self {
+        UploadQueue { dir: cache_dir.join(".upload-queue") }
+    }
+
+    /// Queue the entry stored under `id` for upload to `remote`
+    pub fn push(&self, id: &str, remote: &RemoteStore) -> io::Result<()> {
+        fs::create_dir_all(&self.dir)?;
+        let mut file = tempfile::NamedTempFile::new_in(&self.dir)?;
+        file.write_all(remote.root().to_string_lossy().as_bytes())?;
+        file.persist(self.dir.join(id)).map_err(|e| e.error)?;
+        Ok(())
+    }
+
+    /// IDs of the entries waiting to be uploaded
+    pub fn pending(&self) -> io::Result<Vec<String>> {
+        let mut ids = Vec::new();
+
+        let entries = match fs::read_dir(&self.dir) {
+            Ok(entries) => entries,
+            Err(e) if e.kind() == io::ErrorKind::NotFound => return Ok(ids),
+            Err(e) => return Err(e),
+        };
+
+        for entry in entries {
+            let name = entry?.file_name().to_string_lossy().into_owned();
+            // Skip queue files still being written
+            if !name.starts_with('.') {
+                ids.push(name);
+            }
+        }
+
+        ids.sort();
+        Ok(ids)
+    }
+
+    /// Upload every queued entry from `cache_dir`
+    ///
+    /// Entries that no longer exist locally are dropped from the queue; ones
+    /// that fail to upload stay queued for the next attempt.
+    pub fn drain(&self, cache_dir: &Path) -> io::Result<SyncReport> {
+        let mut report = SyncReport::default();
+
+        for id in self.pending()? {
+            let item = self.dir.join(&id);
+            let root = match fs::read_to_string(&item) {
+                Ok(root) => PathBuf::from(root),
+                // Another sync got to it first
+                Err(e) if e.kind() == io::ErrorKind::NotFound => continue,
+                Err(e) => return Err(e),
+            };
+
+            let entry_dir = cache_dir.join(&id);
+            let result = if read_complete_output(&entry_dir)?.is_some() {
+                RemoteStore::new(root).upload(&entry_dir, &id).map(|_| true)
+            } else {
+                Ok(false)
+            };
+
+            match result {
+                Ok(uploaded) => {
+                    match fs::remove_file(&item) {
+                        Err(e) if e.kind() != io::ErrorKind::NotFound => return Err(e),
+                        _ => {}
+                    }
+                    if uploaded {
+                        report.uploaded += 1;
+                    }
+                },
+                Err(e) => report.failed.push((id, e)),
+            }
+        }
+
+        Ok(report)
+    }
+}
+
+/// Copy an entry directory into `root` as `root/id`, replacing what is there
+fn copy_entry(entry_dir: &Path, root: &Path, id: &str) -> io::Result<()> {
+    fs::create_dir_all(root)?;
+    let staging = tempfile::Builder::new().prefix(STAGING_PREFIX).tempdir_in(root)?;
+    copy_dir(entry_dir, staging.path())?;
+
+    // The source may have been replaced while it was being copied
+    if read_complete_output(staging.path())?.is_none() {
+        return Err(io::Error::new(io::ErrorKind::InvalidData, format!("Entry {} is incomplete", id)));
+    }
+
+    replace_dir(root, staging.path(), id)
+}
+
+fn copy_dir(from: &Path, to: &Path) -> io::Result<()> {
+    fs::create_dir_all(to)?;
+
+    for entry in fs::read_dir(from)? {
+        let entry = entry?;
+        let target = to.join(entry.file_name());
+
+        if entry.file_type()?.is_dir() {
+            copy_dir(&entry.path(), &target)?;
+        } else {
+            fs::copy(entry.path(), target)?;
+        }
+    }
+
+    Ok(())
+}
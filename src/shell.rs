@@ -0,0 +1,307 @@
+use std::fmt::Write;
+use std::path::Path;
+use std::str::FromStr;
+use crate::hint_file::{CommandHint, HintFile};
+
+/// Shells `cacher shell-init` can generate wrapper functions for
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Shell {
+    Bash,
+    Zsh,
+    Fish,
+}
+
+impl FromStr for Shell {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "bash" => Ok(Shell::Bash),
+            "zsh" => Ok(Shell::Zsh),
+            "fish" => Ok(Shell::Fish),
+            _ => Err(format!("Unsupported shell: {} (expected bash, zsh or fish)", s)),
+        }
+    }
+}
+
+/// A command hint pattern routed through cacher by a wrapper function
+struct WrappedPattern<'a> {
+    pattern: &'a str,
+    no_cache_if_env: Vec<&'a str>,
+    no_cache_if_file_exists: Vec<&'a str>,
+}
+
+/// Generate shell functions that run a project's configured commands through cacher
+///
+/// One function is emitted per program that starts a command pattern in the
+/// hint file, e.g. `npm` for `npm run *`. Inside `project_dir` the function
+/// runs commands matching one of the program's patterns with `cacher run`
+/// and everything else directly; outside it, the function does nothing. Each
+/// pattern's `no_cache_if_env` and `no_cache_if_file_exists` conditions, plus
+/// the default ones, are checked by the wrapper so bypassed commands run
+/// without cacher in between.
+///
+/// Patterns whose program part contains wildcards can't be wrapped and are
+/// listed as comments instead.
+pub fn init_script(shell: Shell, hint_file: &HintFile, project_dir: &Path) -> String {
+    let mut programs: Vec<(&str, Vec<WrappedPattern>)> = Vec::new();
+    let mut skipped = Vec::new();
+
+    for hint in &hint_file.commands {
+        let program = match wrappable_program(&hint.pattern) {
+            Some(program) => program,
+            None => {
+                skipped.push(hint.pattern.as_str());
+                continue;
+            }
+        };
+
+        let wrapped = wrapped_pattern(hint_file, hint);
+        match programs.iter_mut().find(|(name, _)| *name == program) {
+            Some((_, patterns)) => patterns.push(wrapped),
+            None => programs.push((program, vec![wrapped])),
+        }
+    }
+
+    let mut script = String::new();
+    let _ = writeln!(script, "# Generated by `cacher shell-init`; commands matching the hint file in");
+    let _ = writeln!(script, "# {} run through cacher", project_dir.display());
+    for pattern in skipped {
+        let _ = writeln!(script, "# skipped pattern without a literal program: {}", pattern.replace('\n', " "));
+    }
+
+    for (program, patterns) in &programs {
+        script.push('\n');
+        match shell {
+            Shell::Bash | Shell::Zsh => write_posix_function(&mut script, program, patterns, project_dir),
+            Shell::Fish => write_fish_function(&mut script, program, patterns, project_dir),
+        }
+    }
+
+    script
+}
+
+/// The program a pattern applies to, if it is a literal a function can be named after
+fn wrappable_program(pattern: &str) -> Option<&str> {
+    let program = pattern.split_whitespace().next()?;
+    let valid = program
+        .chars()
+        .all(|c| c.is_ascii_alphanumeric() || matches!(c, '_' | '-' | '.' | '+'));
+
+    // The pattern must continue with a space, or `npm*` would also match `npmx`
+    let rest = &pattern[program.len()..];
+    let separated = rest.is_empty() || rest.starts_with(' ');
+
+    if valid && separated {
+        Some(program)
+    } else {
+        None
+    }
+}
+
+fn wrapped_pattern<'a>(hint_file: &'a HintFile, hint: &'a CommandHint) -> WrappedPattern<'a> {
+    let default = &hint_file.default;
+    WrappedPattern {
+        pattern: &hint.pattern,
+        // Names that aren't valid shell variables can't be set from a shell either
+        no_cache_if_env: default.no_cache_if_env.iter()
+            .chain(&hint.no_cache_if_env)
+            .map(String::as_str)
+            .filter(|name| is_variable_name(name))
+            .collect(),
+        no_cache_if_file_exists: default.no_cache_if_file_exists.iter()
+            .chain(&hint.no_cache_if_file_exists)
+            .map(String::as_str)
+            .collect(),
+    }
+}
+
+fn is_variable_name(name: &str) -> bool {
+    let mut chars = name.chars();
+    chars.next().is_some_and(|c| c.is_ascii_alphabetic() || c == '_')
+        && chars.all(|c| c.is_ascii_alphanumeric() || c == '_')
+}
+
+/// Write a bash/zsh function for one program
+fn write_posix_function(script: &mut String, program: &str, patterns: &[WrappedPattern], project_dir: &Path) {
+    let project = posix_quote(&project_dir.to_string_lossy());
+
+    let _ = writeln!(script, "{}() {{", program);
+    let _ = writeln!(script, "    local cmd=\"{} $*\"", program);
+    let _ = writeln!(script, "    case \"$PWD/\" in");
+    let _ = writeln!(script, "        {}'/'*) ;;", project);
+    let _ = writeln!(script, "        *) command {} \"$@\"; return ;;", program);
+    let _ = writeln!(script, "    esac");
+    let _ = writeln!(script, "    case \"${{cmd% }}\" in");
+
+    for pattern in patterns {
+        let conditions: Vec<String> = pattern.no_cache_if_env.iter()
+            .map(|name| format!("[ -n \"${{{}:-}}\" ]", name))
+            .chain(pattern.no_cache_if_file_exists.iter().map(|file| format!("[ -e {} ]", posix_quote(file))))
+            .collect();
+
+        let _ = writeln!(script, "        {})", glob_to_case_pattern(pattern.pattern));
+        if conditions.is_empty() {
+            let _ = writeln!(script, "            command cacher run -- {} \"$@\" ;;", program);
+        } else {
+            let _ = writeln!(
+                script,
+                "            if {}; then command {} \"$@\"; else command cacher run -- {} \"$@\"; fi ;;",
+                conditions.join(" || "), program, program,
+            );
+        }
+    }
+
+    let _ = writeln!(script, "        *) command {} \"$@\" ;;", program);
+    let _ = writeln!(script, "    esac");
+    let _ = writeln!(script, "}}");
+}
+
+/// Write a fish function for one program
+fn write_fish_function(script: &mut String, program: &str, patterns: &[WrappedPattern], project_dir: &Path) {
+    let project = format!("^{}/", regex::escape(&project_dir.to_string_lossy()));
+
+    let _ = writeln!(script, "function {} --wraps {}", program, program);
+    let _ = writeln!(script, "    set -l cmd (string trim --right -- \"{} $argv\")", program);
+    let _ = writeln!(script, "    if not string match -rq -- {} \"$PWD/\"", fish_quote(&project));
+    let _ = writeln!(script, "        command {} $argv", program);
+    let _ = writeln!(script, "        return");
+    let _ = writeln!(script, "    end");
+
+    for (i, pattern) in patterns.iter().enumerate() {
+        let keyword = if i == 0 { "if" } else { "else if" };
+        let regex = format!("^{}$", glob_to_regex(pattern.pattern));
+        let _ = writeln!(script, "    {} string match -rq -- {} \"$cmd\"", keyword, fish_quote(&regex));
+
+        let conditions: Vec<String> = pattern.no_cache_if_env.iter()
+            .map(|name| format!("test -n \"${}\"", name))
+            .chain(pattern.no_cache_if_file_exists.iter().map(|file| format!("test -e {}", fish_quote(file))))
+            .collect();
+
+        if conditions.is_empty() {
+            let _ = writeln!(script, "        command cacher run -- {} $argv", program);
+        } else {
+            let _ = writeln!(script, "        if {}", conditions.join("; or "));
+            let _ = writeln!(script, "            command {} $argv", program);
+            let _ = writeln!(script, "        else");
+            let _ = writeln!(script, "            command cacher run -- {} $argv", program);
+            let _ = writeln!(script, "        end");
+        }
+    }
+
+    let _ = writeln!(script, "    else");
+    let _ = writeln!(script, "        command {} $argv", program);
+    let _ = writeln!(script, "    end");
+    let _ = writeln!(script, "end");
+}
+
+/// Quote a string for bash/zsh, where nothing inside single quotes is special
+fn posix_quote(value: &str) -> String {
+    format!("'{}'", value.replace('\'', "'\\''"))
+}
+
+/// Quote a string for fish, where `\` and `'` are escaped inside single quotes
+fn fish_quote(value: &str) -> String {
+    format!("'{}'", value.replace('\\', "\\\\").replace('\'', "\\'"))
+}
+
+/// Translate a hint pattern into a bash/zsh `case` pattern
+///
+/// Wildcards and character classes are left unquoted so the shell matches
+/// them the way the glob crate does; everything else is quoted literally.
+fn glob_to_case_pattern(pattern: &str) -> String {
+    let mut result = String::new();
+    let mut literal = String::new();
+    let mut chars = pattern.chars().peekable();
+
+    while let Some(c) = chars.next() {
+        match c {
+            '*' | '?' => {
+                flush_literal(&mut result, &mut literal);
+                result.push(c);
+            },
+            '[' => match take_class(&mut chars) {
+                Some(class) => {
+                    flush_literal(&mut result, &mut literal);
+                    result.push('[');
+                    for (i, class_char) in class.chars().enumerate() {
+                        if (i == 0 && class_char == '!') || class_char.is_ascii_alphanumeric() || class_char == '-' {
+                            result.push(class_char);
+                        } else {
+                            result.push('\\');
+                            result.push(class_char);
+                        }
+                    }
+                    result.push(']');
+                },
+                None => literal.push(c),
+            },
+            _ => literal.push(c),
+        }
+    }
+
+    flush_literal(&mut result, &mut literal);
+    result
+}
+
+fn flush_literal(result: &mut String, literal: &mut String) {
+    if !literal.is_empty() {
+        result.push_str(&posix_quote(literal));
+        literal.clear();
+    }
+}
+
+/// Translate a hint pattern into an unanchored regex
+fn glob_to_regex(pattern: &str) -> String {
+    let mut result = String::new();
+    let mut chars = pattern.chars().peekable();
+
+    while let Some(c) = chars.next() {
+        match c {
+            '*' => result.push_str(".*"),
+            '?' => result.push('.'),
+            '[' => match take_class(&mut chars) {
+                Some(class) => {
+                    let (negated, members) = match class.strip_prefix('!') {
+                        Some(members) => (true, members),
+                        None => (false, class.as_str()),
+                    };
+                    result.push('[');
+                    if negated {
+                        result.push('^');
+                    }
+                    for member in members.chars() {
+                        if member == '-' || member.is_ascii_alphanumeric() {
+                            result.push(member);
+                        } else {
+                            result.push('\\');
+                            result.push(member);
+                        }
+                    }
+                    result.push(']');
+                },
+                None => result.push_str(&regex::escape("[")),
+            },
+            _ => result.push_str(&regex::escape(&c.to_string())),
+        }
+    }
+
+    result
+}
+
+/// Consume a character class after its opening `[`, returning its contents
+///
+/// A `]` right after the opening bracket (or `[!`) is a member, as in glob.
+/// Returns None, consuming nothing, if the class is never closed.
+fn take_class(chars: &mut std::iter::Peekable<std::str::Chars>) -> Option<String> {
+    let rest: String = chars.clone().collect();
+    let prefix_len = if rest.starts_with('!') { 1 } else { 0 };
+    let search_from = prefix_len + rest[prefix_len..].chars().next().map_or(0, char::len_utf8);
+    let close = search_from + rest.get(search_from..)?.find(']')?;
+
+    let class = rest[..close].to_string();
+    for _ in 0..class.chars().count() + 1 {
+        chars.next();
+    }
+    Some(class)
+}
@@ -0,0 +1,162 @@
+use std::collections::BTreeMap;
+use std::str::FromStr;
+use std::time::Duration;
+use crate::metadata::EntryMetadata;
+
+/// Shown for entries cached before their project was recorded
+const UNKNOWN_PROJECT: &str = "(unknown)";
+
+/// Shown for entries of commands no hint pattern matched
+const NO_PATTERN: &str = "(no pattern)";
+
+/// What `cacher stats --group-by` rolls entries up by
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum GroupBy {
+    /// The project the entry was cached in
+    Project,
+    /// The pattern of the command hint that matched the command
+    Pattern,
+    /// The day the entry was created, in UTC
+    Day,
+}
+
+impl GroupBy {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            GroupBy::Project => "project",
+            GroupBy::Pattern => "pattern",
+            GroupBy::Day => "day",
+        }
+    }
+
+    /// The group an entry falls into
+    fn key(&self, metadata: &EntryMetadata) -> String {
+        match self {
+            GroupBy::Project => metadata.project.clone().unwrap_or_else(|| UNKNOWN_PROJECT.to_string()),
+            GroupBy::Pattern => metadata.inputs.pattern.clone().unwrap_or_else(|| NO_PATTERN.to_string()),
+            // RFC 3339 starts with the date, e.g. 2024-05-01T12:00:00Z
+            GroupBy::Day => humantime::format_rfc3339_seconds(metadata.created_at()).to_string()[..10].to_string(),
+        }
+    }
+}
+
+impl FromStr for GroupBy {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "project" => Ok(GroupBy::Project),
+            "pattern" => Ok(GroupBy::Pattern),
+            "day" => Ok(GroupBy::Day),
+            _ => Err(format!("Unknown grouping: {} (expected project, pattern or day)", s)),
+        }
+    }
+}
+
+/// Totals over a set of entries
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub struct EntryStats {
+    pub entries: u64,
+
+    /// How many times the entries were served from the cache
+    pub hits: u64,
+
+    /// Size of the entries on disk, in bytes
+    pub size: u64,
+
+    /// How long the commands took when they were run
+    pub run_time: Duration,
+
+    /// Run time the hits saved: each hit saves the run time of its entry
+    pub saved: Duration,
+}
+
+impl EntryStats {
+    fn add(&mut self, metadata: &EntryMetadata, size: u64) {
+        self.entries += 1;
+        self.hits += metadata.hit_count;
+        self.size += size;
+        self.run_time += metadata.duration();
+        self.saved += metadata.duration().saturating_mul(metadata.hit_count.min(u32::MAX as u64) as u32);
+    }
+
+    /// Share of lookups served from the cache, counting one miss per entry
+    pub fn hit_rate(&self) -> f64 {
+        let lookups = self.hits + self.entries;
+        if lookups == 0 {
+            0.0
+        } else {
+            self.hits as f64 / lookups as f64
+        }
+    }
+}
+
+/// One line of a rollup
+#[derive(Debug, Clone, PartialEq)]
+pub struct StatsRow {
+    /// The group at each level, outermost first; empty for the total over all entries
+    pub groups: Vec<String>,
+
+    pub stats: EntryStats,
+}
+
+/// Roll entries up by each level of `group_by` in turn
+///
+/// Rows come out depth first: the total, then each outer group followed by
+/// the groups nested in it, sorted by name at every level.
+///
+/// # Arguments
+///
+/// * `entries` - The metadata of each entry, with its size on disk
+/// * `group_by` - The grouping levels, outermost first
+pub fn rollup(entries: &[(EntryMetadata, u64)], group_by: &[GroupBy]) -> Vec<StatsRow> {
+    let mut groups: BTreeMap<Vec<String>, EntryStats> = BTreeMap::new();
+    groups.insert(Vec::new(), EntryStats::default());
+
+    for (metadata, size) in entries {
+        let keys: Vec<String> = group_by.iter().map(|level| level.key(metadata)).collect();
+        for depth in 0..=keys.len() {
+            groups.entry(keys[..depth].to_vec()).or_default().add(metadata, *size);
+        }
+    }
+
+    groups.into_iter().map(|(groups, stats)| StatsRow { groups, stats }).collect()
+}
+
+/// Write the innermost rows of a rollup as CSV, one column per grouping level
+///
+/// Subtotals are left out, so summing a column in a spreadsheet gives the
+/// right total. Durations are in milliseconds and sizes in bytes.
+pub fn to_csv(rows: &[StatsRow], group_by: &[GroupBy]) -> String {
+    let mut header: Vec<&str> = group_by.iter().map(GroupBy::as_str).collect();
+    header.extend(["entries", "hits", "hit_rate", "size_bytes", "run_time_ms", "saved_ms"]);
+
+    let mut csv = header.join(",");
+    csv.push('\n');
+
+    for row in rows.iter().filter(|row| row.groups.len() == group_by.len()) {
+        let stats = &row.stats;
+        let mut fields: Vec<String> = row.groups.iter().map(|group| csv_field(group)).collect();
+        fields.extend([
+            stats.entries.to_string(),
+            stats.hits.to_string(),
+            format!("{:.4}", stats.hit_rate()),
+            stats.size.to_string(),
+            stats.run_time.as_millis().to_string(),
+            stats.saved.as_millis().to_string(),
+        ]);
+        csv.push_str(&fields.join(","));
+        csv.push('\n');
+    }
+
+    csv
+}
+
+/// Quote a CSV field if it contains a comma, quote or line break
+fn csv_field(value: &str) -> String {
+    if value.contains([',', '"', '\n', '\r']) {
+        format!("\"{}\"", value.replace('"', "\"\""))
+    } else {
+        value.to_string()
+    }
+}
@@ -0,0 +1,138 @@
+//! Helpers for testing code that uses [`CommandCache`]
+//!
+//! [`TestEnv`] gives each test its own cache and project directory, so tests
+//! never touch the user's real cache or depend on the process's working
+//! directory, and can run in parallel.
+//!
+//! ```
+//! use cacher::testing::TestEnv;
+//!
+//! let env = TestEnv::new();
+//! env.write_hint_file("commands:\n  - pattern: \"echo *\"\n    ttl: 60\n");
+//!
+//! let mut cache = env.cache();
+//! let output = cache.execute_and_cache("echo hello", None, false).unwrap();
+//! assert_eq!(output.trim(), "hello");
+//! ```
+
+use std::env;
+use std::ffi::OsString;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::{Mutex, MutexGuard};
+use tempfile::TempDir;
+use crate::CommandCache;
+
+/// Serializes tests that change environment variables, which are process-wide
+static ENV_LOCK: Mutex<()> = Mutex::new(());
+
+/// An isolated cache and project directory, removed when dropped
+///
+/// Environment variables changed through [`TestEnv::set_env`] and
+/// [`TestEnv::remove_env`] are restored on drop. The first such change takes
+/// a process-wide lock that is held until then, so tests that modify the
+/// environment don't interleave.
+pub struct TestEnv {
+    cache_dir: TempDir,
+    project_dir: TempDir,
+    saved_env: Vec<(String, Option<OsString>)>,
+    env_lock: Option<MutexGuard<'static, ()>>,
+}
+
+impl TestEnv {
+    /// Create empty temporary cache and project directories
+    pub fn new() -> Self {
+        TestEnv {
+            cache_dir: TempDir::new().expect("Failed to create temporary cache directory"),
+            project_dir: TempDir::new().expect("Failed to create temporary project directory"),
+            saved_env: Vec::new(),
+            env_lock: None,
+        }
+    }
+
+    /// Directory cache entries are stored in
+    pub fn cache_dir(&self) -> &Path {
+        self.cache_dir.path()
+    }
+
+    /// Directory commands run in and the hint file is read from
+    pub fn project_dir(&self) -> &Path {
+        self.project_dir.path()
+    }
+
+    /// Create a cache for the project directory, storing entries in the cache directory
+    ///
+    /// The hint file is read when the cache is created, so write it first.
+    pub fn cache(&self) -> CommandCache {
+        self.cache_in(self.project_dir())
+    }
+
+    /// Create a cache that runs commands in `dir`, storing entries in the cache directory
+    pub fn cache_in(&self, dir: &Path) -> CommandCache {
+        CommandCache::with_dirs(self.cache_dir().to_path_buf(), dir.to_path_buf())
+    }
+
+    /// Write a `.cacher` hint file in the project directory
+    pub fn write_hint_file(&self, content: &str) -> PathBuf {
+        self.write_file(".cacher", content)
+    }
+
+    /// Write a file relative to the project directory, creating parent directories
+    pub fn write_file(&self, path: impl AsRef<Path>, content: impl AsRef<[u8]>) -> PathBuf {
+        let path = self.project_dir().join(path);
+        if let Some(parent) = path.parent() {
+            fs::create_dir_all(parent).expect("Failed to create parent directory");
+        }
+        fs::write(&path, content).expect("Failed to write file");
+        path
+    }
+
+    /// Count the lines of a file relative to the project directory, 0 if it doesn't exist
+    ///
+    /// Handy with scripts that append a line per run, to tell how often a
+    /// command actually ran.
+    pub fn count_lines(&self, path: impl AsRef<Path>) -> usize {
+        fs::read_to_string(self.project_dir().join(path)).unwrap_or_default().lines().count()
+    }
+
+    /// Set an environment variable until this environment is dropped
+    pub fn set_env(&mut self, key: &str, value: impl AsRef<std::ffi::OsStr>) {
+        self.save_env(key);
+        env::set_var(key, value);
+    }
+
+    /// Unset an environment variable until this environment is dropped
+    pub fn remove_env(&mut self, key: &str) {
+        self.save_env(key);
+        env::remove_var(key);
+    }
+
+    /// Remember a variable's original value the first time it is changed
+    fn save_env(&mut self, key: &str) {
+        if self.env_lock.is_none() {
+            // A test that panicked while holding the lock still restored its variables
+            self.env_lock = Some(ENV_LOCK.lock().unwrap_or_else(|e| e.into_inner()));
+        }
+
+        if !self.saved_env.iter().any(|(saved, _)| saved == key) {
+            self.saved_env.push((key.to_string(), env::var_os(key)));
+        }
+    }
+}
+
+impl Default for TestEnv {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Drop for TestEnv {
+    fn drop(&mut self) {
+        for (key, value) in self.saved_env.drain(..).rev() {
+            match value {
+                Some(value) => env::set_var(&key, value),
+                None => env::remove_var(&key),
+            }
+        }
+    }
+}
@@ -0,0 +1,295 @@
+use std::io;
+use std::time::{Duration, SystemTime};
+use cacher::{CacheEntrySummary, CommandCache, ListOptions};
+use ratatui::crossterm::event::{self, Event, KeyCode, KeyEvent, KeyEventKind};
+use ratatui::layout::{Constraint, Layout};
+use ratatui::style::{Modifier, Style, Stylize};
+use ratatui::text::Line;
+use ratatui::widgets::{Block, Paragraph, Row, Table, TableState, Wrap};
+use ratatui::{DefaultTerminal, Frame};
+use crate::{format_duration, format_size};
+
+/// How long to wait for a key before redrawing, so ages stay current
+const REDRAW_INTERVAL: Duration = Duration::from_secs(1);
+
+const HELP: &str = "j/k move  enter view  p pin  d delete  r refresh  / filter  q quit";
+
+/// What the keyboard currently controls
+enum Mode {
+    /// Moving through the entry table
+    Browse,
+    /// Typing a filter pattern
+    Filter,
+    /// Waiting for the user to confirm deleting the selected entry
+    ConfirmDelete,
+    /// Reading an entry's output
+    View { title: String, text: String, scroll: u16 },
+}
+
+/// State of the `cacher ui` screen
+struct App<'a> {
+    cache: &'a mut CommandCache,
+    entries: Vec<CacheEntrySummary>,
+    filter: String,
+    table: TableState,
+    mode: Mode,
+    status: String,
+}
+
+/// Run the interactive cache browser until the user quits
+pub fn run(cache: &mut CommandCache) -> io::Result<()> {
+    let mut app = App {
+        cache,
+        entries: Vec::new(),
+        filter: String::new(),
+        table: TableState::default(),
+        mode: Mode::Browse,
+        status: String::new(),
+    };
+    app.reload()?;
+
+    let mut terminal = ratatui::init();
+    let result = app.event_loop(&mut terminal);
+    ratatui::restore();
+    result
+}
+
+impl App<'_> {
+    fn event_loop(&mut self, terminal: &mut DefaultTerminal) -> io::Result<()> {
+        loop {
+            terminal.draw(|frame| self.draw(frame))?;
+
+            if !event::poll(REDRAW_INTERVAL)? {
+                continue;
+            }
+
+            if let Event::Key(key) = event::read()? {
+                if key.kind != KeyEventKind::Press {
+                    continue;
+                }
+
+                let refreshing = matches!(self.mode, Mode::Browse) && key.code == KeyCode::Char('r');
+                if !self.handle_key(key)? {
+                    return Ok(());
+                }
+
+                // Hooks of a refreshed command may have written over the screen
+                if refreshing {
+                    terminal.clear()?;
+                }
+            }
+        }
+    }
+
+    /// Handle a key press, returning false when the UI should close
+    fn handle_key(&mut self, key: KeyEvent) -> io::Result<bool> {
+        match &mut self.mode {
+            Mode::View { scroll, .. } => match key.code {
+                KeyCode::Char('q') | KeyCode::Esc | KeyCode::Enter => self.mode = Mode::Browse,
+                KeyCode::Down | KeyCode::Char('j') => *scroll = scroll.saturating_add(1),
+                KeyCode::Up | KeyCode::Char('k') => *scroll = scroll.saturating_sub(1),
+                KeyCode::PageDown | KeyCode::Char(' ') => *scroll = scroll.saturating_add(20),
+                KeyCode::PageUp => *scroll = scroll.saturating_sub(20),
+                KeyCode::Home | KeyCode::Char('g') => *scroll = 0,
+                _ => {}
+            },
+            Mode::Filter => match key.code {
+                KeyCode::Enter => self.mode = Mode::Browse,
+                KeyCode::Esc => {
+                    self.filter.clear();
+                    self.mode = Mode::Browse;
+                    self.table.select_first();
+                },
+                KeyCode::Backspace => {
+                    self.filter.pop();
+                    self.table.select_first();
+                },
+                KeyCode::Char(c) => {
+                    self.filter.push(c);
+                    self.table.select_first();
+                },
+                _ => {}
+            },
+            Mode::ConfirmDelete => {
+                self.mode = Mode::Browse;
+                if key.code == KeyCode::Char('y') {
+                    self.delete_selected()?;
+                } else {
+                    self.status = "Delete cancelled".to_string();
+                }
+            },
+            Mode::Browse => match key.code {
+                KeyCode::Char('q') | KeyCode::Esc => return Ok(false),
+                KeyCode::Down | KeyCode::Char('j') => self.table.select_next(),
+                KeyCode::Up | KeyCode::Char('k') => self.table.select_previous(),
+                KeyCode::Home | KeyCode::Char('g') => self.table.select_first(),
+                KeyCode::End | KeyCode::Char('G') => self.table.select_last(),
+                KeyCode::Enter | KeyCode::Char('v') => self.view_selected(),
+                KeyCode::Char('p') => self.toggle_pin_selected()?,
+                KeyCode::Char('d') => {
+                    if let Some(entry) = self.selected() {
+                        self.status = format!("Delete {}? (y/n)", entry.command);
+                        self.mode = Mode::ConfirmDelete;
+                    }
+                },
+                KeyCode::Char('r') => self.refresh_selected()?,
+                KeyCode::Char('/') => {
+                    self.status.clear();
+                    self.mode = Mode::Filter;
+                },
+                _ => {}
+            },
+        }
+
+        Ok(true)
+    }
+
+    /// Re-read the entries from disk, keeping the selection on the same entry
+    ///
+    /// If the entry is gone, the selection stays at the same row instead.
+    fn reload(&mut self) -> io::Result<()> {
+        let selected_id = self.selected().map(|entry| entry.id.clone());
+        self.entries = self.cache.list_cached_commands(&ListOptions::default())?;
+
+        let position = selected_id
+            .and_then(|id| self.visible().iter().position(|entry| entry.id == id))
+            .or(self.table.selected())
+            .unwrap_or(0);
+        self.table.select(Some(position));
+        Ok(())
+    }
+
+    /// Entries matching the current filter
+    fn visible(&self) -> Vec<&CacheEntrySummary> {
+        self.entries.iter().filter(|entry| matches_filter(&entry.command, &self.filter)).collect()
+    }
+
+    fn selected(&self) -> Option<&CacheEntrySummary> {
+        let visible = self.visible();
+        let index = self.table.selected()?.min(visible.len().checked_sub(1)?);
+        Some(visible[index])
+    }
+
+    fn view_selected(&mut self) {
+        let Some(entry) = self.selected() else { return };
+        let title = entry.command.clone();
+
+        match self.cache.load_entry_by_id(&entry.id) {
+            Ok(Some((output, _))) => {
+                let mut text = output.stdout;
+                if !output.stderr.is_empty() {
+                    text.push_str("\n--- stderr ---\n");
+                    text.push_str(&output.stderr);
+                }
+                self.mode = Mode::View { title, text, scroll: 0 };
+            },
+            Ok(None) => self.status = "Entry no longer exists".to_string(),
+            Err(e) => self.status = format!("Error loading entry: {}", e),
+        }
+    }
+
+    fn toggle_pin_selected(&mut self) -> io::Result<()> {
+        let Some(entry) = self.selected() else { return Ok(()) };
+        let (id, pinned) = (entry.id.clone(), !entry.pinned);
+
+        match self.cache.set_pinned(&id, pinned) {
+            Ok(()) => self.status = if pinned { "Pinned" } else { "Unpinned" }.to_string(),
+            Err(e) => self.status = format!("Error pinning entry: {}", e),
+        }
+        self.reload()
+    }
+
+    fn delete_selected(&mut self) -> io::Result<()> {
+        let Some(entry) = self.selected() else { return Ok(()) };
+        let id = entry.id.clone();
+
+        match self.cache.remove_entry(&id) {
+            Ok(()) => self.status = "Deleted".to_string(),
+            Err(e) => self.status = format!("Error deleting entry: {}", e),
+        }
+        self.reload()
+    }
+
+    fn refresh_selected(&mut self) -> io::Result<()> {
+        let Some(entry) = self.selected() else { return Ok(()) };
+        let id = entry.id.clone();
+
+        match self.cache.refresh_entry(&id) {
+            Ok(_) => self.status = "Refreshed".to_string(),
+            Err(e) => self.status = format!("Error refreshing entry: {}", e),
+        }
+        self.reload()
+    }
+
+    fn draw(&mut self, frame: &mut Frame) {
+        if let Mode::View { title, text, scroll } = &self.mode {
+            let paragraph = Paragraph::new(text.as_str())
+                .block(Block::bordered().title(title.as_str()).title_bottom("j/k scroll  q back"))
+                .wrap(Wrap { trim: false })
+                .scroll((*scroll, 0));
+            frame.render_widget(paragraph, frame.area());
+            return;
+        }
+
+        let [table_area, filter_area, status_area] = Layout::vertical([
+            Constraint::Min(3),
+            Constraint::Length(1),
+            Constraint::Length(1),
+        ])
+        .areas(frame.area());
+
+        let now = SystemTime::now();
+        let visible = self.visible();
+        let rows: Vec<Row> = visible.iter().map(|entry| {
+            let age = now.duration_since(entry.created_at).unwrap_or_default();
+            Row::new(vec![
+                if entry.pinned { "*" } else { "" }.to_string(),
+                format_size(entry.size),
+                format_duration(&age),
+                entry.hit_count.to_string(),
+                format_duration(&entry.duration),
+                entry.command.replace('\n', " "),
+            ])
+        }).collect();
+
+        let title = format!(" cacher: {} of {} entries ", visible.len(), self.entries.len());
+        let table = Table::new(rows, [
+            Constraint::Length(3),
+            Constraint::Length(10),
+            Constraint::Length(5),
+            Constraint::Length(6),
+            Constraint::Length(6),
+            Constraint::Fill(1),
+        ])
+        .header(Row::new(["PIN", "SIZE", "AGE", "HITS", "TIME", "COMMAND"]).bold())
+        .block(Block::bordered().title(title))
+        .row_highlight_style(Style::new().add_modifier(Modifier::REVERSED));
+        frame.render_stateful_widget(table, table_area, &mut self.table);
+
+        let filter = match self.mode {
+            Mode::Filter => Line::from(format!("/{}", self.filter)).bold(),
+            _ if !self.filter.is_empty() => Line::from(format!("filter: {}", self.filter)),
+            _ => Line::from(HELP).dim(),
+        };
+        frame.render_widget(filter, filter_area);
+        frame.render_widget(Line::from(self.status.as_str()), status_area);
+    }
+}
+
+/// Whether a command matches a filter typed in the UI
+///
+/// Filters containing glob wildcards are matched as hint-file style patterns;
+/// anything else matches as a substring.
+fn matches_filter(command: &str, filter: &str) -> bool {
+    if filter.is_empty() {
+        return true;
+    }
+
+    if filter.contains(['*', '?', '[']) {
+        if let Ok(pattern) = glob::Pattern::new(filter) {
+            return pattern.matches(command);
+        }
+    }
+
+    command.contains(filter)
+}
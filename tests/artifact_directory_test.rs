@@ -1,14 +1,13 @@
 #[cfg(test)]
 mod tests {
     use std::fs;
-    use tempfile::TempDir;
-    use cacher::CommandCache;
+    use cacher::testing::TestEnv;
 
     #[test]
     fn test_basic_artifact_caching() {
-        // Create a temporary directory for the test
-        let temp_dir = TempDir::new().unwrap();
-        let temp_path = temp_dir.path();
+        // Create an isolated cache and project directory for the test
+        let env = TestEnv::new();
+        let temp_path = env.project_dir();
         
         println!("Test directory: {}", temp_path.display());
         
@@ -29,12 +28,8 @@ commands:
         
         fs::write(temp_path.join(".cacher"), hint_file_content).unwrap();
         
-        // Change to the temp directory
-        let original_dir = std::env::current_dir().unwrap();
-        std::env::set_current_dir(temp_path).unwrap();
-        
         // Create a new CommandCache
-        let mut cache = CommandCache::new();
+        let mut cache = env.cache();
         
         // Execute the command for the first time
         let command = "echo test_artifact";
@@ -55,8 +50,56 @@ commands:
         
         // Verify the content of the file
         assert_eq!(fs::read_to_string(source_dir.join("file1.txt")).unwrap(), "content1");
+    }
+
+    #[test]
+    #[cfg(unix)]
+    fn test_restore_skipped_when_unchanged() {
+        use std::os::unix::fs::PermissionsExt;
+        
+        let env = TestEnv::new();
+        let build_dir = env.project_dir().join("build");
+        let app_js = env.write_file("build/app.js", "console.log('built');");
+        env.write_file("build/assets/logo.svg", "<svg/>");
+        env.write_hint_file(r#"
+commands:
+  - pattern: "echo test_manifest"
+    artifacts:
+      - type: "directory"
+        path: "build"
+"#);
+        
+        let command = "echo test_manifest";
+        let mut cache = env.cache();
+        fs::set_permissions(&app_js, fs::Permissions::from_mode(0o644)).unwrap();
+        cache.execute_and_cache_with_artifacts(command, None, false).unwrap();
+        
+        // The manifest only covers content, so a permission change shows whether
+        // the directory was re-extracted
+        let mode = |path: &std::path::Path| fs::metadata(path).unwrap().permissions().mode() & 0o777;
+        fs::set_permissions(&app_js, fs::Permissions::from_mode(0o600)).unwrap();
+        
+        // An up-to-date directory is left in place rather than re-extracted
+        cache.execute_and_cache_with_artifacts(command, None, false).unwrap();
+        assert_eq!(mode(&app_js), 0o600);
+        
+        // A corrupted file triggers a full restore
+        fs::write(&app_js, "garbage").unwrap();
+        cache.execute_and_cache_with_artifacts(command, None, false).unwrap();
+        assert_eq!(fs::read_to_string(&app_js).unwrap(), "console.log('built');");
+        assert_eq!(mode(&app_js), 0o644);
+        
+        // So does a missing or an extra file
+        fs::remove_file(build_dir.join("assets/logo.svg")).unwrap();
+        fs::write(build_dir.join("stray.txt"), "stray").unwrap();
+        cache.execute_and_cache_with_artifacts(command, None, false).unwrap();
+        assert!(build_dir.join("assets/logo.svg").exists());
+        assert!(!build_dir.join("stray.txt").exists());
         
-        // Clean up
-        std::env::set_current_dir(original_dir).unwrap();
+        // Forcing always re-extracts
+        fs::set_permissions(&app_js, fs::Permissions::from_mode(0o600)).unwrap();
+        cache.set_force_restore(true);
+        cache.execute_and_cache_with_artifacts(command, None, false).unwrap();
+        assert_eq!(mode(&app_js), 0o644);
     }
 }
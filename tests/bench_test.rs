@@ -0,0 +1,25 @@
+#[cfg(test)]
+mod tests {
+    use cacher::bench::{self, ArtifactRestore, Benchmark, HitServing, KeyGeneration};
+
+    #[test]
+    fn test_fixtures() {
+        let key_generation = KeyGeneration::new();
+        assert_eq!(key_generation.run(), key_generation.run());
+
+        let hit_serving = HitServing::new().unwrap();
+        assert!(hit_serving.run().unwrap().starts_with("cached output line\n"));
+
+        assert!(ArtifactRestore::new().unwrap().run().unwrap());
+    }
+
+    #[test]
+    fn test_run() {
+        let results = bench::run(1).unwrap();
+
+        // Budgets are for release builds, so only check every path was timed
+        let benchmarks: Vec<Benchmark> = results.iter().map(|result| result.benchmark).collect();
+        assert_eq!(benchmarks, Benchmark::ALL);
+        assert!(results.iter().all(|result| !result.median.is_zero()));
+    }
+}
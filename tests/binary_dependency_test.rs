@@ -0,0 +1,91 @@
+#[cfg(test)]
+mod tests {
+    use cacher::binary;
+    use cacher::testing::TestEnv;
+    use std::fs;
+    use std::os::unix::fs::PermissionsExt;
+
+    fn write_tool(env: &TestEnv, script: &str) {
+        let path = env.write_file("bin/tool", script);
+        fs::set_permissions(&path, fs::Permissions::from_mode(0o755)).unwrap();
+    }
+
+    #[test]
+    fn test_program() {
+        assert_eq!(binary::program("node --version"), Some("node"));
+        assert_eq!(binary::program("NODE_ENV=production RUST_LOG=1 node build.js"), Some("node"));
+        assert_eq!(binary::program("./configure --prefix=/usr"), Some("./configure"));
+        assert_eq!(binary::program("  "), None);
+    }
+
+    #[test]
+    fn test_resolve() {
+        let env = TestEnv::new();
+        write_tool(&env, "#!/bin/sh\necho v1\n");
+        env.write_file("bin/data.txt", "not executable");
+
+        let sh = binary::resolve("sh", env.project_dir()).unwrap();
+        assert!(sh.is_absolute());
+        assert_eq!(binary::resolve("./bin/tool", env.project_dir()), Some(env.project_dir().join("bin/tool").canonicalize().unwrap()));
+        assert_eq!(binary::resolve("./bin/data.txt", env.project_dir()), None);
+        assert_eq!(binary::resolve("cacher-no-such-program", env.project_dir()), None);
+    }
+
+    #[test]
+    fn test_include_binary() {
+        let env = TestEnv::new();
+        env.write_hint_file(r#"
+commands:
+  - pattern: "./bin/tool*"
+    include_binary: true
+"#);
+        write_tool(&env, "#!/bin/sh\necho v1\n");
+        let mut cache = env.cache();
+
+        let id = cache.generate_id("./bin/tool --version");
+        assert_eq!(cache.execute_and_cache("./bin/tool --version", None, false).unwrap(), "v1\n");
+        assert!(cache.entry_inputs("./bin/tool --version").dependencies.contains(&"bin/tool".to_string()));
+
+        // A rebuilt tool reporting the same version still invalidates the entry
+        write_tool(&env, "#!/bin/sh\necho v1\n# rebuilt\n");
+        assert_ne!(cache.generate_id("./bin/tool --version"), id);
+        assert!(env.cache().get_cached("./bin/tool --version", None).is_none());
+    }
+
+    #[test]
+    fn test_in_project_binary_is_keyed_by_relative_path() {
+        let hints = "commands:\n  - pattern: \"./bin/tool*\"\n    include_binary: true\n";
+        let first = TestEnv::new();
+        let second = TestEnv::new();
+        for env in [&first, &second] {
+            env.write_hint_file(hints);
+            write_tool(env, "#!/bin/sh\necho v1\n");
+        }
+        // Same build in both checkouts
+        let modified = fs::metadata(first.project_dir().join("bin/tool")).unwrap().modified().unwrap();
+        fs::File::options().write(true).open(second.project_dir().join("bin/tool")).unwrap().set_modified(modified).unwrap();
+
+        let fingerprint = |env: &TestEnv| {
+            let tool = env.project_dir().join("bin/tool").canonicalize().unwrap();
+            binary::fingerprint(&tool, env.project_dir()).unwrap()
+        };
+        assert!(fingerprint(&first).starts_with("bin/tool:"));
+        assert_eq!(fingerprint(&first), fingerprint(&second));
+
+        // Binaries outside the project keep their absolute path
+        let sh = binary::resolve("sh", first.project_dir()).unwrap();
+        assert!(binary::fingerprint(&sh, first.project_dir()).unwrap().starts_with(&*sh.to_string_lossy()));
+    }
+
+    #[test]
+    fn test_binary_not_included_by_default() {
+        let env = TestEnv::new();
+        env.write_hint_file("commands:\n  - pattern: \"./bin/tool*\"\n    ttl: 60\n");
+        write_tool(&env, "#!/bin/sh\necho v1\n");
+        let cache = env.cache();
+
+        let id = cache.generate_id("./bin/tool --version");
+        write_tool(&env, "#!/bin/sh\necho v1\n# rebuilt\n");
+        assert_eq!(cache.generate_id("./bin/tool --version"), id);
+    }
+}
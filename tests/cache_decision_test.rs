@@ -0,0 +1,162 @@
+#[cfg(test)]
+mod tests {
+    use cacher::clock::ManualClock;
+    use cacher::metadata::{self, EntryMetadata};
+    use cacher::testing::TestEnv;
+    use cacher::CacheSource;
+    use std::fs;
+    use std::sync::Arc;
+    use std::thread;
+    use std::time::{Duration, SystemTime};
+
+    const HINTS: &str = r#"
+commands:
+  - pattern: "cat *"
+    ttl: 60
+  - pattern: "echo *"
+    no_cache_if_file_exists: ["CACHER_OFF"]
+"#;
+
+    #[test]
+    fn test_decision_reports_where_output_came_from() {
+        let env = TestEnv::new();
+        env.write_hint_file(HINTS);
+        env.write_file("data.txt", "one\n");
+
+        let mut cache = env.cache();
+        let decision = cache.execute_with_decision("cat data.txt", None, false).unwrap();
+        assert_eq!(decision.output, "one\n");
+        assert_eq!(decision.source, CacheSource::Executed);
+        assert!(!decision.is_hit());
+        assert!(decision.elapsed >= decision.run_time);
+
+        let decision = cache.execute_with_decision("cat data.txt", None, false).unwrap();
+        assert_eq!(decision.source, CacheSource::MemoryHit);
+        assert_eq!(decision.output, "one\n");
+
+        let mut cache = env.cache();
+        let decision = cache.execute_with_decision("cat data.txt", None, false).unwrap();
+        assert_eq!(decision.source, CacheSource::DiskHit);
+        assert!(decision.is_hit());
+
+        // Each hit is counted once
+        let (_, metadata) = cache.load_entry("cat data.txt").unwrap().unwrap();
+        assert_eq!(metadata.hit_count, 2);
+    }
+
+    #[test]
+    fn test_rerun_tells_revalidation_from_change() {
+        let env = TestEnv::new();
+        env.write_hint_file(HINTS);
+        env.write_file("data.txt", "one\n");
+
+        let clock = ManualClock::default();
+        let mut cache = env.cache();
+        cache.set_clock(Arc::new(clock.clone()));
+        cache.execute_with_decision("cat data.txt", None, false).unwrap();
+
+        // An expired entry re-run with the same output was revalidated
+        clock.advance(Duration::from_secs(120));
+        let decision = cache.execute_with_decision("cat data.txt", None, false).unwrap();
+        assert_eq!(decision.source, CacheSource::Revalidated);
+
+        let decision = cache.execute_with_decision("cat data.txt", None, true).unwrap();
+        assert_eq!(decision.source, CacheSource::Revalidated);
+
+        env.write_file("data.txt", "two\n");
+        let decision = cache.execute_with_decision("cat data.txt", None, true).unwrap();
+        assert_eq!(decision.source, CacheSource::Executed);
+        assert_eq!(decision.output, "two\n");
+    }
+
+    #[test]
+    fn test_bypassed_commands() {
+        let env = TestEnv::new();
+        env.write_hint_file(HINTS);
+        env.write_file("CACHER_OFF", "");
+
+        let mut cache = env.cache();
+        let decision = cache.execute_with_decision("echo hi", None, false).unwrap();
+        assert_eq!(decision.source, CacheSource::Bypassed);
+        assert_eq!(decision.output.trim(), "hi");
+        assert!(cache.load_entry("echo hi").unwrap().is_none());
+    }
+
+    #[test]
+    fn test_run_all_counts_hits_once() {
+        let env = TestEnv::new();
+        env.write_hint_file(HINTS);
+        env.write_file("data.txt", "one\n");
+
+        let cache = env.cache();
+        let commands = vec!["cat data.txt".to_string()];
+        assert!(!cache.run_all(&commands, None, false, 1)[0].cached);
+        assert!(cache.run_all(&commands, None, false, 1)[0].cached);
+
+        let (_, metadata) = cache.load_entry("cat data.txt").unwrap().unwrap();
+        assert_eq!(metadata.hit_count, 1);
+    }
+
+    #[test]
+    fn test_hits_are_logged_without_rewriting_metadata() {
+        let env = TestEnv::new();
+        env.write_hint_file(HINTS);
+        env.write_file("data.txt", "one\n");
+
+        let mut cache = env.cache();
+        cache.execute_and_cache("cat data.txt", None, false).unwrap();
+        let id = cache.generate_id("cat data.txt");
+        let metadata_path = env.cache_dir().join(&id).join("metadata.json");
+        let written = fs::read(&metadata_path).unwrap();
+
+        // Concurrent hits from several processes neither wait for nor lose each other
+        let threads: Vec<_> = (0..4).map(|_| {
+            let cache = env.cache();
+            thread::spawn(move || {
+                for _ in 0..25 {
+                    assert_eq!(cache.clone().get_cached("cat data.txt", None).as_deref(), Some("one\n"));
+                }
+            })
+        }).collect();
+        for thread in threads {
+            thread.join().unwrap();
+        }
+        assert_eq!(fs::read(&metadata_path).unwrap(), written);
+        assert_eq!(cache.load_entry("cat data.txt").unwrap().unwrap().1.hit_count, 100);
+
+        // The next metadata write folds the logged hits in
+        cache.set_pinned(&id, true).unwrap();
+        assert_eq!(fs::read_to_string(env.cache_dir().join(&id).join("hits")).unwrap(), "");
+        let (_, metadata) = cache.load_entry("cat data.txt").unwrap().unwrap();
+        assert_eq!(metadata.hit_count, 100);
+        assert!(metadata.last_accessed.is_some());
+    }
+
+    #[test]
+    fn test_hits_logged_while_metadata_is_rewritten_are_kept() {
+        let env = TestEnv::new();
+        env.write_hint_file(HINTS);
+        env.write_file("data.txt", "one\n");
+        let mut cache = env.cache();
+        cache.execute_and_cache("cat data.txt", None, false).unwrap();
+        let entry_dir = cache.entry_dir(&cache.generate_id("cat data.txt"));
+        let metadata_path = entry_dir.join("metadata.json");
+        let served = SystemTime::UNIX_EPOCH + Duration::from_secs(2_000_000_000);
+
+        metadata::record_hit(&entry_dir, served).unwrap();
+        let mut metadata = EntryMetadata::read(&metadata_path).unwrap().unwrap();
+        assert_eq!(metadata.hit_count, 1);
+
+        // Hits served between reading and writing the metadata aren't lost
+        metadata::record_hit(&entry_dir, served).unwrap();
+        metadata::record_hit(&entry_dir, served).unwrap();
+        metadata.pinned = true;
+        metadata.write(&metadata_path).unwrap();
+        assert_eq!(fs::read_to_string(entry_dir.join(metadata::HITS_FILE)).unwrap(), "");
+
+        let metadata = EntryMetadata::read(&metadata_path).unwrap().unwrap();
+        assert_eq!(metadata.hit_count, 3);
+        assert_eq!(metadata.last_accessed(), Some(served));
+        assert!(metadata.pinned);
+    }
+}
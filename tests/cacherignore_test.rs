@@ -0,0 +1,105 @@
+#[cfg(test)]
+mod tests {
+    use cacher::hint_file::Dependency;
+    use cacher::ignore::IgnoreRules;
+    use cacher::testing::TestEnv;
+    use std::fs;
+    use std::path::Path;
+
+    const IGNORE: &str = "# build junk
+*.tmp
+target/
+/dist/cache/
+*.map
+!keep.map
+";
+
+    #[test]
+    fn test_ignore_rules() {
+        let rules = IgnoreRules::parse(Path::new("/project"), IGNORE);
+        let ignored = |path: &str, is_dir: bool| rules.is_ignored(Path::new(path), is_dir);
+
+        assert!(ignored("notes.tmp", false));
+        assert!(ignored("src/deep/notes.tmp", false));
+        assert!(ignored("/project/src/notes.tmp", false));
+        assert!(!ignored("/elsewhere/notes.tmp", false));
+
+        // Directory patterns cover the directory's contents, but not files of that name
+        assert!(ignored("target", true));
+        assert!(ignored("crates/a/target/debug/app", false));
+        assert!(!ignored("target", false));
+
+        // Anchored patterns only match from the project directory
+        assert!(ignored("dist/cache/chunk.js", false));
+        assert!(!ignored("web/dist/cache/chunk.js", false));
+
+        assert!(ignored("dist/app.js.map", false));
+        assert!(!ignored("dist/keep.map", false));
+        assert!(!ignored("src/main.rs", false));
+
+        assert!(IgnoreRules::parse(Path::new("/project"), "# nothing\n\n").is_empty());
+    }
+
+    #[test]
+    fn test_ignored_files_are_left_out_of_keys() {
+        let env = TestEnv::new();
+        env.write_hint_file(r#"
+commands:
+  - pattern: "cat src/main.rs"
+    depends_on:
+      - files: "src/**/*"
+"#);
+        env.write_file(".cacherignore", IGNORE);
+        env.write_file("src/main.rs", "fn main() {}\n");
+
+        let command = "cat src/main.rs";
+        let id = env.cache().generate_id(command);
+
+        env.write_file("src/scratch.tmp", "junk");
+        env.write_file("src/target/debug/out", "junk");
+        assert_eq!(env.cache().generate_id(command), id);
+        let dependencies = env.cache().entry_inputs(command).dependencies;
+        assert_eq!(dependencies, vec!["src/**/*", "src/main.rs"]);
+
+        env.write_file("src/lib.rs", "pub fn f() {}\n");
+        assert_ne!(env.cache().generate_id(command), id);
+
+        let dependency = Dependency::Files { files: "src/**/*".to_string() };
+        let files = dependency.get_files(env.project_dir()).unwrap();
+        assert_eq!(files.len(), 2);
+        assert!(files.iter().all(|file| file.ends_with(".rs")));
+    }
+
+    #[test]
+    fn test_ignored_files_are_left_out_of_artifacts() {
+        let env = TestEnv::new();
+        env.write_hint_file(r#"
+commands:
+  - pattern: "echo build"
+    artifacts:
+      - type: "directory"
+        path: "dist"
+"#);
+        env.write_file(".cacherignore", IGNORE);
+        env.write_file("dist/app.js", "app");
+        env.write_file("dist/keep.map", "kept");
+        env.write_file("dist/app.js.map", "junk");
+        env.write_file("dist/cache/chunk.js", "junk");
+
+        let mut cache = env.cache();
+        cache.execute_and_cache_with_artifacts("echo build", None, false).unwrap();
+
+        let dist = env.project_dir().join("dist");
+        fs::remove_dir_all(&dist).unwrap();
+        cache.execute_and_cache_with_artifacts("echo build", None, false).unwrap();
+        assert_eq!(fs::read_to_string(dist.join("app.js")).unwrap(), "app");
+        assert_eq!(fs::read_to_string(dist.join("keep.map")).unwrap(), "kept");
+        assert!(!dist.join("app.js.map").exists());
+        assert!(!dist.join("cache").exists());
+
+        // New junk doesn't make the restored directory differ from the cached one
+        env.write_file("dist/cache/other.js", "junk");
+        cache.execute_and_cache_with_artifacts("echo build", None, false).unwrap();
+        assert!(dist.join("cache/other.js").exists());
+    }
+}
@@ -0,0 +1,103 @@
+#[cfg(test)]
+mod tests {
+    use cacher::chain::{self, Separator};
+    use cacher::testing::TestEnv;
+    use std::time::Duration;
+
+    fn commands(command: &str) -> Vec<String> {
+        chain::split(command).into_iter().map(|part| part.command).collect()
+    }
+
+    #[test]
+    fn test_split_at_top_level_separators() {
+        let parts = chain::split("npm ci && npm run build; echo done");
+        assert_eq!(parts.len(), 3);
+        assert_eq!(parts[0].separator, None);
+        assert_eq!(parts[1].separator, Some(Separator::And));
+        assert_eq!(parts[2].separator, Some(Separator::Then));
+
+        assert_eq!(commands("echo 'a && b'; echo \"c; d\""), vec!["echo 'a && b'", "echo \"c; d\""]);
+        assert_eq!(commands("(cd x && make) && echo $(a; b)"), vec!["(cd x && make)", "echo $(a; b)"]);
+        assert_eq!(commands(r"echo a \; b && echo c # d && e"), vec![r"echo a \; b", "echo c # d && e"]);
+
+        // Anything it can't split safely stays whole
+        assert_eq!(commands("echo single"), vec!["echo single"]);
+        assert_eq!(commands("a && b || c"), vec!["a && b || c"]);
+        assert_eq!(commands("for f in *; do echo $f; done"), vec!["for f in *; do echo $f; done"]);
+        assert_eq!(commands("make && echo done"), vec!["make", "echo done"]);
+        assert_eq!(commands("echo 'unterminated && x"), vec!["echo 'unterminated && x"]);
+    }
+
+    #[test]
+    fn test_shell_mode_records_parts() {
+        let env = TestEnv::new();
+        env.write_hint_file("default:\n  shell: true\n");
+        env.write_file("sub/file.txt", "inside\n");
+        let mut cache = env.cache();
+
+        // The parts share one shell, so `cd` carries over
+        let command = "sleep 0.2 && cd sub && cat file.txt; false && echo never; echo after";
+        assert_eq!(cache.execute_and_cache(command, None, false).unwrap(), "inside\nafter\n");
+
+        let (_, metadata) = cache.load_entry(command).unwrap().unwrap();
+        let parts = metadata.parts;
+        assert_eq!(parts.len(), 6);
+        assert!(parts[0].duration().unwrap() >= Duration::from_millis(150));
+        assert_eq!(parts[2].exit_code, Some(0));
+        assert_eq!(parts[3].exit_code, Some(1));
+        assert_eq!(parts[4].exit_code, None);
+        assert_eq!(parts[5].exit_code, Some(0));
+        assert_eq!(parts[2].id, Some(cache.generate_id("cat file.txt")));
+
+        // A failing chain fails the command, like in the shell
+        assert!(cache.execute_and_cache("true && false", None, false).is_err());
+    }
+
+    #[test]
+    fn test_part_dependencies_invalidate_the_chain() {
+        let env = TestEnv::new();
+        env.write_hint_file(r#"
+commands:
+  - pattern: "*&&*"
+    shell: true
+  - pattern: "cat dep.txt"
+    depends_on:
+      - lines:
+          file: dep.txt
+          pattern: ".*"
+"#);
+        env.write_file("dep.txt", "v1");
+        let mut cache = env.cache();
+        let command = "echo start && cat dep.txt";
+
+        assert_eq!(cache.execute_and_cache(command, None, false).unwrap(), "start\nv1");
+        let explanation = cache.explain(command).unwrap();
+        assert!(explanation.cached.is_some());
+        assert_eq!(explanation.inputs.dependencies, vec!["dep.txt".to_string()]);
+        assert!(explanation.parts.iter().all(|part| !part.changed));
+        assert_eq!(explanation.parts[1].inputs.pattern.as_deref(), Some("cat dep.txt"));
+
+        env.write_file("dep.txt", "v2");
+        let explanation = cache.explain(command).unwrap();
+        assert!(explanation.cached.is_none());
+        assert!(explanation.previous.is_some());
+        assert!(!explanation.parts[0].changed);
+        assert!(explanation.parts[1].changed);
+        assert!(explanation.parts[1].part.exit_code.is_some());
+
+        assert_eq!(env.cache().execute_and_cache(command, None, false).unwrap(), "start\nv2");
+    }
+
+    #[test]
+    fn test_chains_without_shell_mode_are_not_split() {
+        let env = TestEnv::new();
+        let mut cache = env.cache();
+
+        assert!(cache.chain_parts("echo a && echo b").is_none());
+        assert_eq!(cache.execute_and_cache("echo a && echo b", None, false).unwrap(), "a && echo b\n");
+
+        cache.set_shell(true);
+        assert_eq!(cache.chain_parts("echo a && echo b").unwrap().len(), 2);
+        assert!(cache.explain("echo a && echo b").unwrap().shell);
+    }
+}
@@ -0,0 +1,63 @@
+#[cfg(test)]
+mod tests {
+    use cacher::CommandOutput;
+    use cacher::testing::TestEnv;
+    use cacher::diff::CommandDiff;
+
+    fn output(stdout: &str, stderr: &str, exit_code: i32) -> CommandOutput {
+        CommandOutput {
+            stdout: stdout.to_string(),
+            stderr: stderr.to_string(),
+            exit_code,
+            ..CommandOutput::default()
+        }
+    }
+
+    #[test]
+    fn test_identical_outputs() {
+        let cached = output("a\nb\n", "", 0);
+        let diff = CommandDiff::between(&cached, &cached.clone());
+        
+        assert!(diff.is_identical());
+    }
+
+    #[test]
+    fn test_changed_stdout_stderr_and_exit_code() {
+        let cached = output("a\nb\nc\n", "warning\n", 0);
+        let fresh = output("a\nB\nc\n", "", 3);
+        let diff = CommandDiff::between(&cached, &fresh);
+        
+        assert!(!diff.is_identical());
+        
+        let stdout_diff = diff.stdout.unwrap();
+        assert!(stdout_diff.contains("--- cached/stdout"));
+        assert!(stdout_diff.contains("+++ fresh/stdout"));
+        assert!(stdout_diff.contains("-b\n"));
+        assert!(stdout_diff.contains("+B\n"));
+        
+        assert!(diff.stderr.unwrap().contains("-warning"));
+        assert_eq!(diff.exit_code, Some((0, 3)));
+    }
+
+    #[test]
+    fn test_diff_with_cache() {
+        let env = TestEnv::new();
+        let cache = env.cache();
+        let command = "echo diff_with_cache_test";
+        
+        // No entry yet
+        assert!(cache.diff_with_cache("echo diff_with_cache_missing").unwrap().is_none());
+        
+        // A cached result that matches a fresh run
+        cache.save_output_to_disk(command, &output("diff_with_cache_test\n", "", 0)).unwrap();
+        assert!(cache.diff_with_cache(command).unwrap().unwrap().is_identical());
+        
+        // A cached result that has drifted
+        cache.save_output_to_disk(command, &output("stale\n", "", 0)).unwrap();
+        let diff = cache.diff_with_cache(command).unwrap().unwrap();
+        assert!(diff.stdout.unwrap().contains("+diff_with_cache_test"));
+        
+        // Diffing doesn't update the cache
+        assert_eq!(cache.load_from_disk(command).unwrap(), Some("stale\n".to_string()));
+    }
+}
@@ -0,0 +1,64 @@
+#[cfg(test)]
+mod tests {
+    use std::io::ErrorKind;
+    use std::time::{Duration, Instant};
+    use cacher::{CommandKilled, KillReason};
+    use cacher::testing::TestEnv;
+    use cacher::process::{run_with_limits, shell_command, Limits};
+
+    fn killed(err: &std::io::Error) -> &CommandKilled {
+        err.get_ref().and_then(|e| e.downcast_ref::<CommandKilled>()).expect("expected CommandKilled")
+    }
+
+    #[test]
+    fn test_process_timeout_keeps_partial_output() {
+        let limits = Limits { timeout: Some(Duration::from_millis(500)), max_output_bytes: None };
+        let output = run_with_limits(&mut shell_command("echo started; sleep 10"), limits).unwrap();
+        
+        assert!(output.timed_out);
+        assert!(output.status.is_none());
+        assert_eq!(String::from_utf8_lossy(&output.stdout), "started\n");
+    }
+
+    #[test]
+    fn test_process_output_limit() {
+        let limits = Limits { timeout: None, max_output_bytes: Some(1000) };
+        let output = run_with_limits(&mut shell_command("yes"), limits).unwrap();
+        
+        assert!(output.output_limit_exceeded);
+        assert_eq!(output.stdout.len(), 1000);
+    }
+
+    #[test]
+    fn test_timed_out_command_is_not_cached() {
+        let env = TestEnv::new();
+        let mut cache = env.cache();
+        let command = "sleep 10";
+        cache.set_timeout(Some(Duration::from_millis(500)));
+        
+        let started = Instant::now();
+        let err = cache.execute_and_cache(command, None, true).unwrap_err();
+        
+        assert!(started.elapsed() < Duration::from_secs(5));
+        assert_eq!(err.kind(), ErrorKind::TimedOut);
+        assert_eq!(killed(&err).reason, KillReason::Timeout(Duration::from_millis(500)));
+        assert!(cache.get(command).is_none());
+        assert!(cache.load_entry(command).unwrap().is_none());
+    }
+
+    #[test]
+    fn test_runaway_output_is_not_cached() {
+        let env = TestEnv::new();
+        let mut cache = env.cache();
+        let command = "yes runaway_output_test";
+        cache.set_max_output_bytes(Some(4096));
+        
+        let err = cache.execute_and_cache(command, None, true).unwrap_err();
+        let killed = killed(&err);
+        
+        assert_eq!(killed.reason, KillReason::OutputLimit(4096));
+        assert!(killed.partial_output.stdout.starts_with("runaway_output_test\n"));
+        assert!(killed.partial_output.stdout.len() <= 4096);
+        assert!(cache.load_entry(command).unwrap().is_none());
+    }
+}
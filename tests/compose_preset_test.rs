@@ -0,0 +1,65 @@
+#[cfg(test)]
+mod tests {
+    use std::fs;
+    use tempfile::TempDir;
+    use cacher::hint_file::{Dependency, HintFile};
+
+    #[test]
+    fn test_docker_compose_preset() {
+        let temp_dir = TempDir::new().unwrap();
+        let hint_path = temp_dir.path().join(".cacher");
+        
+        // The project's own pattern comes before the preset's
+        let hint_file_content = r#"
+presets:
+  - docker_compose
+
+commands:
+  - pattern: "docker compose ps --all"
+    ttl: 60
+"#;
+        fs::write(&hint_path, hint_file_content).unwrap();
+        let hint_file = HintFile::from_file(&hint_path).unwrap();
+        
+        assert_eq!(hint_file.find_matching_command("docker compose ps --all").unwrap().ttl, Some(60));
+        
+        let ps = hint_file.find_matching_command("docker compose ps").unwrap();
+        assert_eq!(ps.ttl, Some(5));
+        assert!(ps.include_env.contains("COMPOSE_PROJECT_NAME"));
+        assert!(matches!(ps.depends_on[0], Dependency::ComposeFile { .. }));
+        
+        assert!(hint_file.find_matching_command("docker-compose logs --tail 50 web").is_some());
+        assert!(hint_file.find_matching_command("docker compose config --services").is_some());
+        
+        // Commands with side effects are left alone
+        assert!(hint_file.find_matching_command("docker compose up -d").is_none());
+        assert!(hint_file.find_matching_command("docker compose logs -f").is_none());
+    }
+
+    #[test]
+    fn test_compose_file_dependency() {
+        let temp_dir = TempDir::new().unwrap();
+        let temp_path = temp_dir.path();
+        let dependency = Dependency::ComposeFile { compose_file: "auto".to_string() };
+        
+        std::env::remove_var("COMPOSE_FILE");
+        std::env::remove_var("COMPOSE_PROJECT_NAME");
+        
+        fs::write(temp_path.join("compose.yaml"), "services:\n  web:\n    image: nginx\n").unwrap();
+        assert_eq!(dependency.get_files(temp_path).unwrap(), vec!["compose.yaml"]);
+        let initial_hash = dependency.get_content_hash(temp_path).unwrap();
+        
+        // An override file is picked up alongside the compose file
+        fs::write(temp_path.join("compose.override.yaml"), "services:\n  web:\n    ports: [\"8080:80\"]\n").unwrap();
+        assert_eq!(dependency.get_files(temp_path).unwrap(), vec!["compose.yaml", "compose.override.yaml"]);
+        let override_hash = dependency.get_content_hash(temp_path).unwrap();
+        assert_ne!(initial_hash, override_hash);
+        
+        // Another project name addresses different containers
+        std::env::set_var("COMPOSE_PROJECT_NAME", "staging");
+        assert_ne!(override_hash, dependency.get_content_hash(temp_path).unwrap());
+        std::env::remove_var("COMPOSE_PROJECT_NAME");
+        
+        assert_eq!(override_hash, dependency.get_content_hash(temp_path).unwrap());
+    }
+}
@@ -0,0 +1,96 @@
+#[cfg(test)]
+mod tests {
+    use cacher::clock::ManualClock;
+    use cacher::testing::TestEnv;
+    use std::fs;
+    use std::sync::Arc;
+    use std::time::{Duration, SystemTime};
+
+    #[test]
+    fn test_partial_entries_are_misses() {
+        let env = TestEnv::new();
+        let mut cache = env.cache();
+
+        cache.execute_and_cache("echo complete", None, false).unwrap();
+        let entry_dir = cache.get_cache_path(&cache.generate_id("echo complete"));
+        assert!(entry_dir.join("complete").exists());
+
+        // A truncated stdout no longer matches the checksum in the marker
+        fs::write(entry_dir.join("stdout"), "comp").unwrap();
+        assert!(env.cache().load_entry("echo complete").unwrap().is_none());
+
+        // Neither is an entry that never got its marker
+        cache.execute_and_cache("echo complete", None, true).unwrap();
+        fs::remove_file(entry_dir.join("complete")).unwrap();
+        assert!(env.cache().get_cached("echo complete", None).is_none());
+        assert_eq!(env.cache().execute_and_cache("echo complete", None, false).unwrap(), "complete\n");
+    }
+
+    #[test]
+    fn test_saves_leave_no_staging_directories() {
+        let env = TestEnv::new();
+        let mut cache = env.cache();
+
+        cache.execute_and_cache("echo one", None, false).unwrap();
+        cache.execute_and_cache("echo one", None, true).unwrap();
+
+        // Only the entry is left; the search index lives in plain files next to it
+        let names: Vec<String> = fs::read_dir(env.cache_dir()).unwrap()
+            .map(|entry| entry.unwrap())
+            .filter(|entry| entry.path().is_dir())
+            .map(|entry| entry.file_name().to_string_lossy().into_owned())
+            .collect();
+        assert_eq!(names, vec![cache.generate_id("echo one")]);
+    }
+
+    #[test]
+    fn test_gc_removes_orphaned_entries() {
+        let env = TestEnv::new();
+        let clock = ManualClock::new(SystemTime::now());
+        let mut cache = env.cache();
+        cache.set_clock(Arc::new(clock.clone()));
+
+        cache.execute_and_cache("echo kept", None, false).unwrap();
+        cache.execute_and_cache("echo truncated", None, false).unwrap();
+        let truncated = cache.get_cache_path(&cache.generate_id("echo truncated"));
+        fs::write(truncated.join("stdout"), "").unwrap();
+
+        // What an interrupted save leaves behind
+        let staging = env.cache_dir().join(".staging-interrupted");
+        fs::create_dir(&staging).unwrap();
+        fs::write(staging.join("stdout"), "partial").unwrap();
+        let no_marker = env.cache_dir().join("0123abcd");
+        fs::create_dir(&no_marker).unwrap();
+        fs::write(no_marker.join("stdout"), "partial").unwrap();
+
+        // Staging directories might still be in use by a running save
+        assert_eq!(cache.gc().unwrap(), 2);
+        assert!(staging.exists());
+        assert!(!truncated.exists());
+        assert!(!no_marker.exists());
+
+        clock.advance(Duration::from_secs(2 * 3600));
+        assert_eq!(cache.gc().unwrap(), 1);
+        assert!(!staging.exists());
+
+        assert_eq!(cache.gc().unwrap(), 0);
+        assert_eq!(cache.get_cached("echo kept", None).unwrap(), "kept\n");
+    }
+
+    #[test]
+    fn test_clear_all_leaves_staging_and_queue_alone() {
+        let env = TestEnv::new();
+        let mut cache = env.cache();
+        cache.execute_and_cache("echo one", None, false).unwrap();
+
+        let staging = env.cache_dir().join(".staging-in-flight");
+        let queue = env.cache_dir().join(".upload-queue");
+        fs::create_dir(&staging).unwrap();
+        fs::create_dir(&queue).unwrap();
+
+        cache.clear_cache(None).unwrap();
+        assert!(!cache.entry_dir(&cache.generate_id("echo one")).exists());
+        assert!(staging.exists());
+        assert!(queue.exists());
+    }
+}
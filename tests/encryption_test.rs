@@ -0,0 +1,142 @@
+#[cfg(test)]
+mod tests {
+    use std::fs;
+    use std::thread;
+    use cacher::testing::TestEnv;
+    use cacher::crypto::{self, Cipher};
+
+    #[test]
+    fn test_cipher_round_trip() {
+        let salt = b"cacher-test-salt";
+        let cipher = Cipher::from_passphrase("correct horse", salt).unwrap();
+        
+        let encrypted = cipher.encrypt(b"AWS_SECRET=abc123", "entry/stdout").unwrap();
+        assert!(crypto::is_encrypted(&encrypted));
+        assert!(!encrypted.windows(6).any(|w| w == b"abc123"));
+        assert_eq!(cipher.decrypt(&encrypted, "entry/stdout").unwrap(), b"AWS_SECRET=abc123");
+        
+        // The same passphrase and salt derive the same key
+        let same = Cipher::from_passphrase("correct horse", salt).unwrap();
+        assert!(same.decrypt(&encrypted, "entry/stdout").is_ok());
+        
+        // Another key, or moving the file to another entry, fails to decrypt
+        let other = Cipher::from_passphrase("battery staple", salt).unwrap();
+        assert!(other.decrypt(&encrypted, "entry/stdout").is_err());
+        assert!(cipher.decrypt(&encrypted, "other/stdout").is_err());
+    }
+
+    #[test]
+    fn test_encrypted_entries() {
+        // Create an isolated cache and project directory for the test
+        let env = TestEnv::new();
+        let temp_path = env.project_dir();
+        
+        let secrets_dir = temp_path.join("secrets");
+        fs::create_dir_all(&secrets_dir).unwrap();
+        fs::write(secrets_dir.join("token.txt"), "token-contents").unwrap();
+        
+        // Create a .cacher file that encrypts one command's entries
+        let hint_file_content = r#"
+commands:
+  - pattern: "echo encrypted_secret"
+    encrypt: true
+    artifacts:
+      - type: "directory"
+        path: "secrets"
+"#;
+        
+        fs::write(temp_path.join(".cacher"), hint_file_content).unwrap();
+        
+        let command = "echo encrypted_secret";
+        let mut cache = env.cache();
+        cache.set_cipher(Cipher::from_key([7; 32]));
+        assert!(cache.encryption_enabled(command));
+        assert!(!cache.encryption_enabled("echo plain"));
+        
+        let output = cache.execute_and_cache_with_artifacts(command, None, true).unwrap();
+        assert_eq!(output.trim(), "encrypted_secret");
+        
+        // Nothing is stored in plaintext
+        let id = cache.generate_id(command);
+        let stored = fs::read(cache.get_stdout_path(&id)).unwrap();
+        assert!(crypto::is_encrypted(&stored));
+        let archive = fs::read(cache.get_cache_path(&id).join("artifacts/directory.tar.gz")).unwrap();
+        assert!(crypto::is_encrypted(&archive));
+        
+        // A cache with the same key decrypts hits and restores artifacts
+        fs::remove_dir_all(&secrets_dir).unwrap();
+        let mut same_key = env.cache();
+        same_key.set_cipher(Cipher::from_key([7; 32]));
+        let output = same_key.execute_and_cache_with_artifacts(command, None, false).unwrap();
+        assert_eq!(output.trim(), "encrypted_secret");
+        assert_eq!(fs::read_to_string(secrets_dir.join("token.txt")).unwrap(), "token-contents");
+        
+        // With another key the entry is unreadable, so it counts as a miss
+        let mut other_key = env.cache();
+        other_key.set_cipher(Cipher::from_key([8; 32]));
+        assert_eq!(other_key.get_cached(command, None), None);
+    }
+
+    #[test]
+    fn test_salt_is_created_once() {
+        let mut env = TestEnv::new();
+        env.set_env(crypto::PASSPHRASE_ENV_VAR, "correct horse");
+        let cache_dir = env.cache_dir().join("fresh");
+
+        // Processes starting at the same time all end up with the same key
+        let threads: Vec<_> = (0..8).map(|_| {
+            let cache_dir = cache_dir.clone();
+            thread::spawn(move || Cipher::from_environment(&cache_dir).unwrap())
+        }).collect();
+        let ciphers: Vec<Cipher> = threads.into_iter().map(|thread| thread.join().unwrap()).collect();
+        let encrypted = ciphers[0].encrypt(b"secret", "entry/stdout").unwrap();
+        for cipher in &ciphers {
+            assert_eq!(cipher.decrypt(&encrypted, "entry/stdout").unwrap(), b"secret");
+        }
+        assert_eq!(fs::read_dir(&cache_dir).unwrap().count(), 1);
+
+        // A damaged salt is reported instead of silently replaced
+        let salt_path = cache_dir.join("encryption.salt");
+        fs::write(&salt_path, b"short").unwrap();
+        let err = Cipher::from_environment(&cache_dir).err().unwrap();
+        assert!(err.to_string().contains("malformed"));
+        assert_eq!(fs::read(&salt_path).unwrap(), b"short");
+    }
+
+    #[test]
+    fn test_plaintext_that_looks_encrypted() {
+        let env = TestEnv::new();
+        let mut cache = env.cache();
+
+        // Only the entry's metadata says whether it is encrypted, not its contents
+        assert_eq!(cache.execute_and_cache("echo CACHERENC1 plain", None, false).unwrap(), "CACHERENC1 plain\n");
+        let (output, metadata) = env.cache().load_entry("echo CACHERENC1 plain").unwrap().unwrap();
+        assert_eq!(output.stdout, "CACHERENC1 plain\n");
+        assert_eq!(metadata.encrypted, Some(false));
+        assert_eq!(env.cache().get_cached("echo CACHERENC1 plain", None).unwrap(), "CACHERENC1 plain\n");
+    }
+
+    #[test]
+    fn test_history_follows_the_entry_encryption() {
+        let env = TestEnv::new();
+        env.write_file("data.txt", "one\n");
+        env.write_hint_file("commands:\n  - pattern: \"cat *\"\n    history: 3\n");
+        env.cache().execute_and_cache("cat data.txt", None, false).unwrap();
+        env.write_file("data.txt", "two\n");
+        env.cache().execute_and_cache("cat data.txt", None, true).unwrap();
+
+        // Turning encryption on re-encrypts the older generations with the entry
+        env.write_hint_file("commands:\n  - pattern: \"cat *\"\n    history: 3\n    encrypt: true\n");
+        env.write_file("data.txt", "three\n");
+        let mut cache = env.cache();
+        cache.set_cipher(Cipher::from_key([7; 32]));
+        cache.execute_and_cache("cat data.txt", None, true).unwrap();
+
+        let history: Vec<String> = cache.history("cat data.txt").unwrap().into_iter().map(|entry| entry.stdout).collect();
+        assert_eq!(history, ["two\n", "one\n"]);
+        let id = cache.generate_id("cat data.txt");
+        for file in fs::read_dir(cache.entry_dir(&id).join("history")).unwrap().flatten() {
+            assert!(crypto::is_encrypted(&fs::read(file.path()).unwrap()));
+        }
+    }
+}
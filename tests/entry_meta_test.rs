@@ -0,0 +1,89 @@
+#[cfg(test)]
+mod tests {
+    use cacher::index::FindQuery;
+    use cacher::metadata::{parse_meta_pair, MetaFilter};
+    use cacher::testing::TestEnv;
+    use cacher::ListOptions;
+    use std::collections::BTreeMap;
+
+    const HINTS: &str = r#"
+default:
+  meta:
+    team: infra
+    stage: dev
+commands:
+  - pattern: "echo release*"
+    meta:
+      stage: release
+"#;
+
+    fn meta(pairs: &[(&str, &str)]) -> BTreeMap<String, String> {
+        pairs.iter().map(|(key, value)| (key.to_string(), value.to_string())).collect()
+    }
+
+    fn filter(s: &str) -> MetaFilter {
+        s.parse().unwrap()
+    }
+
+    #[test]
+    fn test_meta_is_recorded() {
+        let env = TestEnv::new();
+        env.write_hint_file(HINTS);
+        let mut cache = env.cache();
+
+        cache.execute_and_cache("echo build", None, false).unwrap();
+        let (_, metadata) = cache.load_entry("echo build").unwrap().unwrap();
+        assert_eq!(metadata.meta, meta(&[("stage", "dev"), ("team", "infra")]));
+
+        // Pairs set on the cache take precedence over the command's, which take precedence over the defaults
+        cache.set_meta(meta(&[("build", "1234"), ("team", "web")]));
+        cache.execute_and_cache("echo release 1", None, false).unwrap();
+        let (_, metadata) = cache.load_entry("echo release 1").unwrap().unwrap();
+        assert_eq!(metadata.meta, meta(&[("build", "1234"), ("stage", "release"), ("team", "web")]));
+
+        // Meta doesn't change the key
+        let id = cache.generate_id("echo build");
+        cache.set_meta(meta(&[("build", "5678")]));
+        assert_eq!(cache.generate_id("echo build"), id);
+    }
+
+    #[test]
+    fn test_list_and_find_by_meta() {
+        let env = TestEnv::new();
+        env.write_hint_file(HINTS);
+        let mut cache = env.cache();
+
+        cache.set_meta(meta(&[("ticket", "PROJ-12")]));
+        cache.execute_and_cache("echo build", None, false).unwrap();
+        cache.set_meta(meta(&[("ticket", "OPS-3")]));
+        cache.execute_and_cache("echo release 1", None, false).unwrap();
+
+        let listed = |filters: Vec<MetaFilter>| -> Vec<String> {
+            let options = ListOptions { meta: filters, ..ListOptions::default() };
+            let mut commands: Vec<String> = cache.list_cached_commands(&options).unwrap()
+                .into_iter()
+                .map(|entry| entry.command)
+                .collect();
+            commands.sort();
+            commands
+        };
+        assert_eq!(listed(vec![filter("ticket=PROJ-*")]), vec!["echo build"]);
+        assert_eq!(listed(vec![filter("team=infra")]), vec!["echo build", "echo release 1"]);
+        assert_eq!(listed(vec![filter("team=infra"), filter("stage=release")]), vec!["echo release 1"]);
+        assert!(listed(vec![filter("build=1")]).is_empty());
+
+        let found = cache.find(&FindQuery { meta: vec![filter("ticket=OPS-3")], ..FindQuery::default() }).unwrap();
+        assert_eq!(found.len(), 1);
+        assert_eq!(found[0].command, "echo release 1");
+        assert_eq!(found[0].meta["ticket"], "OPS-3");
+    }
+
+    #[test]
+    fn test_parse_meta() {
+        assert_eq!(parse_meta_pair("sha=ab=cd"), Ok(("sha".to_string(), "ab=cd".to_string())));
+        assert_eq!(parse_meta_pair("empty="), Ok(("empty".to_string(), String::new())));
+        assert!(parse_meta_pair("build").is_err());
+        assert!(parse_meta_pair("=1").is_err());
+        assert!("ticket=[".parse::<MetaFilter>().is_err());
+    }
+}
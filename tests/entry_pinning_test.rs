@@ -0,0 +1,101 @@
+#[cfg(test)]
+mod tests {
+    use cacher::clock::ManualClock;
+    use cacher::testing::TestEnv;
+    use cacher::ListOptions;
+    use std::sync::Arc;
+    use std::time::Duration;
+
+    #[test]
+    fn test_hits_are_counted() {
+        let env = TestEnv::new();
+        let mut cache = env.cache();
+
+        cache.execute_and_cache("echo counted", None, false).unwrap();
+        cache.execute_and_cache("echo counted", None, false).unwrap();
+
+        // A fresh instance has to read the entry from disk
+        let mut other = env.cache();
+        other.execute_and_cache("echo counted", None, false).unwrap();
+
+        let entries = cache.list_cached_commands(&ListOptions::default()).unwrap();
+        assert_eq!(entries.len(), 1);
+        assert_eq!(entries[0].command, "echo counted");
+        assert_eq!(entries[0].hit_count, 2);
+        assert_eq!(entries[0].id, cache.generate_id("echo counted"));
+        assert!(entries[0].size > 0);
+        assert!(!entries[0].pinned);
+    }
+
+    #[test]
+    fn test_pinned_entries_ignore_ttl() {
+        let env = TestEnv::new();
+        let clock = ManualClock::default();
+        let mut cache = env.cache();
+        cache.set_clock(Arc::new(clock.clone()));
+
+        let ttl = Some(Duration::from_secs(10));
+        cache.execute_and_cache("date +%s%N", ttl, false).unwrap();
+        let id = cache.generate_id("date +%s%N");
+        cache.set_pinned(&id, true).unwrap();
+
+        clock.advance(Duration::from_secs(60));
+        assert!(cache.get_cached("date +%s%N", ttl).is_some());
+
+        // A maximum age still applies to pinned entries
+        cache.set_max_age(Some(Duration::from_secs(30)));
+        assert!(cache.get_cached("date +%s%N", ttl).is_none());
+        cache.set_max_age(None);
+
+        // Force refreshing keeps the pin
+        cache.execute_and_cache("date +%s%N", ttl, true).unwrap();
+        assert!(cache.is_pinned("date +%s%N"));
+
+        cache.set_pinned(&id, false).unwrap();
+        clock.advance(Duration::from_secs(60));
+        assert!(cache.get_cached("date +%s%N", ttl).is_none());
+
+        // Pinning an unknown entry fails without leaving a directory behind
+        assert!(cache.set_pinned("unknown", true).is_err());
+        assert!(!cache.is_pinned("echo never cached"));
+        assert!(!cache.entry_dir("unknown").exists());
+        assert!(!cache.entry_dir(&cache.generate_id("echo never cached")).exists());
+    }
+
+    #[test]
+    fn test_clear_all_keeps_pinned_entries() {
+        let env = TestEnv::new();
+        let mut cache = env.cache();
+
+        cache.execute_and_cache("echo pinned", None, false).unwrap();
+        cache.execute_and_cache("echo unpinned", None, false).unwrap();
+        cache.set_pinned(&cache.generate_id("echo pinned"), true).unwrap();
+
+        cache.clear_cache(None).unwrap();
+
+        let commands: Vec<String> = cache.list_cached_commands(&ListOptions::default()).unwrap().into_iter().map(|e| e.command).collect();
+        assert_eq!(commands, vec!["echo pinned".to_string()]);
+        assert!(cache.get_cached("echo unpinned", None).is_none());
+
+        // Pinned entries can still be deleted individually
+        cache.remove_entry(&cache.generate_id("echo pinned")).unwrap();
+        assert!(cache.list_cached_commands(&ListOptions::default()).unwrap().is_empty());
+        assert!(cache.get_cached("echo pinned", None).is_none());
+    }
+
+    #[test]
+    fn test_refresh_entry() {
+        let env = TestEnv::new();
+        let path = env.write_file("value.txt", "old");
+        let mut cache = env.cache();
+
+        cache.execute_and_cache("cat value.txt", None, false).unwrap();
+        std::fs::write(path, "new").unwrap();
+
+        let id = cache.generate_id("cat value.txt");
+        assert_eq!(cache.refresh_entry(&id).unwrap(), "new");
+        assert_eq!(env.cache().get_cached("cat value.txt", None).unwrap(), "new");
+
+        assert!(cache.refresh_entry("missing").is_err());
+    }
+}
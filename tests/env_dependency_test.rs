@@ -0,0 +1,60 @@
+#[cfg(test)]
+mod tests {
+    use std::fs;
+    use std::io::ErrorKind;
+    use cacher::testing::TestEnv;
+
+    #[test]
+    fn test_env_presence_and_required_env() {
+        // Create an isolated cache and project directory for the test
+        let mut env = TestEnv::new();
+        let temp_path = env.project_dir().to_path_buf();
+        
+        // Create a .cacher file that keys on one variable and requires another
+        let hint_file_content = r#"
+commands:
+  - pattern: "echo *"
+    include_env:
+      - CACHER_TEST_OPTIONAL_VAR
+    require_env:
+      - CACHER_TEST_REQUIRED_VAR
+
+  - pattern: "date*"
+    include_locale: true
+"#;
+        
+        fs::write(temp_path.join(".cacher"), hint_file_content).unwrap();
+        
+        let command = "echo env_presence";
+        
+        // PART 1: An unset variable and one set to an empty string produce different keys
+        env.remove_env("CACHER_TEST_OPTIONAL_VAR");
+        let cache = env.cache();
+        let unset_hash = cache.generate_id(command);
+        
+        env.set_env("CACHER_TEST_OPTIONAL_VAR", "");
+        let empty_hash = cache.generate_id(command);
+        
+        assert_ne!(unset_hash, empty_hash, "Hash should change when a variable goes from unset to set");
+        env.remove_env("CACHER_TEST_OPTIONAL_VAR");
+        
+        // PART 2: Running without a required variable fails before executing
+        env.remove_env("CACHER_TEST_REQUIRED_VAR");
+        let mut cache = env.cache();
+        let err = cache.execute_and_cache(command, None, true).unwrap_err();
+        assert_eq!(err.kind(), ErrorKind::NotFound);
+        assert!(err.to_string().contains("CACHER_TEST_REQUIRED_VAR"));
+        
+        // PART 3: Once the variable is set the command runs
+        env.set_env("CACHER_TEST_REQUIRED_VAR", "1");
+        let output = cache.execute_and_cache(command, None, true).unwrap();
+        assert_eq!(output.trim(), "env_presence");
+        
+        // PART 4: Locale-sensitive commands are keyed on the time zone
+        env.set_env("TZ", "UTC");
+        let utc_hash = cache.generate_id("date");
+        env.set_env("TZ", "Asia/Tokyo");
+        let tokyo_hash = cache.generate_id("date");
+        assert_ne!(utc_hash, tokyo_hash, "Hash should change with TZ when include_locale is set");
+    }
+}
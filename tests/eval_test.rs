@@ -0,0 +1,96 @@
+#[cfg(test)]
+mod tests {
+    use cacher::clock::ManualClock;
+    use cacher::testing::TestEnv;
+    use cacher::ListOptions;
+    use std::sync::Arc;
+    use std::time::{Duration, SystemTime};
+
+    const HINT_FILE: &str = r#"
+interpreters:
+  sh:
+    command: ["sh", "-c"]
+    version_args: ["-c", "cat version.txt"]
+    imports: ["lib/*.sh"]
+"#;
+
+    #[test]
+    fn test_eval_caches_snippets() {
+        let env = TestEnv::new();
+        env.write_hint_file(HINT_FILE);
+        env.write_file("version.txt", "1.0");
+        env.write_file("lib/util.sh", "greeting=hello");
+        let mut cache = env.cache();
+
+        let snippet = ". ./lib/util.sh; echo $greeting $(date +%s%N)";
+        let first = cache.eval("sh", snippet, &[], None, false).unwrap();
+        assert!(first.starts_with("hello "));
+        assert_eq!(cache.eval("sh", snippet, &[], None, false).unwrap(), first);
+
+        // Upgrading the interpreter invalidates the entry
+        env.write_file("version.txt", "2.0");
+        let upgraded = cache.eval("sh", snippet, &[], None, false).unwrap();
+        assert_ne!(upgraded, first);
+        assert_eq!(cache.eval("sh", snippet, &[], None, false).unwrap(), upgraded);
+
+        // So does editing an imported file
+        env.write_file("lib/util.sh", "greeting=hi");
+        assert!(cache.eval("sh", snippet, &[], None, false).unwrap().starts_with("hi "));
+
+        // The entry is listed under the snippet's command line
+        let commands: Vec<String> = cache.list_cached_commands(&ListOptions::default()).unwrap().into_iter().map(|e| e.command).collect();
+        assert!(commands.contains(&format!("sh -c {}", snippet)));
+    }
+
+    #[test]
+    fn test_eval_extra_imports_and_errors() {
+        let env = TestEnv::new();
+        env.write_hint_file(HINT_FILE);
+        env.write_file("version.txt", "1.0");
+        env.write_file("data.txt", "a");
+        let mut cache = env.cache();
+
+        let imports = vec!["*.txt".to_string()];
+        let first = cache.eval("sh", "date +%s%N", &imports, None, false).unwrap();
+        assert_eq!(cache.eval("sh", "date +%s%N", &imports, None, false).unwrap(), first);
+
+        env.write_file("data.txt", "b");
+        assert_ne!(cache.eval("sh", "date +%s%N", &imports, None, false).unwrap(), first);
+
+        // Failing snippets aren't cached, and unknown languages are rejected
+        assert!(cache.eval("sh", "exit 3", &[], None, false).is_err());
+        assert!(cache.list_cached_commands(&ListOptions::default()).unwrap().iter().all(|e| e.command != "sh -c exit 3"));
+        assert!(cache.eval("cobol", "DISPLAY 'HI'", &[], None, false).is_err());
+        assert_eq!(cache.interpreter("python").unwrap().command, vec!["python3", "-c"]);
+    }
+
+    #[test]
+    fn test_eval_keys_and_pins() {
+        let env = TestEnv::new();
+        env.write_hint_file(HINT_FILE);
+        env.write_file("version.txt", "1.0");
+        let clock = ManualClock::new(SystemTime::now());
+        let mut cache = env.cache();
+        cache.set_clock(Arc::new(clock.clone()));
+
+        // Moving text from the snippet to the version output makes a different key
+        let first = cache.eval("sh", "true; date +%s%N", &[], None, false).unwrap();
+        env.write_file("version.txt", "1.0t");
+        assert_ne!(cache.eval("sh", "rue; date +%s%N", &[], None, false).unwrap(), first);
+        env.write_file("version.txt", "1.0");
+
+        // Pins on eval entries are honored
+        let ttl = Some(Duration::from_secs(10));
+        let pinned = cache.eval("sh", "date +%s%N", &[], ttl, false).unwrap();
+        let entry = cache.list_cached_commands(&ListOptions::default()).unwrap()
+            .into_iter()
+            .find(|entry| entry.command == "sh -c date +%s%N")
+            .unwrap();
+        cache.set_pinned(&entry.id, true).unwrap();
+        clock.advance(Duration::from_secs(60));
+        assert_eq!(cache.eval("sh", "date +%s%N", &[], ttl, false).unwrap(), pinned);
+
+        // Checking the pin leaves nothing under the command line's own ID
+        assert!(!cache.entry_dir(&cache.generate_id("sh -c date +%s%N")).exists());
+    }
+}
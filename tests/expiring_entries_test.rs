@@ -0,0 +1,108 @@
+#[cfg(test)]
+mod tests {
+    use cacher::clock::ManualClock;
+    use cacher::testing::TestEnv;
+    use std::sync::Arc;
+    use std::time::{Duration, SystemTime};
+
+    #[test]
+    fn test_expiring_entries() {
+        let env = TestEnv::new();
+        env.write_hint_file("commands:\n  - pattern: \"echo short*\"\n    ttl: 60\n  - pattern: \"echo long*\"\n    ttl: 3600\n");
+        // Metadata timestamps have whole-second precision
+        let clock = ManualClock::new(SystemTime::UNIX_EPOCH + Duration::from_secs(1_700_000_000));
+        let mut cache = env.cache();
+        cache.set_clock(Arc::new(clock.clone()));
+
+        for command in ["echo short hot", "echo short cold", "echo long hot", "echo forever"] {
+            cache.execute_and_cache(command, None, false).unwrap();
+        }
+        for _ in 0..3 {
+            cache.get_cached("echo short hot", None).unwrap();
+            cache.get_cached("echo long hot", None).unwrap();
+        }
+        cache.set_pinned(&cache.generate_id("echo short cold"), true).unwrap();
+
+        clock.advance(Duration::from_secs(30));
+        let within = Duration::from_secs(300);
+        let commands = |expiring: Vec<cacher::ExpiringEntry>| -> Vec<String> {
+            expiring.into_iter().map(|e| e.entry.command).collect()
+        };
+
+        // Cold unpinned entries, entries expiring later and entries without a TTL are left out
+        let expiring = cache.expiring_entries(within, 3, None).unwrap();
+        assert_eq!(expiring[0].expires_in, Duration::from_secs(30));
+        let mut listed = commands(expiring);
+        listed.sort();
+        assert_eq!(listed, vec!["echo short cold", "echo short hot"]);
+
+        // Once expired, only pinned entries are still worth refreshing
+        clock.advance(Duration::from_secs(60));
+        let expiring = cache.expiring_entries(within, 3, None).unwrap();
+        assert_eq!(expiring[0].expires_in, Duration::ZERO);
+        assert_eq!(commands(expiring), vec!["echo short cold"]);
+
+        // Refreshing keeps the entry's hits, so it stays on the list next time round
+        cache.refresh_entry(&cache.generate_id("echo short hot")).unwrap();
+        assert_eq!(commands(cache.expiring_entries(Duration::from_secs(60), 3, None).unwrap()).len(), 2);
+
+        // A default TTL applies to commands without one in the hint file
+        assert!(commands(cache.expiring_entries(within, 0, Some(Duration::from_secs(100)))
+            .unwrap()).contains(&"echo forever".to_string()));
+    }
+
+    #[test]
+    fn test_refresh_policy_and_expiry_hook() {
+        let env = TestEnv::new();
+        env.write_hint_file(r#"
+default:
+  on_expiring: echo "$CACHER_HOOK $CACHER_ID $CACHER_EXPIRES_IN" >> events.txt
+commands:
+  - pattern: "echo policy*"
+    ttl: 60
+    refresh_before_expiry: 20
+  - pattern: "echo quiet*"
+    ttl: 60
+    on_expiring: "true"
+"#);
+        let clock = ManualClock::new(SystemTime::UNIX_EPOCH + Duration::from_secs(1_700_000_000));
+        let mut cache = env.cache();
+        cache.set_clock(Arc::new(clock.clone()));
+        cache.execute_and_cache("echo policy", None, false).unwrap();
+        cache.execute_and_cache("echo quiet", None, false).unwrap();
+        let id = cache.generate_id("echo policy");
+        let events = || std::fs::read_to_string(env.project_dir().join("events.txt")).unwrap_or_default();
+
+        // Not due yet, and never hit, so not listed
+        clock.advance(Duration::from_secs(30));
+        assert!(cache.expiring_entries(Duration::ZERO, 100, None).unwrap().is_empty());
+
+        // Within its refresh window the entry is listed and marked for refreshing
+        clock.advance(Duration::from_secs(15));
+        let expiring = cache.expiring_entries(Duration::ZERO, 100, None).unwrap();
+        assert_eq!(expiring.len(), 1);
+        assert_eq!(expiring[0].entry.id, id);
+        assert!(expiring[0].refresh);
+
+        // The hook runs once per entry
+        assert!(cache.notify_expiring(&expiring[0]).unwrap());
+        assert!(!cache.notify_expiring(&expiring[0]).unwrap());
+        assert_eq!(events(), format!("on_expiring {} 15\n", id));
+
+        // A refreshed entry is a new one, notified again when it nears expiry
+        cache.refresh_entry(&id).unwrap();
+        assert!(cache.expiring_entries(Duration::ZERO, 100, None).unwrap().is_empty());
+        clock.advance(Duration::from_secs(50));
+        let expiring = cache.expiring_entries(Duration::ZERO, 100, None).unwrap();
+        assert!(cache.notify_expiring(&expiring[0]).unwrap());
+        assert_eq!(events().lines().count(), 2);
+
+        // A command's own hook takes precedence over the default one
+        cache.set_pinned(&cache.generate_id("echo quiet"), true).unwrap();
+        let expiring = cache.expiring_entries(Duration::from_secs(3600), 100, None).unwrap();
+        let quiet = expiring.iter().find(|e| e.entry.command == "echo quiet").unwrap();
+        assert!(!quiet.refresh);
+        assert!(cache.notify_expiring(quiet).unwrap());
+        assert_eq!(events().lines().count(), 2);
+    }
+}
@@ -0,0 +1,88 @@
+#[cfg(test)]
+mod tests {
+    use cacher::clock::ManualClock;
+    use cacher::hint_file::HintFile;
+    use cacher::testing::TestEnv;
+    use cacher::CommandFailed;
+    use std::io;
+    use std::sync::Arc;
+    use std::time::Duration;
+
+    const SCRIPT: &str = "echo run >> runs.txt\necho output\necho oops >&2\nexit $(cat status.txt)\n";
+
+    fn failure(err: &io::Error) -> &CommandFailed {
+        err.get_ref().and_then(|e| e.downcast_ref::<CommandFailed>()).expect("not a CommandFailed")
+    }
+
+    #[test]
+    fn test_failures_are_not_cached_by_default() {
+        let env = TestEnv::new();
+        env.write_file("script.sh", SCRIPT);
+        env.write_file("status.txt", "3");
+        let mut cache = env.cache();
+
+        let err = cache.execute_and_cache("sh script.sh", None, false).unwrap_err();
+        assert_eq!(failure(&err).output.exit_code, 3);
+        assert!(!failure(&err).cached);
+        cache.execute_and_cache("sh script.sh", None, false).unwrap_err();
+        assert_eq!(env.count_lines("runs.txt"), 2);
+    }
+
+    #[test]
+    fn test_ttl_by_exit() {
+        let env = TestEnv::new();
+        env.write_hint_file("commands:\n  - pattern: \"sh script.sh\"\n    cache_failures: true\n    ttl_by_exit: { 0: 3600, nonzero: 60 }\n");
+        env.write_file("script.sh", SCRIPT);
+        env.write_file("status.txt", "3");
+        let clock = ManualClock::default();
+        let mut cache = env.cache();
+        cache.set_clock(Arc::new(clock.clone()));
+
+        cache.execute_and_cache("sh script.sh", None, false).unwrap_err();
+
+        // The failure is replayed from the cache, but isn't a hit for `get`
+        let err = cache.execute_and_cache("sh script.sh", None, false).unwrap_err();
+        assert!(failure(&err).cached);
+        assert_eq!(failure(&err).output.exit_code, 3);
+        assert_eq!(failure(&err).output.stderr, "oops\n");
+        assert_eq!(env.count_lines("runs.txt"), 1);
+        assert!(cache.get_cached("sh script.sh", None).is_none());
+
+        // Failures retry quickly
+        clock.advance(Duration::from_secs(61));
+        env.write_file("status.txt", "0");
+        assert_eq!(cache.execute_and_cache("sh script.sh", None, false).unwrap(), "output\n");
+        assert_eq!(env.count_lines("runs.txt"), 2);
+
+        // Successes live long
+        clock.advance(Duration::from_secs(600));
+        assert_eq!(env.cache().execute_and_cache("sh script.sh", None, false).unwrap(), "output\n");
+        assert_eq!(env.count_lines("runs.txt"), 2);
+        assert_eq!(cache.get_effective_ttl("sh script.sh", None), Some(Duration::from_secs(3600)));
+    }
+
+    #[test]
+    fn test_ttl_by_exit_settings() {
+        let env = TestEnv::new();
+        env.write_hint_file(r#"
+default:
+  ttl: 300
+  ttl_by_exit: { nonzero: 10 }
+commands:
+  - pattern: "make *"
+    ttl: 900
+    ttl_by_exit: { 2: 30 }
+"#);
+        let cache = env.cache();
+
+        assert_eq!(cache.get_effective_ttl_for_exit("make all", None, 0), Some(Duration::from_secs(900)));
+        assert_eq!(cache.get_effective_ttl_for_exit("make all", None, 2), Some(Duration::from_secs(30)));
+        // Other failures fall back to the command's `ttl` before the defaults
+        assert_eq!(cache.get_effective_ttl_for_exit("make all", None, 1), Some(Duration::from_secs(900)));
+        assert_eq!(cache.get_effective_ttl_for_exit("ls", None, 1), Some(Duration::from_secs(10)));
+        assert_eq!(cache.get_effective_ttl_for_exit("ls", None, 0), Some(Duration::from_secs(300)));
+
+        let invalid = env.write_hint_file("default:\n  ttl_by_exit: { failure: 10 }\n");
+        assert!(HintFile::from_file(&invalid).is_err());
+    }
+}
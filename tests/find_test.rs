@@ -0,0 +1,153 @@
+#[cfg(test)]
+mod tests {
+    use cacher::clock::ManualClock;
+    use cacher::index::{FindQuery, IndexRecord, SearchIndex};
+    use cacher::metadata::EntryInputs;
+    use cacher::testing::TestEnv;
+    use std::collections::BTreeMap;
+    use std::fs;
+    use std::process::Command;
+    use std::sync::Arc;
+    use std::time::{Duration, SystemTime};
+
+    const HINTS: &str = r#"
+commands:
+  - pattern: "npm *"
+    include_env: [TEST_ENV_VAR, NODE_ENV]
+    tags: [frontend]
+    depends_on:
+      - file: package.json
+  - pattern: "cargo *"
+    include_env: [TEST_ENV_VAR]
+    depends_on:
+      - files: "src/*.rs"
+"#;
+
+    fn find(env: &TestEnv, query: FindQuery) -> Vec<String> {
+        env.cache().find(&query).unwrap().into_iter().map(|e| e.command).collect()
+    }
+
+    #[test]
+    fn test_find_by_inputs() {
+        let env = TestEnv::new();
+        env.write_hint_file(HINTS);
+        env.write_file("package.json", "{}");
+        env.write_file("src/main.rs", "fn main() {}");
+        let cache = env.cache();
+
+        cache.save_to_disk("npm test", "ok").unwrap();
+        cache.save_to_disk("cargo test", "ok").unwrap();
+        cache.save_to_disk("echo unrelated", "ok").unwrap();
+
+        let by_env = find(&env, FindQuery { env: vec!["TEST_ENV_VAR".to_string()], ..FindQuery::default() });
+        assert_eq!(by_env.len(), 2);
+        assert!(!by_env.contains(&"echo unrelated".to_string()));
+
+        let by_dep = find(&env, FindQuery { dependencies: vec!["package.json".to_string()], ..FindQuery::default() });
+        assert_eq!(by_dep, vec!["npm test".to_string()]);
+
+        // Globs are resolved to the files they matched when the entry was saved
+        let by_file = find(&env, FindQuery { dependencies: vec!["src/main.rs".to_string()], ..FindQuery::default() });
+        assert_eq!(by_file, vec!["cargo test".to_string()]);
+        let by_name = find(&env, FindQuery { dependencies: vec!["main.rs".to_string()], ..FindQuery::default() });
+        assert_eq!(by_name, vec!["cargo test".to_string()]);
+
+        let combined = find(&env, FindQuery {
+            env: vec!["NODE_ENV".to_string()],
+            tags: vec!["frontend".to_string()],
+            pattern: Some("npm*".to_string()),
+            ..FindQuery::default()
+        });
+        assert_eq!(combined, vec!["npm test".to_string()]);
+
+        assert!(find(&env, FindQuery { tags: vec!["backend".to_string()], ..FindQuery::default() }).is_empty());
+        assert!(env.cache().find(&FindQuery { dependencies: vec!["[".to_string()], ..FindQuery::default() }).is_err());
+    }
+
+    #[test]
+    fn test_find_since() {
+        let env = TestEnv::new();
+        env.write_hint_file(HINTS);
+        let start = SystemTime::UNIX_EPOCH + Duration::from_secs(1_700_000_000);
+        let clock = ManualClock::new(start);
+        let mut cache = env.cache();
+        cache.set_clock(Arc::new(clock.clone()));
+
+        cache.save_to_disk("npm old", "ok").unwrap();
+        clock.advance(Duration::from_secs(3 * 86400));
+        cache.save_to_disk("npm new", "ok").unwrap();
+
+        let since = Some(start + Duration::from_secs(86400));
+        assert_eq!(find(&env, FindQuery { since, ..FindQuery::default() }), vec!["npm new".to_string()]);
+    }
+
+    #[test]
+    fn test_index_follows_removals_and_is_rebuilt() {
+        let env = TestEnv::new();
+        env.write_hint_file(HINTS);
+        let mut cache = env.cache();
+
+        cache.save_to_disk("npm test", "ok").unwrap();
+        cache.save_to_disk("npm run build", "ok").unwrap();
+        cache.save_to_disk("npm run lint", "ok").unwrap();
+
+        cache.remove_entry(&cache.generate_id("npm test")).unwrap();
+        cache.clear_cache(Some("npm run build")).unwrap();
+        let query = FindQuery { tags: vec!["frontend".to_string()], ..FindQuery::default() };
+        assert_eq!(find(&env, query.clone()), vec!["npm run lint".to_string()]);
+
+        // A missing or corrupted index is rebuilt from the entries
+        let index = env.cache_dir().join("index.json");
+        fs::write(&index, "not json").unwrap();
+        assert_eq!(find(&env, query.clone()), vec!["npm run lint".to_string()]);
+        fs::remove_file(&index).unwrap();
+        assert_eq!(find(&env, query), vec!["npm run lint".to_string()]);
+        assert!(index.exists());
+    }
+
+    #[test]
+    fn test_concurrent_index_updates_are_compacted() {
+        let env = TestEnv::new();
+        let index = SearchIndex::new(env.cache_dir());
+        let record = |i: usize| IndexRecord {
+            command: format!("echo {} {}", i, "x".repeat(100)),
+            timestamp: i as u64,
+            inputs: EntryInputs::default(),
+            meta: BTreeMap::new(),
+        };
+
+        // Enough changes from concurrent writers to compact the log several times
+        let writers: Vec<_> = (0..8).map(|writer| {
+            let index = index.clone();
+            std::thread::spawn(move || {
+                for i in (writer * 500)..(writer * 500 + 500) {
+                    index.insert(&format!("id{}", i), record(i)).unwrap();
+                }
+                let removed: Vec<String> = (writer * 500..writer * 500 + 100).map(|i| format!("id{}", i)).collect();
+                index.remove(removed.iter().map(String::as_str)).unwrap();
+            })
+        }).collect();
+        for writer in writers {
+            writer.join().unwrap();
+        }
+
+        let records = index.records().unwrap();
+        assert_eq!(records.len(), 8 * 400);
+        assert_eq!(records["id150"], record(150));
+        assert!(!records.contains_key("id50"));
+        assert!(fs::metadata(env.cache_dir().join("index.log")).map_or(0, |m| m.len()) < 512 * 1024);
+    }
+
+    #[test]
+    fn test_since_too_far_back_is_an_error() {
+        let env = TestEnv::new();
+        let output = Command::new(env!("CARGO_BIN_EXE_cacher"))
+            .args(["find", "--since", &u64::MAX.to_string()])
+            .current_dir(env.project_dir())
+            .env("XDG_CACHE_HOME", env.cache_dir())
+            .output()
+            .unwrap();
+        assert_eq!(output.status.code(), Some(1));
+        assert!(String::from_utf8_lossy(&output.stderr).contains("--since"));
+    }
+}
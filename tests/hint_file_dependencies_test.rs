@@ -3,14 +3,13 @@ mod tests {
     use std::fs;
     use std::thread;
     use std::time::Duration;
-    use tempfile::TempDir;
-    use cacher::CommandCache;
+    use cacher::testing::TestEnv;
 
     #[test]
     fn test_hint_file_dependencies() {
-        // Create a temporary directory for the test
-        let temp_dir = TempDir::new().unwrap();
-        let temp_path = temp_dir.path();
+        // Create an isolated cache and project directory for the test
+        let env = TestEnv::new();
+        let temp_path = env.project_dir();
         
         // Create a directory structure with various files
         fs::create_dir_all(temp_path.join("src/components")).unwrap();
@@ -39,12 +38,8 @@ commands:
         
         fs::write(temp_path.join(".cacher"), hint_file_content).unwrap();
         
-        // Change to the temp directory
-        let original_dir = std::env::current_dir().unwrap();
-        std::env::set_current_dir(temp_path).unwrap();
-        
         // Create a new CommandCache
-        let cache = CommandCache::new();
+        let cache = env.cache();
         
         // Verify the hint file was loaded
         assert!(cache.get_hint_file().is_some());
@@ -58,7 +53,7 @@ commands:
         fs::write(temp_path.join("notes.txt"), "Updated notes").unwrap();
         
         // Create a new cache to pick up the file changes
-        let cache = CommandCache::new();
+        let cache = env.cache();
         
         // Get the hash after modifying non-included files
         let hash_after_non_included_changes = cache.generate_id(command);
@@ -76,7 +71,7 @@ commands:
         fs::write(temp_path.join("package.json"), r#"{"name": "test-project", "version": "1.0.1"}"#).unwrap();
         
         // Create a new cache to pick up the file changes
-        let cache = CommandCache::new();
+        let cache = env.cache();
         
         // Get the hash after modifying an included file
         let hash_after_included_changes = cache.generate_id(command);
@@ -94,7 +89,7 @@ commands:
         fs::write(temp_path.join("src/components/Button.js"), "export const Button = (props) => {};").unwrap();
         
         // Create a new cache to pick up the file changes
-        let cache = CommandCache::new();
+        let cache = env.cache();
         
         // Get the hash after modifying a file matching a glob pattern
         let hash_after_glob_changes = cache.generate_id(command);
@@ -105,8 +100,5 @@ commands:
             hash_after_glob_changes,
             "Hash should change when modifying files matching glob patterns in hint file dependencies"
         );
-        
-        // Clean up
-        std::env::set_current_dir(original_dir).unwrap();
     }
 }
@@ -1,17 +1,14 @@
 #[cfg(test)]
 mod tests {
-    use std::fs;
     use std::time::Duration;
-    use tempfile::TempDir;
-    use cacher::CommandCache;
+    use cacher::testing::TestEnv;
 
     #[test]
     fn test_cache_with_hint_file() {
-        // Create a temporary directory for the test
-        let temp_dir = TempDir::new().unwrap();
-        let temp_path = temp_dir.path();
+        // Create an isolated cache and project directory for the test
+        let env = TestEnv::new();
         
-        // Create a .cacher file in the temp directory
+        // Create a .cacher file in the project directory
         let hint_file_content = r#"
 default:
   ttl: 60
@@ -23,14 +20,10 @@ commands:
       - TEST_ENV_VAR
 "#;
         
-        fs::write(temp_path.join(".cacher"), hint_file_content).unwrap();
-        
-        // Change to the temp directory
-        let original_dir = std::env::current_dir().unwrap();
-        std::env::set_current_dir(temp_path).unwrap();
+        env.write_hint_file(hint_file_content);
         
         // Create a new CommandCache
-        let cache = CommandCache::new();
+        let cache = env.cache();
         
         // Verify the hint file was loaded
         assert!(cache.get_hint_file().is_some());
@@ -39,18 +32,14 @@ commands:
         let command = "echo hello";
         let ttl = cache.get_effective_ttl(command, Some(Duration::from_secs(30)));
         assert_eq!(ttl, Some(Duration::from_secs(10))); // Should use command-specific TTL
-        
-        // Clean up
-        std::env::set_current_dir(original_dir).unwrap();
     }
     
     #[test]
     fn test_effective_ttl() {
-        // Create a temporary directory for the test
-        let temp_dir = TempDir::new().unwrap();
-        let temp_path = temp_dir.path();
+        // Create an isolated cache and project directory for the test
+        let env = TestEnv::new();
         
-        // Create a .cacher file in the temp directory
+        // Create a .cacher file in the project directory
         let hint_file_content = r#"
 default:
   ttl: 60
@@ -60,14 +49,10 @@ commands:
     ttl: 10
 "#;
         
-        fs::write(temp_path.join(".cacher"), hint_file_content).unwrap();
-        
-        // Change to the temp directory
-        let original_dir = std::env::current_dir().unwrap();
-        std::env::set_current_dir(temp_path).unwrap();
+        env.write_hint_file(hint_file_content);
         
         // Create a new CommandCache
-        let cache = CommandCache::new();
+        let cache = env.cache();
         
         // Test effective TTL for matching command
         let echo_ttl = cache.get_effective_ttl("echo hello", Some(Duration::from_secs(30)));
@@ -76,8 +61,5 @@ commands:
         // Test effective TTL for non-matching command
         let ls_ttl = cache.get_effective_ttl("ls -la", Some(Duration::from_secs(30)));
         assert_eq!(ls_ttl, Some(Duration::from_secs(60))); // Should use default TTL
-        
-        // Clean up
-        std::env::set_current_dir(original_dir).unwrap();
     }
 }
@@ -18,8 +18,8 @@ mod tests {
         
         assert_eq!(hint_file.default.ttl, Some(3600));
         assert_eq!(hint_file.default.include_env.len(), 2);
-        assert!(hint_file.default.include_env.contains(&"PATH".to_string()));
-        assert!(hint_file.default.include_env.contains(&"USER".to_string()));
+        assert!(hint_file.default.include_env.contains("PATH"));
+        assert!(hint_file.default.include_env.contains("USER"));
     }
 
     #[test]
@@ -82,6 +82,21 @@ mod tests {
         }
     }
 
+    #[test]
+    fn test_load_lockfile_dependencies() {
+        let hint_file = HintFile::from_file(Path::new("tests/fixtures/lockfile.cacher")).unwrap();
+        
+        let npm_command = hint_file.commands.iter().find(|c| c.pattern == "npm ci").unwrap();
+        if let Dependency::Lockfile { lockfile } = &npm_command.depends_on[0] {
+            assert_eq!(lockfile, "package-lock.json");
+        } else {
+            panic!("Expected Lockfile dependency");
+        }
+        
+        let cargo_command = hint_file.commands.iter().find(|c| c.pattern == "cargo fetch").unwrap();
+        assert!(matches!(&cargo_command.depends_on[0], Dependency::Lockfile { lockfile } if lockfile == "auto"));
+    }
+
     #[test]
     fn test_load_complex() {
         let hint_file = HintFile::from_file(Path::new("tests/fixtures/complex.cacher")).unwrap();
@@ -106,6 +121,28 @@ mod tests {
         assert!(src_files_dep.is_some());
     }
 
+    #[test]
+    fn test_load_required_env() {
+        let hint_file = HintFile::from_file(Path::new("tests/fixtures/required_env.cacher")).unwrap();
+        
+        assert!(hint_file.default.require_env.contains("HOME"));
+        
+        let aws_command = hint_file.commands.iter().find(|c| c.pattern == "aws *").unwrap();
+        assert_eq!(aws_command.require_env.len(), 2);
+        assert!(aws_command.require_env.contains("AWS_REGION"));
+        assert!(aws_command.include_env.contains("AWS_PROFILE"));
+    }
+
+    #[test]
+    fn test_include_locale_precedence() {
+        let hint_file = HintFile::from_file(Path::new("tests/fixtures/locale.cacher")).unwrap();
+        
+        assert!(hint_file.default.include_locale);
+        assert!(hint_file.include_locale("cal 2024"));
+        assert!(!hint_file.include_locale("git status"));
+        assert!(hint_file.include_locale("echo unmatched"));
+    }
+
     #[test]
     fn test_find_matching_command() {
         let hint_file = HintFile::from_file(Path::new("tests/fixtures/command_patterns.cacher")).unwrap();
@@ -0,0 +1,91 @@
+#[cfg(test)]
+mod tests {
+    use cacher::clock::ManualClock;
+    use cacher::crypto::Cipher;
+    use cacher::history;
+    use cacher::testing::TestEnv;
+    use std::fs;
+    use std::sync::Arc;
+    use std::time::{Duration, SystemTime};
+
+    #[test]
+    fn test_delta_round_trip() {
+        let base = b"line 1\nline 2\nline 3\n".repeat(100);
+        let mut target = base.clone();
+        target.splice(7..7, b"inserted\n".iter().copied());
+        target.extend_from_slice(b"no trailing newline");
+
+        let created_at = SystemTime::UNIX_EPOCH + Duration::from_secs(1_700_000_000);
+        let delta = history::diff(&base, &target, created_at);
+        assert!(delta.len() < target.len() / 4);
+        assert_eq!(history::apply(&base, &delta).unwrap(), target);
+        assert_eq!(history::created_at(&delta).unwrap(), created_at);
+
+        // Binary and empty outputs work too
+        let binary: Vec<u8> = (0..=255).collect();
+        assert_eq!(history::apply(b"", &history::diff(b"", &binary, created_at)).unwrap(), binary);
+        assert_eq!(history::apply(&binary, &history::diff(&binary, b"", created_at)).unwrap(), b"");
+
+        assert!(history::apply(&base, &delta[..delta.len() - 3]).is_err());
+        assert!(history::apply(b"short", &delta).is_err());
+    }
+
+    #[test]
+    fn test_history_keeps_generations_as_deltas() {
+        let env = TestEnv::new();
+        env.write_hint_file("commands:\n  - pattern: \"cat *\"\n    history: 2\n");
+        let clock = ManualClock::new(SystemTime::UNIX_EPOCH + Duration::from_secs(1_700_000_000));
+        let mut cache = env.cache();
+        cache.set_clock(Arc::new(clock.clone()));
+
+        let big = "unchanged line\n".repeat(1000);
+        for version in 1..=4 {
+            env.write_file("data.txt", format!("version {}\n{}", version, big));
+            cache.execute_and_cache("cat data.txt", None, true).unwrap();
+            clock.advance(Duration::from_secs(60));
+        }
+
+        // Only the two generations before the current output are kept
+        let history = cache.history("cat data.txt").unwrap();
+        assert_eq!(history.len(), 2);
+        assert_eq!(history[0].stdout, format!("version 3\n{}", big));
+        assert_eq!(history[1].stdout, format!("version 2\n{}", big));
+        assert_eq!(history[0].created_at, SystemTime::UNIX_EPOCH + Duration::from_secs(1_700_000_000 + 120));
+
+        // And they are stored as small deltas, not copies
+        let history_dir = cache.get_cache_path(&cache.generate_id("cat data.txt")).join("history");
+        let mut names: Vec<String> = fs::read_dir(&history_dir).unwrap()
+            .map(|entry| entry.unwrap().file_name().to_string_lossy().into_owned())
+            .collect();
+        names.sort();
+        assert_eq!(names, vec!["2.delta", "3.delta"]);
+        for name in names {
+            assert!(fs::metadata(history_dir.join(name)).unwrap().len() < 200);
+        }
+    }
+
+    #[test]
+    fn test_history_disabled_and_encrypted() {
+        let env = TestEnv::new();
+        env.write_hint_file("commands:\n  - pattern: \"cat secret*\"\n    history: 3\n    encrypt: true\n");
+        let mut cache = env.cache();
+        cache.set_cipher(Cipher::from_key([7; 32]));
+
+        env.write_file("plain.txt", "one");
+        cache.execute_and_cache("cat plain.txt", None, false).unwrap();
+        cache.execute_and_cache("cat plain.txt", None, true).unwrap();
+        assert!(cache.history("cat plain.txt").unwrap().is_empty());
+
+        env.write_file("secret.txt", "hunter2");
+        cache.execute_and_cache("cat secret.txt", None, false).unwrap();
+        env.write_file("secret.txt", "correct horse");
+        cache.execute_and_cache("cat secret.txt", None, true).unwrap();
+
+        let history = cache.history("cat secret.txt").unwrap();
+        assert_eq!(history.len(), 1);
+        assert_eq!(history[0].stdout, "hunter2");
+
+        let delta = cache.get_cache_path(&cache.generate_id("cat secret.txt")).join("history/1.delta");
+        assert!(cacher::crypto::is_encrypted(&fs::read(delta).unwrap()));
+    }
+}
@@ -0,0 +1,85 @@
+#[cfg(test)]
+mod tests {
+    use std::fs;
+    use std::time::{Duration, Instant};
+    use tempfile::TempDir;
+    use cacher::testing::TestEnv;
+    use cacher::hint_file::HintFile;
+    use cacher::hooks::{Hook, HookFailure, HookStage};
+
+    fn hook(run: &str, timeout: Option<u64>, on_failure: Option<HookFailure>) -> Hook {
+        Hook { run: run.to_string(), timeout, on_failure }
+    }
+
+    #[test]
+    fn test_hook_definitions() {
+        let temp_dir = TempDir::new().unwrap();
+        let hint_path = temp_dir.path().join(".cacher");
+        fs::write(&hint_path, r#"
+commands:
+  - pattern: "npm test"
+    before: "docker start test-db"
+    after_miss:
+      run: "notify-send done"
+      timeout: 5
+      on_failure: ignore
+"#).unwrap();
+        
+        let hint_file = HintFile::from_file(&hint_path).unwrap();
+        let npm_command = hint_file.find_matching_command("npm test").unwrap();
+        
+        assert_eq!(npm_command.before, Some(hook("docker start test-db", None, None)));
+        assert_eq!(npm_command.after_miss, Some(hook("notify-send done", Some(5), Some(HookFailure::Ignore))));
+        assert_eq!(npm_command.after_hit, None);
+    }
+
+    #[test]
+    fn test_failure_policies() {
+        let temp_dir = TempDir::new().unwrap();
+        let dir = temp_dir.path();
+        
+        // before hooks abort by default, after hooks only warn
+        assert!(hook("exit 3", None, None).run(HookStage::Before, "cmd", dir).is_err());
+        assert!(hook("exit 3", None, None).run(HookStage::AfterMiss, "cmd", dir).is_ok());
+        
+        // An explicit policy wins
+        assert!(hook("exit 3", None, Some(HookFailure::Ignore)).run(HookStage::Before, "cmd", dir).is_ok());
+        assert!(hook("exit 3", None, Some(HookFailure::Abort)).run(HookStage::AfterHit, "cmd", dir).is_err());
+    }
+
+    #[test]
+    fn test_hook_timeout() {
+        let temp_dir = TempDir::new().unwrap();
+        
+        let started = Instant::now();
+        let err = hook("sleep 10", Some(1), None).run(HookStage::Before, "cmd", temp_dir.path()).unwrap_err();
+        
+        assert!(err.to_string().contains("timed out"));
+        assert!(started.elapsed() < Duration::from_secs(5));
+    }
+
+    #[test]
+    fn test_hooks_run_around_command() {
+        let env = TestEnv::new();
+        
+        // Each hook appends its stage to a log file
+        let hint_file_content = r#"
+commands:
+  - pattern: "echo hooks_test"
+    before: "echo $CACHER_HOOK >> hooks.log"
+    after_miss: "echo $CACHER_HOOK >> hooks.log"
+    after_hit: "echo $CACHER_HOOK >> hooks.log"
+"#;
+        env.write_hint_file(hint_file_content);
+        
+        let mut cache = env.cache();
+        let command = "echo hooks_test";
+        
+        // A miss runs before and after_miss, a hit runs after_hit
+        cache.execute_and_cache(command, None, true).unwrap();
+        cache.execute_and_cache(command, None, false).unwrap();
+        
+        let log = fs::read_to_string(env.project_dir().join("hooks.log")).unwrap();
+        assert_eq!(log, "before\nafter_miss\nafter_hit\n");
+    }
+}
@@ -0,0 +1,106 @@
+#[cfg(test)]
+mod tests {
+    use cacher::index::FindQuery;
+    use cacher::invalidate::InvalidationManifest;
+    use cacher::testing::TestEnv;
+    use std::fs;
+
+    const HINTS: &str = r#"
+commands:
+  - pattern: "echo api*"
+    tags: [backend]
+  - pattern: "echo web*"
+    tags: [frontend]
+"#;
+
+    fn manifest(content: &str) -> InvalidationManifest {
+        InvalidationManifest::parse(content).unwrap()
+    }
+
+    fn cached(env: &TestEnv, command: &str) -> bool {
+        env.cache().load_entry(command).unwrap().is_some()
+    }
+
+    #[test]
+    fn test_invalidate_by_pattern_and_tag() {
+        let env = TestEnv::new();
+        env.write_hint_file(HINTS);
+        let mut cache = env.cache();
+        for command in ["echo api users", "echo api orders", "echo web home", "echo docs", "echo deploy notes"] {
+            cache.execute_and_cache(command, None, false).unwrap();
+        }
+        cache.set_pinned(&cache.generate_id("echo api orders"), true).unwrap();
+
+        let deploy = manifest("patterns: [\"echo deploy *\"]\ntags: [backend]\n");
+        let mut targets: Vec<String> = cache.invalidation_targets(&deploy).unwrap()
+            .into_iter()
+            .map(|entry| entry.command)
+            .collect();
+        targets.sort();
+        assert_eq!(targets, vec!["echo api users", "echo deploy notes"]);
+
+        let removed = cache.invalidate(&deploy).unwrap();
+        assert_eq!(removed.len(), 2);
+        assert!(!cached(&env, "echo api users"));
+        assert!(!cached(&env, "echo deploy notes"));
+        assert!(cached(&env, "echo api orders"));
+        assert!(cached(&env, "echo web home"));
+        assert!(cached(&env, "echo docs"));
+
+        // Nothing is left over in memory, in the index or in staging
+        assert!(cache.get_cached("echo api users", None).is_none());
+        let found = cache.find(&FindQuery { tags: vec!["backend".to_string()], ..FindQuery::default() }).unwrap();
+        assert_eq!(found.len(), 1);
+        let staged = fs::read_dir(env.cache_dir()).unwrap()
+            .flatten()
+            .filter(|entry| entry.file_name().to_string_lossy().starts_with(".staging-"))
+            .count();
+        assert_eq!(staged, 0);
+
+        // Pinned entries go only when asked for
+        let pinned = manifest("tags: [backend]\ninclude_pinned: true\n");
+        assert_eq!(cache.invalidate(&pinned).unwrap().len(), 1);
+        assert!(!cached(&env, "echo api orders"));
+    }
+
+    #[test]
+    fn test_invalidate_by_namespace() {
+        let env = TestEnv::new();
+        env.write_file("other/.keep", "");
+        let mut cache = env.cache();
+        let mut other = env.cache_in(&env.project_dir().join("other"));
+        cache.execute_and_cache("echo hello", None, false).unwrap();
+        other.execute_and_cache("echo hello", None, false).unwrap();
+
+        let namespace = format!("namespaces: [\"{}\"]\n", other.project_id());
+        cache.invalidate(&manifest(&namespace)).unwrap();
+        assert!(cache.load_entry("echo hello").unwrap().is_some());
+        assert!(other.load_entry("echo hello").unwrap().is_none());
+    }
+
+    #[test]
+    fn test_failed_invalidation_removes_nothing() {
+        let env = TestEnv::new();
+        env.write_hint_file(HINTS);
+        let mut cache = env.cache();
+        cache.execute_and_cache("echo api users", None, false).unwrap();
+        cache.execute_and_cache("echo api orders", None, false).unwrap();
+
+        // An index that can't be updated fails the invalidation after the entries were staged
+        let _ = fs::remove_file(env.cache_dir().join("index.log"));
+        fs::create_dir(env.cache_dir().join("index.log")).unwrap();
+
+        assert!(cache.invalidate(&manifest("tags: [backend]\n")).is_err());
+        assert!(cached(&env, "echo api users"));
+        assert!(cached(&env, "echo api orders"));
+    }
+
+    #[test]
+    fn test_invalid_manifests() {
+        assert!(InvalidationManifest::parse("").is_err());
+        assert!(InvalidationManifest::parse("include_pinned: true\n").is_err());
+        assert!(InvalidationManifest::parse("pattern: [\"echo *\"]\n").is_err());
+        assert!(InvalidationManifest::parse("patterns: [\"[\"]\n").is_err());
+        assert!(InvalidationManifest::parse("namespaces: [\"git:github.com/acme/*\"]\n").is_ok());
+    }
+}
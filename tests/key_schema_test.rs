@@ -0,0 +1,45 @@
+#[cfg(test)]
+mod tests {
+    use cacher::hint_file::HintFile;
+    use cacher::testing::TestEnv;
+    use cacher::KEY_SCHEMA_VERSION;
+
+    #[test]
+    fn test_key_schema_is_part_of_the_key() {
+        let env = TestEnv::new();
+        let mut cache = env.cache();
+        let id = cache.generate_id("echo hello");
+
+        assert_eq!(cache.key_schema(), KEY_SCHEMA_VERSION);
+        assert_eq!(cache.explain("echo hello").unwrap().key_schema, KEY_SCHEMA_VERSION);
+
+        // Pinning the current version keeps the current keys
+        cache.set_key_schema(KEY_SCHEMA_VERSION).unwrap();
+        assert_eq!(cache.generate_id("echo hello"), id);
+    }
+
+    #[test]
+    fn test_unsupported_key_schema() {
+        let env = TestEnv::new();
+        let mut cache = env.cache();
+
+        assert!(cache.set_key_schema(0).is_err());
+        assert!(cache.set_key_schema(KEY_SCHEMA_VERSION + 1).is_err());
+        assert_eq!(cache.key_schema(), KEY_SCHEMA_VERSION);
+
+        let path = env.write_hint_file(&format!("default:\n  key_schema: {}\n", KEY_SCHEMA_VERSION + 1));
+        let err = HintFile::from_file(&path).unwrap_err();
+        assert!(format!("{:#}", err).contains("Unsupported key_schema"));
+    }
+
+    #[test]
+    fn test_key_schema_in_hint_file() {
+        let env = TestEnv::new();
+        env.write_hint_file(&format!("default:\n  key_schema: {}\n", KEY_SCHEMA_VERSION));
+        let mut cache = env.cache();
+
+        assert_eq!(cache.key_schema(), KEY_SCHEMA_VERSION);
+        assert_eq!(cache.execute_and_cache("echo pinned", None, false).unwrap(), "pinned\n");
+        assert_eq!(cache.get_cached("echo pinned", None).unwrap(), "pinned\n");
+    }
+}
@@ -0,0 +1,109 @@
+#[cfg(test)]
+mod tests {
+    use cacher::metadata::FULL_COMMAND_FILE;
+    use cacher::testing::TestEnv;
+    use cacher::{CommandCache, LAYOUT_FILE, LAYOUT_VERSION};
+    use std::fs;
+
+    #[test]
+    fn test_layout_paths() {
+        let env = TestEnv::new();
+        env.write_hint_file(r#"
+commands:
+  - pattern: "echo build"
+    artifacts:
+      - type: "directory"
+        path: "dist"
+"#);
+        env.write_file("dist/app.js", "app");
+        let mut cache = env.cache();
+        assert_eq!(CommandCache::layout_version(), LAYOUT_VERSION);
+        assert_eq!(cache.cache_dir(), env.cache_dir());
+
+        // Asking for paths doesn't create anything
+        let id = cache.generate_id("echo build");
+        let entry_dir = cache.entry_dir(&id);
+        assert_eq!(entry_dir, env.cache_dir().join(&id));
+        assert_eq!(cache.artifact_dir(&id), entry_dir.join("artifacts"));
+        assert!(!entry_dir.exists());
+
+        cache.execute_and_cache_with_artifacts("echo build", None, false).unwrap();
+        assert_eq!(fs::read_to_string(entry_dir.join("stdout")).unwrap(), "build\n");
+        assert!(entry_dir.join("stderr").exists());
+        assert!(entry_dir.join("metadata.json").exists());
+        assert!(entry_dir.join("complete").exists());
+        assert!(!entry_dir.join(FULL_COMMAND_FILE).exists());
+        assert!(cache.artifact_dir(&id).is_dir());
+    }
+
+    #[test]
+    fn test_copied_entry_dir_is_served() {
+        let env = TestEnv::new();
+        let mut cache = env.cache();
+        let output = cache.execute_and_cache("date +%s%N", None, false).unwrap();
+        let id = cache.generate_id("date +%s%N");
+
+        // Restore the entry into an empty cache dir, the way a CI cache step would
+        let restored_dir = env.project_dir().join("restored-cache");
+        let mut restored = CommandCache::with_dirs(restored_dir.clone(), env.project_dir().to_path_buf());
+        let target = restored.entry_dir(&id);
+        fs::create_dir_all(&target).unwrap();
+        for file in fs::read_dir(cache.entry_dir(&id)).unwrap().flatten() {
+            if file.path().is_file() {
+                fs::copy(file.path(), target.join(file.file_name())).unwrap();
+            }
+        }
+        assert!(!restored_dir.join("index.json").exists());
+
+        assert_eq!(restored.get_cached("date +%s%N", None), Some(output));
+    }
+
+    #[test]
+    fn test_layout_version_is_persisted() {
+        let env = TestEnv::new();
+        let mut cache = env.cache();
+        let layout_file = env.cache_dir().join(LAYOUT_FILE);
+        assert_eq!(fs::read_to_string(&layout_file).unwrap(), format!("{}\n", LAYOUT_VERSION));
+        assert_eq!(cache.stored_layout_version().unwrap(), Some(LAYOUT_VERSION));
+
+        // A cache dir restored without the file gets it back with the next entry
+        fs::remove_file(&layout_file).unwrap();
+        assert_eq!(cache.stored_layout_version().unwrap(), None);
+        cache.execute_and_cache("echo one", None, false).unwrap();
+        assert_eq!(cache.stored_layout_version().unwrap(), Some(LAYOUT_VERSION));
+
+        // Entries aren't written into a cache dir with another layout
+        fs::write(&layout_file, format!("{}\n", LAYOUT_VERSION + 1)).unwrap();
+        let err = env.cache().execute_and_cache("echo two", None, false).unwrap_err();
+        assert!(err.to_string().contains("layout version"));
+        assert_eq!(fs::read_to_string(&layout_file).unwrap(), format!("{}\n", LAYOUT_VERSION + 1));
+    }
+
+    #[test]
+    fn test_passphrase_encrypted_entry_needs_the_salt() {
+        let mut env = TestEnv::new();
+        env.set_env("CACHER_ENCRYPTION_KEY", "correct horse battery staple");
+        env.write_hint_file("commands:\n  - pattern: \"date *\"\n    encrypt: true\n");
+        let mut cache = env.cache();
+        let output = cache.execute_and_cache("date +%s%N", None, false).unwrap();
+        let id = cache.generate_id("date +%s%N");
+
+        let restore = |name: &str, with_salt: bool| {
+            let restored_dir = env.project_dir().join(name);
+            let mut restored = CommandCache::with_dirs(restored_dir.clone(), env.project_dir().to_path_buf());
+            let target = restored.entry_dir(&id);
+            fs::create_dir_all(&target).unwrap();
+            for file in fs::read_dir(cache.entry_dir(&id)).unwrap().flatten() {
+                if file.path().is_file() {
+                    fs::copy(file.path(), target.join(file.file_name())).unwrap();
+                }
+            }
+            if with_salt {
+                fs::copy(env.cache_dir().join("encryption.salt"), restored_dir.join("encryption.salt")).unwrap();
+            }
+            restored.get_cached("date +%s%N", None)
+        };
+        assert_eq!(restore("with-salt", true), Some(output));
+        assert_eq!(restore("without-salt", false), None);
+    }
+}
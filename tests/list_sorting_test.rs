@@ -0,0 +1,51 @@
+#[cfg(test)]
+mod tests {
+    use cacher::clock::{Clock, ManualClock};
+    use cacher::testing::TestEnv;
+    use cacher::{ListOptions, SortBy};
+    use std::sync::Arc;
+    use std::time::{Duration, SystemTime};
+
+    fn commands(cache: &cacher::CommandCache, options: ListOptions) -> Vec<String> {
+        cache.list_cached_commands(&options).unwrap().into_iter().map(|e| e.command).collect()
+    }
+
+    #[test]
+    fn test_list_sort_filter_limit() {
+        let env = TestEnv::new();
+        let clock = ManualClock::new(SystemTime::UNIX_EPOCH + Duration::from_secs(1_700_000_000));
+        let mut cache = env.cache();
+        cache.set_clock(Arc::new(clock.clone()));
+
+        cache.execute_and_cache("echo old", None, false).unwrap();
+        clock.advance(Duration::from_secs(10));
+        cache.execute_and_cache("seq 1 5000", None, false).unwrap();
+        clock.advance(Duration::from_secs(10));
+        cache.execute_and_cache("echo new", None, false).unwrap();
+
+        clock.advance(Duration::from_secs(10));
+        cache.get_cached("echo new", None).unwrap();
+        clock.advance(Duration::from_secs(10));
+        cache.get_cached("echo old", None).unwrap();
+        cache.get_cached("echo old", None).unwrap();
+
+        let by = |sort| ListOptions { sort, ..ListOptions::default() };
+        assert_eq!(commands(&cache, by(SortBy::Age)), vec!["echo new", "seq 1 5000", "echo old"]);
+        assert_eq!(commands(&cache, by(SortBy::Size))[0], "seq 1 5000");
+        assert_eq!(commands(&cache, by(SortBy::Hits)), vec!["echo old", "echo new", "seq 1 5000"]);
+        assert_eq!(commands(&cache, by(SortBy::LastUsed)), vec!["echo old", "echo new", "seq 1 5000"]);
+
+        let entries = cache.list_cached_commands(&by(SortBy::LastUsed)).unwrap();
+        assert_eq!(entries[0].hit_count, 2);
+        assert_eq!(entries[0].last_accessed, Some(clock.now()));
+        assert_eq!(entries[2].last_accessed, None);
+
+        let filtered = ListOptions { filter: Some("echo *".to_string()), limit: Some(1), ..ListOptions::default() };
+        assert_eq!(commands(&cache, filtered), vec!["echo new"]);
+
+        let invalid = ListOptions { filter: Some("[".to_string()), ..ListOptions::default() };
+        assert!(cache.list_cached_commands(&invalid).is_err());
+        assert_eq!("last-used".parse::<SortBy>(), Ok(SortBy::LastUsed));
+        assert!("newest".parse::<SortBy>().is_err());
+    }
+}
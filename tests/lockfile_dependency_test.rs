@@ -0,0 +1,89 @@
+#[cfg(test)]
+mod tests {
+    use std::fs;
+    use tempfile::TempDir;
+    use cacher::hint_file::Dependency;
+
+    fn package_lock(name: &str, version: &str, lodash: &str) -> String {
+        format!(r#"{{
+  "name": "{name}",
+  "version": "{version}",
+  "lockfileVersion": 3,
+  "packages": {{
+    "": {{ "name": "{name}", "version": "{version}", "dependencies": {{ "lodash": "^4.0.0" }} }},
+    "node_modules/lodash": {{ "version": "{lodash}" }}
+  }}
+}}"#)
+    }
+
+    #[test]
+    fn test_package_lock_ignores_project_version() {
+        let temp_dir = TempDir::new().unwrap();
+        let temp_path = temp_dir.path();
+        let dependency = Dependency::Lockfile { lockfile: "package-lock.json".to_string() };
+        
+        fs::write(temp_path.join("package-lock.json"), package_lock("app", "1.0.0", "4.17.20")).unwrap();
+        let initial_hash = dependency.get_content_hash(temp_path).unwrap();
+        
+        // Bumping the project's own version doesn't change the installed dependencies
+        fs::write(temp_path.join("package-lock.json"), package_lock("app", "1.0.1", "4.17.20")).unwrap();
+        assert_eq!(initial_hash, dependency.get_content_hash(temp_path).unwrap());
+        
+        // Upgrading a dependency does
+        fs::write(temp_path.join("package-lock.json"), package_lock("app", "1.0.1", "4.17.21")).unwrap();
+        assert_ne!(initial_hash, dependency.get_content_hash(temp_path).unwrap());
+    }
+
+    #[test]
+    fn test_cargo_lock_ignores_workspace_packages() {
+        let temp_dir = TempDir::new().unwrap();
+        let temp_path = temp_dir.path();
+        let dependency = Dependency::Lockfile { lockfile: "auto".to_string() };
+        
+        let cargo_lock = |own_version: &str, serde_version: &str| format!(r#"# This file is automatically @generated by Cargo.
+version = 3
+
+[[package]]
+name = "app"
+version = "{own_version}"
+dependencies = ["serde"]
+
+[[package]]
+name = "serde"
+version = "{serde_version}"
+source = "registry+https://github.com/rust-lang/crates.io-index"
+"#);
+        
+        fs::write(temp_path.join("Cargo.lock"), cargo_lock("0.1.0", "1.0.100")).unwrap();
+        assert_eq!(dependency.get_files(temp_path).unwrap(), vec!["Cargo.lock".to_string()]);
+        let initial_hash = dependency.get_content_hash(temp_path).unwrap();
+        
+        fs::write(temp_path.join("Cargo.lock"), cargo_lock("0.2.0", "1.0.100")).unwrap();
+        assert_eq!(initial_hash, dependency.get_content_hash(temp_path).unwrap());
+        
+        fs::write(temp_path.join("Cargo.lock"), cargo_lock("0.2.0", "1.0.101")).unwrap();
+        assert_ne!(initial_hash, dependency.get_content_hash(temp_path).unwrap());
+    }
+
+    #[test]
+    fn test_auto_detects_multiple_lockfiles() {
+        let temp_dir = TempDir::new().unwrap();
+        let temp_path = temp_dir.path();
+        
+        fs::write(temp_path.join("yarn.lock"), "# yarn lockfile v1\nlodash@^4:\n  version \"4.17.21\"\n").unwrap();
+        fs::write(temp_path.join("poetry.lock"), "[[package]]\nname = \"requests\"\n").unwrap();
+        fs::write(temp_path.join("notes.txt"), "not a lockfile").unwrap();
+        
+        let dependency = Dependency::Lockfile { lockfile: "auto".to_string() };
+        let files = dependency.get_files(temp_path).unwrap();
+        
+        assert_eq!(files.len(), 2);
+        assert!(files.contains(&"yarn.lock".to_string()));
+        assert!(files.contains(&"poetry.lock".to_string()));
+        
+        // Comment-only edits don't change the hash
+        let initial_hash = dependency.get_content_hash(temp_path).unwrap();
+        fs::write(temp_path.join("yarn.lock"), "# regenerated\nlodash@^4:\n  version \"4.17.21\"\n").unwrap();
+        assert_eq!(initial_hash, dependency.get_content_hash(temp_path).unwrap());
+    }
+}
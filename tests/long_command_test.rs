@@ -0,0 +1,70 @@
+#[cfg(test)]
+mod tests {
+    use cacher::metadata::{display_command, FULL_COMMAND_FILE, MAX_COMMAND_BYTES};
+    use cacher::testing::TestEnv;
+    use cacher::ListOptions;
+
+    /// A command with a large inline payload, ending in something to filter on
+    fn long_command() -> String {
+        format!("echo {{\"items\":[{}]}} tail-marker", "\"item\",".repeat(1000))
+    }
+
+    #[test]
+    fn test_long_command_is_stored_truncated() {
+        let env = TestEnv::new();
+        let mut cache = env.cache();
+        let command = long_command();
+
+        let output = cache.execute_and_cache(&command, None, false).unwrap();
+        assert!(output.trim_end().ends_with("tail-marker"));
+
+        let (_, metadata) = cache.load_entry(&command).unwrap().unwrap();
+        assert!(metadata.command.len() < MAX_COMMAND_BYTES + 32);
+        assert!(metadata.command.ends_with(&format!("… [+{} bytes]", command.len() - MAX_COMMAND_BYTES)));
+        assert_eq!(metadata.command_bytes, Some(command.len() as u64));
+
+        let entry_dir = env.cache_dir().join(cache.generate_id(&command));
+        assert!(entry_dir.join(FULL_COMMAND_FILE).exists());
+        assert_eq!(metadata.full_command(&entry_dir).unwrap(), command);
+
+        // Served from disk by a fresh cache
+        assert_eq!(env.cache().get_cached(&command, None), Some(output));
+    }
+
+    #[test]
+    fn test_list_refresh_and_clear_long_commands() {
+        let env = TestEnv::new();
+        let mut cache = env.cache();
+        let command = long_command();
+        cache.execute_and_cache(&command, None, false).unwrap();
+        cache.execute_and_cache("echo short", None, false).unwrap();
+
+        let filtered = ListOptions { filter: Some("*tail-marker".to_string()), ..ListOptions::default() };
+        let entries = cache.list_cached_commands(&filtered).unwrap();
+        assert_eq!(entries.len(), 1);
+        assert_eq!(entries[0].id, cache.generate_id(&command));
+        assert!(entries[0].command.len() < command.len());
+
+        let id = entries[0].id.clone();
+        assert!(cache.refresh_entry(&id).unwrap().contains("tail-marker"));
+
+        cache.clear_cache(Some(&command)).unwrap();
+        assert!(cache.load_entry(&command).unwrap().is_none());
+        assert!(cache.get_cached(&command, None).is_none());
+        assert!(cache.load_entry("echo short").unwrap().is_some());
+    }
+
+    #[test]
+    fn test_display_command() {
+        assert_eq!(display_command("echo short"), "echo short");
+
+        let exact = "x".repeat(MAX_COMMAND_BYTES);
+        assert_eq!(display_command(&exact), exact);
+
+        // Cut at a character boundary
+        let multibyte = format!("x{}", "é".repeat(MAX_COMMAND_BYTES));
+        let shown = display_command(&multibyte);
+        assert!(shown.starts_with(&format!("x{}", "é".repeat(MAX_COMMAND_BYTES / 2 - 1))));
+        assert!(shown.ends_with(&format!("… [+{} bytes]", multibyte.len() - (MAX_COMMAND_BYTES - 1))));
+    }
+}
@@ -0,0 +1,58 @@
+#[cfg(test)]
+mod tests {
+    use std::fs;
+    use cacher::testing::TestEnv;
+
+    #[test]
+    fn test_no_cache_conditions() {
+        // Create an isolated cache and project directory for the test
+        let mut env = TestEnv::new();
+        let temp_path = env.project_dir().to_path_buf();
+        
+        // Create a .cacher file with a default and a per-command condition
+        let hint_file_content = r#"
+default:
+  no_cache_if_env:
+    - CACHER_TEST_SKIP_CACHE
+
+commands:
+  - pattern: "date *"
+    no_cache_if_file_exists:
+      - .no-cache
+"#;
+        
+        fs::write(temp_path.join(".cacher"), hint_file_content).unwrap();
+        
+        env.remove_env("CACHER_TEST_SKIP_CACHE");
+        
+        let command = "date +%s%N";
+        let mut cache = env.cache();
+        
+        // PART 1: Without any condition the second run is served from the cache
+        assert_eq!(cache.no_cache_reason(command), None);
+        let first = cache.execute_and_cache(command, None, false).unwrap();
+        let second = cache.execute_and_cache(command, None, false).unwrap();
+        assert_eq!(first, second, "Second run should be a cache hit");
+        
+        // PART 2: The marker file bypasses the cache for matching commands
+        fs::write(temp_path.join(".no-cache"), "").unwrap();
+        assert_eq!(cache.no_cache_reason(command), Some(".no-cache exists".to_string()));
+        assert_eq!(cache.no_cache_reason("echo other"), None);
+        
+        let bypassed = cache.execute_and_cache(command, None, false).unwrap();
+        assert_ne!(first, bypassed, "Command should run when the cache is bypassed");
+        fs::remove_file(temp_path.join(".no-cache")).unwrap();
+        
+        // The bypassed run didn't replace the cached entry
+        assert_eq!(cache.get_cached(command, None), Some(first.clone()));
+        
+        // PART 3: Default conditions apply to every command, empty values don't count
+        env.set_env("CACHER_TEST_SKIP_CACHE", "");
+        assert_eq!(cache.no_cache_reason("echo other"), None);
+        
+        env.set_env("CACHER_TEST_SKIP_CACHE", "1");
+        assert_eq!(cache.no_cache_reason("echo other"), Some("CACHER_TEST_SKIP_CACHE is set".to_string()));
+        let bypassed = cache.execute_and_cache(command, None, false).unwrap();
+        assert_ne!(first, bypassed, "Command should run when the cache is bypassed");
+    }
+}
@@ -0,0 +1,48 @@
+#[cfg(test)]
+mod tests {
+    use std::path::Path;
+    use cacher::hint_file::HintFile;
+    use cacher::normalize::{normalize_output, NormalizeRule, Normalizer};
+
+    #[test]
+    fn test_rules_applied_in_order() {
+        let rules = vec![
+            NormalizeRule { pattern: r"\d+ms".to_string(), replacement: "<duration>".to_string() },
+            NormalizeRule { pattern: r"<duration>".to_string(), replacement: "N/A".to_string() },
+        ];
+        
+        let output = normalize_output("built in 1234ms\n", &rules).unwrap();
+        assert_eq!(output, "built in N/A\n");
+    }
+
+    #[test]
+    fn test_capture_group_replacement() {
+        let rules = vec![NormalizeRule {
+            pattern: r"(?m)^(\w+) pid=\d+$".to_string(),
+            replacement: "$1 pid=<pid>".to_string(),
+        }];
+        let normalizer = Normalizer::new(&rules).unwrap();
+        
+        assert_eq!(normalizer.apply("worker pid=42\nserver pid=7\n"), "worker pid=<pid>\nserver pid=<pid>\n");
+        // Applying twice gives the same result, so cached output stays stable
+        assert_eq!(normalizer.apply(&normalizer.apply("worker pid=42\n")), "worker pid=<pid>\n");
+    }
+
+    #[test]
+    fn test_invalid_pattern() {
+        let rules = vec![NormalizeRule { pattern: "(unclosed".to_string(), replacement: String::new() }];
+        assert!(Normalizer::new(&rules).is_err());
+    }
+
+    #[test]
+    fn test_load_normalize_rules() {
+        let hint_file = HintFile::from_file(Path::new("tests/fixtures/normalize.cacher")).unwrap();
+        let build_command = hint_file.find_matching_command("./build.sh release").unwrap();
+        
+        assert_eq!(build_command.normalize.len(), 2);
+        
+        let normalizer = Normalizer::new(&build_command.normalize).unwrap();
+        let output = normalizer.apply("started 2024-05-01T12:30:00Z request-id: a1b2c3\n");
+        assert_eq!(output, "started <timestamp> request-id: <id>\n");
+    }
+}
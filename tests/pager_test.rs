@@ -0,0 +1,33 @@
+#[cfg(test)]
+mod tests {
+    use cacher::pager::{self, Preview};
+    use cacher::testing::TestEnv;
+    use std::fs;
+
+    #[test]
+    fn test_preview() {
+        let text = "one\ntwo\nthree\nfour\n";
+
+        assert_eq!(pager::preview(text, 2), Preview { text: "one\ntwo\n", omitted_lines: 2 });
+        assert_eq!(pager::preview(text, 4), Preview { text, omitted_lines: 0 });
+        assert_eq!(pager::preview(text, 10), Preview { text, omitted_lines: 0 });
+        assert_eq!(pager::preview(text, 0), Preview { text: "", omitted_lines: 4 });
+
+        // The last line doesn't need a newline
+        assert_eq!(pager::preview("one\ntwo", 1), Preview { text: "one\n", omitted_lines: 1 });
+        assert_eq!(pager::preview("one\ntwo", 2), Preview { text: "one\ntwo", omitted_lines: 0 });
+    }
+
+    #[test]
+    fn test_page_with() {
+        let env = TestEnv::new();
+        let paged = env.project_dir().join("paged.txt");
+
+        pager::page_with(&format!("cat > '{}'", paged.display()), "cached output\n").unwrap();
+        assert_eq!(fs::read_to_string(&paged).unwrap(), "cached output\n");
+
+        // A pager quitting early isn't an error, but one that fails is
+        pager::page_with("true", &"line\n".repeat(100_000)).unwrap();
+        assert!(pager::page_with("exit 3", "output").is_err());
+    }
+}
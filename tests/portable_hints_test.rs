@@ -0,0 +1,88 @@
+#[cfg(test)]
+mod tests {
+    use std::fs;
+    use cacher::hint_file::{Dependency, HintFile};
+    use cacher::testing::TestEnv;
+
+    #[test]
+    fn test_dependency_separators_are_normalized() {
+        let env = TestEnv::new();
+        env.write_file("src/lib/a.rs", "a");
+        env.write_hint_file("commands:\n  - pattern: \"cargo build\"\n    depends_on:\n      - files: 'src\\lib\\*.rs'\n      - file: 'src\\lib\\a.rs'\n");
+
+        let cache = env.cache();
+        let hint_file = cache.get_hint_file().unwrap();
+        let depends_on = &hint_file.find_matching_command("cargo build").unwrap().depends_on;
+        assert!(matches!(&depends_on[0], Dependency::Files { files } if files == "src/lib/*.rs"));
+        assert!(matches!(&depends_on[1], Dependency::File { file } if file == "src/lib/a.rs"));
+        assert!(cache.entry_inputs("cargo build").dependencies.contains(&"src/lib/a.rs".to_string()));
+
+        // The key doesn't depend on which separator the hint file was written with
+        let windows_id = cache.generate_id("cargo build");
+        env.write_hint_file("commands:\n  - pattern: \"cargo build\"\n    depends_on:\n      - files: 'src/lib/*.rs'\n      - file: 'src/lib/a.rs'\n");
+        assert_eq!(env.cache().generate_id("cargo build"), windows_id);
+    }
+
+    #[test]
+    fn test_case_insensitive_keys() {
+        let env = TestEnv::new();
+        env.write_file("src/Main.RS", "fn main() {}");
+        let hints = |case_insensitive: bool, pattern: &str| format!(
+            "default:\n  case_insensitive_keys: {}\ncommands:\n  - pattern: \"cargo build\"\n    depends_on:\n      - files: \"{}\"\n",
+            case_insensitive, pattern,
+        );
+
+        env.write_hint_file(&hints(false, "src/*.rs"));
+        let without_match = env.cache().generate_id("cargo build");
+
+        // The glob matches regardless of case, and the matched path is hashed lowercased
+        env.write_hint_file(&hints(true, "src/*.rs"));
+        let lower = env.cache().generate_id("cargo build");
+        assert_ne!(lower, without_match);
+        env.write_hint_file(&hints(true, "src/*.RS"));
+        assert_eq!(env.cache().generate_id("cargo build"), lower);
+        // The recorded inputs match the same files the key did
+        assert!(env.cache().entry_inputs("cargo build").dependencies.contains(&"src/Main.RS".to_string()));
+        env.write_hint_file(&hints(false, "src/*.rs"));
+        assert!(!env.cache().entry_inputs("cargo build").dependencies.contains(&"src/Main.RS".to_string()));
+
+        // A command can opt out of the default
+        env.write_hint_file("default:\n  case_insensitive_keys: true\ncommands:\n  - pattern: \"cargo build\"\n    case_insensitive_keys: false\n");
+        assert!(!env.cache().get_hint_file().unwrap().case_insensitive_keys("cargo build"));
+        assert!(env.cache().get_hint_file().unwrap().case_insensitive_keys("cargo test"));
+    }
+
+    #[test]
+    fn test_hint_file_with_yaml_extension() {
+        let env = TestEnv::new();
+        env.write_file(".cacher.yaml", "commands:\n  - pattern: \"echo *\"\n    ttl: 42\n");
+        fs::create_dir_all(env.project_dir().join("nested")).unwrap();
+
+        let path = HintFile::find_hint_file_path(&env.project_dir().join("nested")).unwrap();
+        assert_eq!(path, env.project_dir().join(".cacher.yaml"));
+        assert_eq!(env.cache().get_hint_file().unwrap().find_matching_command("echo hi").unwrap().ttl, Some(42));
+
+        // `.cacher` wins when both exist
+        env.write_hint_file("commands: []\n");
+        assert_eq!(HintFile::find_hint_file_path(env.project_dir()).unwrap(), env.project_dir().join(".cacher"));
+    }
+
+    #[test]
+    fn test_powershell_preset() {
+        let env = TestEnv::new();
+        env.write_hint_file("presets:\n  - powershell\n");
+        let cache = env.cache();
+        let hint_file = cache.get_hint_file().unwrap();
+
+        let modules = hint_file.find_matching_command("pwsh -NoProfile -Command Get-Module -ListAvailable").unwrap();
+        assert_eq!(modules.ttl, Some(3600));
+        assert!(modules.include_env.contains("PSModulePath"));
+
+        // PowerShell ignores case, and -Command can be abbreviated
+        assert!(hint_file.find_matching_command("powershell.exe -c get-computerinfo").is_some());
+        assert!(hint_file.find_matching_command("pwsh -command GET-HELP Get-Process -Full").is_some());
+
+        assert!(hint_file.find_matching_command("pwsh -Command Remove-Item build").is_none());
+        assert!(hint_file.find_matching_command("pwsh -Command Get-Module").is_none());
+    }
+}
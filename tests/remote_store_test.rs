@@ -0,0 +1,181 @@
+#[cfg(test)]
+mod tests {
+    use cacher::crypto::Cipher;
+    use cacher::invalidate::InvalidationManifest;
+    use cacher::testing::TestEnv;
+    use cacher::CommandCache;
+    use std::fs;
+    use tempfile::tempdir;
+
+    fn hint_file(remote: &std::path::Path, write: &str) -> String {
+        format!(
+            "remote:\n  path: {}\n  write: {}\ncommands:\n  - pattern: \"echo private*\"\n    write: local-only\n",
+            remote.display(), write,
+        )
+    }
+
+    #[test]
+    fn test_write_through_shares_entries() {
+        let env = TestEnv::new();
+        let remote = tempdir().unwrap();
+        env.write_hint_file(&hint_file(remote.path(), "through"));
+
+        let mut cache = env.cache();
+        let output = cache.execute_and_cache("date +%s%N", None, false).unwrap();
+        let id = cache.generate_id("date +%s%N");
+        assert!(remote.path().join(&id).join("complete").exists());
+
+        // Another machine, with an empty local cache, gets the entry from the remote
+        let other_cache_dir = tempdir().unwrap();
+        let mut other = CommandCache::with_dirs(other_cache_dir.path().to_path_buf(), env.project_dir().to_path_buf());
+        assert_eq!(other.get_cached("date +%s%N", None).unwrap(), output);
+        assert!(other_cache_dir.path().join(&id).join("complete").exists());
+
+        // Commands marked local-only are never uploaded
+        cache.execute_and_cache("echo private", None, false).unwrap();
+        assert!(!remote.path().join(cache.generate_id("echo private")).exists());
+        assert!(other.get_cached("echo private", None).is_none());
+    }
+
+    #[test]
+    fn test_write_back_queues_uploads() {
+        let env = TestEnv::new();
+        let remote = tempdir().unwrap();
+        env.write_hint_file(&hint_file(remote.path(), "back"));
+
+        let mut cache = env.cache();
+        cache.execute_and_cache("echo shared", None, false).unwrap();
+        cache.execute_and_cache("echo gone", None, false).unwrap();
+        let id = cache.generate_id("echo shared");
+        assert!(!remote.path().join(&id).exists());
+
+        // The queue outlives the cache instance that filled it
+        let mut pending = vec![id.clone(), cache.generate_id("echo gone")];
+        pending.sort();
+        assert_eq!(env.cache().pending_uploads().unwrap(), pending);
+
+        // Entries removed in the meantime are dropped from the queue
+        cache.clear_cache(Some("echo gone")).unwrap();
+        let report = env.cache().sync().unwrap();
+        assert_eq!(report.uploaded, 1);
+        assert!(report.failed.is_empty());
+        assert!(remote.path().join(&id).join("complete").exists());
+        assert!(cache.pending_uploads().unwrap().is_empty());
+
+        // The queue isn't mistaken for a broken entry
+        assert_eq!(cache.gc().unwrap(), 0);
+    }
+
+    #[test]
+    fn test_failed_uploads_stay_queued() {
+        let env = TestEnv::new();
+        let blocker = env.write_file("not-a-directory", "");
+        env.write_hint_file(&hint_file(&blocker.join("store"), "back"));
+
+        let mut cache = env.cache();
+        cache.execute_and_cache("echo later", None, false).unwrap();
+
+        let report = cache.sync().unwrap();
+        assert_eq!(report.uploaded, 0);
+        assert_eq!(report.failed.len(), 1);
+        assert_eq!(cache.pending_uploads().unwrap(), vec![cache.generate_id("echo later")]);
+    }
+
+    #[test]
+    fn test_relative_remote_path_and_encrypted_entries() {
+        let env = TestEnv::new();
+        env.write_hint_file("remote:\n  path: shared\ncommands:\n  - pattern: \"echo secret*\"\n    encrypt: true\n");
+        env.write_file("sub/.keep", "");
+        let shared = env.project_dir().join("shared");
+
+        // A relative path is resolved against the hint file, not the directory cacher runs in
+        let mut cache = env.cache_in(&env.project_dir().join("sub"));
+        assert_eq!(cache.remote().unwrap().root(), shared);
+        cache.execute_and_cache("echo shared", None, false).unwrap();
+        assert!(shared.join(cache.generate_id("echo shared")).join("complete").exists());
+
+        // Encrypted entries are never uploaded
+        cache.set_cipher(Cipher::from_key([7; 32]));
+        cache.execute_and_cache("echo secret", None, false).unwrap();
+        let id = cache.generate_id("echo secret");
+        assert!(!shared.join(&id).exists());
+
+        // Nor downloaded, should one end up in the store anyway
+        fs::create_dir_all(shared.join(&id)).unwrap();
+        for file in fs::read_dir(cache.entry_dir(&id)).unwrap().flatten() {
+            if file.path().is_file() {
+                fs::copy(file.path(), shared.join(&id).join(file.file_name())).unwrap();
+            }
+        }
+        let other_cache_dir = tempdir().unwrap();
+        let mut other = CommandCache::with_dirs(other_cache_dir.path().to_path_buf(), env.project_dir().join("sub"));
+        other.set_cipher(Cipher::from_key([8; 32]));
+        assert!(other.get_cached("echo secret", None).is_none());
+        assert!(!other.entry_dir(&id).exists());
+        assert!(other.get_cached("echo shared", None).is_some());
+    }
+
+    #[test]
+    fn test_unreachable_remote_is_not_fatal() {
+        let env = TestEnv::new();
+        let blocker = env.write_file("not-a-directory", "");
+        env.write_hint_file(&hint_file(&blocker.join("store"), "through"));
+
+        let mut cache = env.cache();
+        assert_eq!(cache.execute_and_cache("echo local", None, false).unwrap(), "local\n");
+        assert_eq!(env.cache().get_cached("echo local", None).unwrap(), "local\n");
+    }
+
+    #[test]
+    fn test_invalidate_removes_remote_entries() {
+        let env = TestEnv::new();
+        let remote = tempdir().unwrap();
+        env.write_hint_file(&hint_file(remote.path(), "through"));
+
+        let mut cache = env.cache();
+        cache.execute_and_cache("echo one", None, false).unwrap();
+        cache.execute_and_cache("echo two", None, false).unwrap();
+        // Cached by another machine, so only in the remote store
+        let other_cache_dir = tempdir().unwrap();
+        let mut other = CommandCache::with_dirs(other_cache_dir.path().to_path_buf(), env.project_dir().to_path_buf());
+        other.execute_and_cache("echo three", None, false).unwrap();
+        assert!(!cache.entry_dir(&cache.generate_id("echo three")).exists());
+
+        let manifest = InvalidationManifest::parse("patterns: [\"echo t*\"]\n").unwrap();
+        let mut commands: Vec<String> = cache.invalidation_targets(&manifest).unwrap().into_iter().map(|entry| entry.command).collect();
+        commands.sort();
+        assert_eq!(commands, ["echo three", "echo two"]);
+        assert_eq!(cache.invalidate(&manifest).unwrap().len(), 2);
+
+        for command in ["echo two", "echo three"] {
+            assert!(!remote.path().join(cache.generate_id(command)).exists());
+        }
+        assert!(remote.path().join(cache.generate_id("echo one")).exists());
+
+        // A machine with an empty local cache doesn't get them back
+        let fresh_cache_dir = tempdir().unwrap();
+        let mut fresh = CommandCache::with_dirs(fresh_cache_dir.path().to_path_buf(), env.project_dir().to_path_buf());
+        assert!(fresh.get_cached("echo two", None).is_none());
+        assert!(fresh.get_cached("echo three", None).is_none());
+        assert_eq!(fresh.get_cached("echo one", None).unwrap(), "one\n");
+    }
+
+    #[test]
+    fn test_failed_invalidation_keeps_remote_entries() {
+        let env = TestEnv::new();
+        let remote = tempdir().unwrap();
+        env.write_hint_file(&hint_file(remote.path(), "through"));
+        let mut cache = env.cache();
+        cache.execute_and_cache("echo two", None, false).unwrap();
+        let id = cache.generate_id("echo two");
+
+        // An index that can't be updated fails the invalidation before the remote is touched
+        let _ = fs::remove_file(env.cache_dir().join("index.log"));
+        fs::create_dir(env.cache_dir().join("index.log")).unwrap();
+
+        let manifest = InvalidationManifest::parse("patterns: [\"echo t*\"]\n").unwrap();
+        assert!(cache.invalidate(&manifest).is_err());
+        assert!(cache.entry_dir(&id).join("complete").exists());
+        assert!(remote.path().join(&id).join("complete").exists());
+    }
+}
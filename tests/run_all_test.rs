@@ -0,0 +1,39 @@
+#[cfg(test)]
+mod tests {
+    use cacher::testing::TestEnv;
+    use cacher::batch::parse_task_list;
+
+    #[test]
+    fn test_parse_task_list() {
+        let content = "# build steps\necho first\n\n   echo second  \n";
+        assert_eq!(parse_task_list(content), vec!["echo first", "echo second"]);
+    }
+
+    #[test]
+    fn test_run_all() {
+        let env = TestEnv::new();
+        let cache = env.cache();
+        let commands: Vec<String> = ["echo run_all_one", "echo run_all_two", "false", "echo run_all_three"]
+            .iter()
+            .map(|c| c.to_string())
+            .collect();
+        
+        // Results come back in list order, with failures reported per command
+        let results = cache.run_all(&commands, None, false, 2);
+        assert_eq!(results.len(), 4);
+        assert_eq!(results[0].command, "echo run_all_one");
+        assert_eq!(results[0].result.as_ref().unwrap().trim(), "run_all_one");
+        assert_eq!(results[3].result.as_ref().unwrap().trim(), "run_all_three");
+        assert!(!results[2].success());
+        assert!(results.iter().all(|r| !r.cached));
+        
+        // A second batch is served from the cache the workers wrote to disk
+        let results = cache.run_all(&commands, None, false, 2);
+        assert!(results[0].cached && results[1].cached && results[3].cached);
+        assert!(!results[2].cached);
+        
+        // Forcing runs everything again
+        let results = cache.run_all(&commands, None, true, 4);
+        assert!(results.iter().all(|r| !r.cached));
+    }
+}
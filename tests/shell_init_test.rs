@@ -0,0 +1,194 @@
+#[cfg(test)]
+mod tests {
+    use std::fs;
+    use std::path::Path;
+    use std::process::Command;
+    use tempfile::TempDir;
+    use cacher::hint_file::HintFile;
+    use cacher::process::{command_line, split_command_line};
+    use cacher::shell::{init_script, Shell};
+
+    /// Write an executable script that echoes its name and arguments
+    fn write_fake_program(bin_dir: &Path, name: &str) {
+        let path = bin_dir.join(name);
+        fs::write(&path, format!("#!/bin/sh\necho \"{} $*\"\n", name)).unwrap();
+        
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::PermissionsExt;
+            fs::set_permissions(&path, fs::Permissions::from_mode(0o755)).unwrap();
+        }
+    }
+
+    #[test]
+    fn test_bash_wrappers() {
+        let temp_dir = TempDir::new().unwrap();
+        let bin_dir = temp_dir.path().join("bin");
+        let project_dir = temp_dir.path().join("it's a project");
+        fs::create_dir_all(&bin_dir).unwrap();
+        fs::create_dir_all(&project_dir).unwrap();
+        
+        // Stand-ins for cacher and the wrapped tool, so the test can see which one ran
+        write_fake_program(&bin_dir, "cacher");
+        write_fake_program(&bin_dir, "tool");
+        
+        let hint_file_content = r#"
+default:
+  no_cache_if_env:
+    - CACHER_TEST_SKIP
+
+commands:
+  - pattern: "tool build [ab]*"
+    no_cache_if_file_exists:
+      - .no-cache
+  - pattern: "tool it's"
+  - pattern: "*"
+"#;
+        fs::write(project_dir.join(".cacher"), hint_file_content).unwrap();
+        let hint_file = HintFile::from_file(&project_dir.join(".cacher")).unwrap();
+        
+        let script = init_script(Shell::Bash, &hint_file, &project_dir);
+        assert!(script.contains("# skipped pattern without a literal program: *"));
+        let script_path = temp_dir.path().join("init.sh");
+        fs::write(&script_path, &script).unwrap();
+        
+        let run = |dir: &Path, line: &str| -> String {
+            let output = Command::new("bash")
+                .arg("-c")
+                .arg(format!("source '{}'; {}", script_path.display(), line))
+                .current_dir(dir)
+                .env("PATH", format!("{}:/usr/bin:/bin", bin_dir.display()))
+                .env_remove("CACHER_TEST_SKIP")
+                .output()
+                .unwrap();
+            String::from_utf8_lossy(&output.stdout).trim().to_string()
+        };
+        
+        // Matching commands go through cacher, with quotes and classes matched literally
+        assert_eq!(run(&project_dir, "tool build alpha"), "cacher run -- tool build alpha");
+        assert_eq!(run(&project_dir, "tool \"it's\""), "cacher run -- tool it's");
+        assert_eq!(run(&project_dir, "tool build c"), "tool build c");
+        assert_eq!(run(&project_dir, "tool"), "tool");
+        
+        // no_cache conditions run the tool directly
+        assert_eq!(run(&project_dir, "CACHER_TEST_SKIP=1 tool build alpha"), "tool build alpha");
+        fs::write(project_dir.join(".no-cache"), "").unwrap();
+        assert_eq!(run(&project_dir, "tool build alpha"), "tool build alpha");
+        assert_eq!(run(&project_dir, "tool \"it's\""), "cacher run -- tool it's");
+        
+        // Outside the project the wrapper stays out of the way
+        assert_eq!(run(temp_dir.path(), "tool build alpha"), "tool build alpha");
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_wrapped_commands_are_transparent() {
+        use std::os::unix::fs::{symlink, PermissionsExt};
+        
+        let temp_dir = TempDir::new().unwrap();
+        let bin_dir = temp_dir.path().join("bin");
+        let project_dir = temp_dir.path().join("project");
+        fs::create_dir_all(&bin_dir).unwrap();
+        fs::create_dir_all(&project_dir).unwrap();
+        symlink(env!("CARGO_BIN_EXE_cacher"), bin_dir.join("cacher")).unwrap();
+        
+        // Prints each argument on its own line, warns on stderr and fails when asked to
+        let tool = bin_dir.join("tool");
+        fs::write(&tool, "#!/bin/sh\necho run >> runs.txt\nfor arg in \"$@\"; do printf '[%s]\\n' \"$arg\"; done\nprintf 'no newline'\necho warning >&2\n[ \"$1\" = fail ] && exit 3\nexit 0\n").unwrap();
+        fs::set_permissions(&tool, fs::Permissions::from_mode(0o755)).unwrap();
+        
+        fs::write(project_dir.join(".cacher"), "commands:\n  - pattern: \"tool *\"\n").unwrap();
+        let hint_file = HintFile::from_file(&project_dir.join(".cacher")).unwrap();
+        let script_path = temp_dir.path().join("init.sh");
+        fs::write(&script_path, init_script(Shell::Bash, &hint_file, &project_dir)).unwrap();
+        
+        let run = |line: &str| {
+            Command::new("bash")
+                .arg("-c")
+                .arg(format!("source '{}'; {}", script_path.display(), line))
+                .current_dir(&project_dir)
+                .env("PATH", format!("{}:/usr/bin:/bin", bin_dir.display()))
+                .env("XDG_CACHE_HOME", temp_dir.path().join("cache"))
+                .output()
+                .unwrap()
+        };
+        let runs = || fs::read_to_string(project_dir.join("runs.txt")).unwrap().lines().count();
+        
+        let line = "tool log --format=\"%h %s\" '' \"it's\"";
+        let expected = "[log]\n[--format=%h %s]\n[]\n[it's]\nno newline";
+        for _ in 0..2 {
+            let output = run(line);
+            assert_eq!(String::from_utf8_lossy(&output.stdout), expected);
+            assert_eq!(String::from_utf8_lossy(&output.stderr), "warning\n");
+            assert!(output.status.success());
+        }
+        assert_eq!(runs(), 1);
+        
+        let output = run("tool fail \"a b\"");
+        assert_eq!(String::from_utf8_lossy(&output.stdout), "[fail]\n[a b]\nno newline");
+        assert_eq!(String::from_utf8_lossy(&output.stderr), "warning\n");
+        assert_eq!(output.status.code(), Some(3));
+    }
+
+    #[test]
+    fn test_command_line_round_trip() {
+        let argv: Vec<String> = ["git", "log", "--format=%h %s", "", "it's", "C:\\dir", "a\"b"]
+            .iter()
+            .map(|arg| arg.to_string())
+            .collect();
+        let line = command_line(&argv);
+        assert_eq!(line, "git log '--format=%h %s' '' 'it'\\''s' 'C:\\dir' 'a\"b'");
+        assert_eq!(split_command_line(&line), argv);
+        
+        assert_eq!(split_command_line("ls  -la"), vec!["ls", "-la"]);
+        assert_eq!(split_command_line(r#"echo "a \"b\"" c\ d C:\dir"#), vec!["echo", "a \"b\"", "c d", "C:\\dir"]);
+    }
+
+    #[test]
+    fn test_fish_wrappers() {
+        let temp_dir = TempDir::new().unwrap();
+        let hint_file: HintFile = serde_yaml::from_str(r#"
+commands:
+  - pattern: "make test-[!x]*"
+    no_cache_if_env:
+      - SKIP_CACHE
+      - "NOT A VARIABLE"
+"#).unwrap();
+        
+        let script = init_script(Shell::Fish, &hint_file, temp_dir.path());
+        assert!(script.contains("function make --wraps make"));
+        assert!(script.contains(r"string match -rq -- '^make test\\-[^x].*$' "));
+        assert!(script.contains("if test -n \"$SKIP_CACHE\"\n"));
+        assert!(script.contains("command cacher run -- make $argv"));
+    }
+
+    #[test]
+    fn test_cli_commands_agree_on_quoted_arguments() {
+        let temp_dir = TempDir::new().unwrap();
+        let cacher = |args: &[&str]| {
+            let output = Command::new(env!("CARGO_BIN_EXE_cacher"))
+                .args(args)
+                .current_dir(temp_dir.path())
+                .env("XDG_CACHE_HOME", temp_dir.path().join("cache"))
+                .output()
+                .unwrap();
+            assert!(output.status.success(), "{}", String::from_utf8_lossy(&output.stderr));
+            String::from_utf8(output.stdout).unwrap()
+        };
+        
+        let argv = ["printf", "[%s]", "a b"];
+        let run = |subcommand: &str| cacher(&[&[subcommand], &argv[..]].concat());
+        assert_eq!(run("run"), "[a b]");
+        
+        // Every command derives the same key from argv, and `get` prints the output unchanged
+        let hash = run("hash");
+        assert!(run("explain").contains(&format!("Hash: {}", hash.trim())));
+        assert_eq!(run("get"), "[a b]");
+        let ids: Vec<String> = fs::read_dir(temp_dir.path().join("cache"))
+            .unwrap()
+            .flat_map(|dir| fs::read_dir(dir.unwrap().path()).unwrap())
+            .map(|entry| entry.unwrap().file_name().to_string_lossy().into_owned())
+            .collect();
+        assert!(ids.contains(&hash.trim().to_string()));
+    }
+}
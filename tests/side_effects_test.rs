@@ -0,0 +1,54 @@
+#[cfg(test)]
+mod tests {
+    use cacher::stats::GroupBy;
+    use cacher::testing::TestEnv;
+
+    const HINTS: &str = r#"
+default:
+  ttl: 3600
+commands:
+  - pattern: "sh deploy.sh"
+    side_effects: true
+    history: 5
+"#;
+
+    #[test]
+    fn test_side_effects_always_run() {
+        let env = TestEnv::new();
+        env.write_hint_file(HINTS);
+        env.write_file("deploy.sh", "echo run >> runs.txt\nwc -l < runs.txt\n");
+        let mut cache = env.cache();
+
+        assert!(cache.has_side_effects("sh deploy.sh"));
+        assert_eq!(cache.execute_and_cache("sh deploy.sh", None, false).unwrap().trim(), "1");
+        assert_eq!(cache.execute_and_cache("sh deploy.sh", None, false).unwrap().trim(), "2");
+        assert_eq!(cache.execute_and_cache_with_artifacts("sh deploy.sh", None, false).unwrap().trim(), "3");
+        assert_eq!(env.count_lines("runs.txt"), 3);
+        assert!(cache.get_cached("sh deploy.sh", None).is_none());
+
+        // Every run is still recorded, without counting as a hit
+        let (output, metadata) = cache.load_entry("sh deploy.sh").unwrap().unwrap();
+        assert_eq!(output.stdout.trim(), "3");
+        assert_eq!(metadata.hit_count, 0);
+        let history = cache.history("sh deploy.sh").unwrap();
+        let history: Vec<&str> = history.iter().map(|entry| entry.stdout.trim()).collect();
+        assert_eq!(history, vec!["2", "1"]);
+
+        let rows = cache.stats(&[GroupBy::Pattern]).unwrap();
+        let row = rows.iter().find(|row| row.groups == vec!["sh deploy.sh"]).unwrap();
+        assert_eq!((row.stats.entries, row.stats.hits), (1, 0));
+    }
+
+    #[test]
+    fn test_other_commands_are_still_cached() {
+        let env = TestEnv::new();
+        env.write_hint_file(HINTS);
+        env.write_file("build.sh", "echo run >> runs.txt\necho built\n");
+        let mut cache = env.cache();
+
+        assert!(!cache.has_side_effects("sh build.sh"));
+        cache.execute_and_cache("sh build.sh", None, false).unwrap();
+        cache.execute_and_cache("sh build.sh", None, false).unwrap();
+        assert_eq!(env.count_lines("runs.txt"), 1);
+    }
+}
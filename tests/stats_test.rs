@@ -0,0 +1,92 @@
+#[cfg(test)]
+mod tests {
+    use cacher::metadata::EntryMetadata;
+    use cacher::stats::{self, GroupBy};
+    use cacher::testing::TestEnv;
+    use std::time::{Duration, SystemTime};
+
+    const DAY: u64 = 86400;
+
+    fn entry(project: &str, pattern: Option<&str>, day: u64, hits: u64, duration_ms: u64) -> (EntryMetadata, u64) {
+        let created = SystemTime::UNIX_EPOCH + Duration::from_secs(day * DAY + 3600);
+        let mut metadata = EntryMetadata::new("cmd", created, 0, Duration::from_millis(duration_ms));
+        metadata.project = Some(project.to_string());
+        metadata.inputs.pattern = pattern.map(str::to_string);
+        metadata.hit_count = hits;
+        (metadata, 100)
+    }
+
+    #[test]
+    fn test_rollup() {
+        let entries = vec![
+            entry("b", Some("npm *"), 0, 3, 1000),
+            entry("a", Some("npm *"), 0, 1, 500),
+            entry("a", None, 1, 0, 200),
+        ];
+
+        let rows = stats::rollup(&entries, &[GroupBy::Project, GroupBy::Day]);
+        let groups: Vec<Vec<&str>> = rows.iter().map(|row| row.groups.iter().map(String::as_str).collect()).collect();
+        assert_eq!(groups, vec![
+            vec![],
+            vec!["a"],
+            vec!["a", "1970-01-01"],
+            vec!["a", "1970-01-02"],
+            vec!["b"],
+            vec!["b", "1970-01-01"],
+        ]);
+
+        let total = &rows[0].stats;
+        assert_eq!((total.entries, total.hits, total.size), (3, 4, 300));
+        assert_eq!(total.run_time, Duration::from_millis(1700));
+        assert_eq!(total.saved, Duration::from_millis(3500));
+        assert_eq!(total.hit_rate(), 4.0 / 7.0);
+
+        let project_a = &rows[1].stats;
+        assert_eq!((project_a.entries, project_a.hits), (2, 1));
+
+        let by_pattern = stats::rollup(&entries, &[GroupBy::Pattern]);
+        assert_eq!(by_pattern[1].groups, vec!["(no pattern)"]);
+        assert_eq!(by_pattern[2].groups, vec!["npm *"]);
+        assert_eq!(by_pattern[2].stats.entries, 2);
+    }
+
+    #[test]
+    fn test_csv() {
+        let entries = vec![
+            entry("git:github.com/a/b,c:", Some("npm *"), 0, 3, 1000),
+            entry("a", Some("say \"hi\""), 1, 0, 200),
+        ];
+
+        let rows = stats::rollup(&entries, &[GroupBy::Project, GroupBy::Pattern]);
+        assert_eq!(stats::to_csv(&rows, &[GroupBy::Project, GroupBy::Pattern]), concat!(
+            "project,pattern,entries,hits,hit_rate,size_bytes,run_time_ms,saved_ms\n",
+            "a,\"say \"\"hi\"\"\",1,0,0.0000,100,200,0\n",
+            "\"git:github.com/a/b,c:\",npm *,1,3,0.7500,100,1000,3000\n",
+        ));
+
+        // Without groups, the CSV is the total
+        let rows = stats::rollup(&entries, &[]);
+        assert_eq!(stats::to_csv(&rows, &[]), "entries,hits,hit_rate,size_bytes,run_time_ms,saved_ms\n2,3,0.6000,200,1200,3000\n");
+    }
+
+    #[test]
+    fn test_stats_of_cache() {
+        let env = TestEnv::new();
+        env.write_hint_file("commands:\n  - pattern: \"echo *\"\n    ttl: 60\n");
+        let mut cache = env.cache();
+
+        cache.execute_and_cache("echo one", None, false).unwrap();
+        cache.execute_and_cache("echo two", None, false).unwrap();
+        env.cache().get_cached("echo one", None).unwrap();
+
+        let rows = cache.stats(&[GroupBy::Project, GroupBy::Pattern]).unwrap();
+        assert_eq!(rows.len(), 3);
+        assert_eq!(rows[1].groups, vec![cache.project_id().to_string()]);
+        assert_eq!(rows[2].groups, vec![cache.project_id().to_string(), "echo *".to_string()]);
+        assert_eq!((rows[2].stats.entries, rows[2].stats.hits), (2, 1));
+        assert!(rows[2].stats.size > 0);
+
+        assert_eq!("day".parse::<GroupBy>(), Ok(GroupBy::Day));
+        assert!("week".parse::<GroupBy>().is_err());
+    }
+}
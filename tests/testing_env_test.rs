@@ -0,0 +1,29 @@
+#[cfg(test)]
+mod tests {
+    use cacher::testing::TestEnv;
+
+    #[test]
+    fn test_env_is_isolated_and_restored() {
+        std::env::remove_var("CACHER_TEST_GUARD_VAR");
+        
+        let cache_dir = {
+            let mut env = TestEnv::new();
+            env.set_env("CACHER_TEST_GUARD_VAR", "inside");
+            env.write_hint_file("commands:\n  - pattern: \"echo *\"\n    ttl: 60\n");
+            
+            // Commands run in the project directory and are cached in the temporary cache
+            let mut cache = env.cache();
+            let output = cache.execute_and_cache("pwd", None, false).unwrap();
+            assert_eq!(output.trim(), env.project_dir().canonicalize().unwrap().to_str().unwrap());
+            assert!(cache.get_hint_file().is_some());
+            assert_eq!(cache.list_cached_commands(&cacher::ListOptions::default()).unwrap().len(), 1);
+            assert_eq!(std::env::var("CACHER_TEST_GUARD_VAR").unwrap(), "inside");
+            
+            env.cache_dir().to_path_buf()
+        };
+        
+        // Dropping the guard removes its directories and restores the environment
+        assert!(!cache_dir.exists());
+        assert!(std::env::var_os("CACHER_TEST_GUARD_VAR").is_none());
+    }
+}
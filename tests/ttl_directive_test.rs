@@ -0,0 +1,63 @@
+#[cfg(test)]
+mod tests {
+    use cacher::clock::ManualClock;
+    use cacher::directive;
+    use cacher::testing::TestEnv;
+    use std::sync::Arc;
+    use std::time::Duration;
+
+    const HINTS: &str = "default:\n  ttl: 3600\ncommands:\n  - pattern: \"sh *\"\n    ttl_from_output: true\n";
+
+    #[test]
+    fn test_take_ttl() {
+        assert_eq!(directive::take_ttl("data\n#cacher: ttl=300\n"), Some(("data\n".to_string(), Duration::from_secs(300))));
+        assert_eq!(directive::take_ttl("data\n  #cacher:ttl=10m"), Some(("data\n".to_string(), Duration::from_secs(600))));
+        assert_eq!(directive::take_ttl("#cacher: ttl=5\r\n"), Some((String::new(), Duration::from_secs(5))));
+
+        // Only a valid directive on the last line counts
+        assert_eq!(directive::take_ttl("#cacher: ttl=300\ndata\n"), None);
+        assert_eq!(directive::take_ttl("data\n#cacher: ttl=soon\n"), None);
+        assert_eq!(directive::take_ttl("data\n#cacher: pin\n"), None);
+        assert_eq!(directive::take_ttl("data\n"), None);
+    }
+
+    #[test]
+    fn test_directive_sets_entry_ttl() {
+        let env = TestEnv::new();
+        env.write_hint_file(HINTS);
+        env.write_file("script.sh", "echo run >> runs.txt\necho data\necho '#cacher: ttl=60'\n");
+        let clock = ManualClock::default();
+        let mut cache = env.cache();
+        cache.set_clock(Arc::new(clock.clone()));
+
+        assert_eq!(cache.execute_and_cache("sh script.sh", None, false).unwrap(), "data\n");
+        let (output, metadata) = cache.load_entry("sh script.sh").unwrap().unwrap();
+        assert_eq!(output.stdout, "data\n");
+        assert_eq!(metadata.ttl, Some(60));
+
+        clock.advance(Duration::from_secs(30));
+        assert_eq!(cache.execute_and_cache("sh script.sh", None, false).unwrap(), "data\n");
+        assert_eq!(env.count_lines("runs.txt"), 1);
+        let expires_in = cache.entry_summary("sh script.sh", None).unwrap().unwrap().expires_in.unwrap();
+        assert!(expires_in <= Duration::from_secs(30) && expires_in > Duration::from_secs(28));
+
+        // The directive overrides the hint file's hour, in memory and on disk
+        clock.advance(Duration::from_secs(31));
+        assert!(cache.get_cached("sh script.sh", None).is_none());
+        let mut fresh_cache = env.cache();
+        fresh_cache.set_clock(Arc::new(clock.clone()));
+        assert!(fresh_cache.get_cached("sh script.sh", None).is_none());
+    }
+
+    #[test]
+    fn test_directive_needs_opt_in() {
+        let env = TestEnv::new();
+        env.write_hint_file(HINTS);
+        env.write_file("script.sh", "echo data\necho '#cacher: ttl=60'\n");
+        let mut cache = env.cache();
+
+        // `bash` doesn't match the pattern enabling directives
+        assert_eq!(cache.execute_and_cache("bash script.sh", None, false).unwrap(), "data\n#cacher: ttl=60\n");
+        assert_eq!(cache.load_entry("bash script.sh").unwrap().unwrap().1.ttl, None);
+    }
+}